@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use systemd_unit::{SystemdUnit, SystemdUnitRef};
+
+const SERVICE: &str = r#"# /usr/lib/systemd/system/example.service
+[Unit]
+Description=Example service with a somewhat realistic amount of settings
+Documentation=man:example(8) https://example.org/docs
+After=network-online.target remote-fs.target nss-lookup.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+Environment="LANG=C.UTF-8" "FOO=bar baz"
+ExecStartPre=/usr/bin/example --check-config \
+    --config=/etc/example/example.conf
+ExecStart=/usr/bin/example --foreground --config=/etc/example/example.conf
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+ProtectSystem=strict
+ProtectHome=yes
+PrivateTmp=yes
+NoNewPrivileges=yes
+StateDirectory=example
+
+[Install]
+WantedBy=multi-user.target
+"#;
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(SERVICE.len() as u64));
+
+    group.bench_function("owned", |b| {
+        b.iter(|| SystemdUnit::load_from_str(black_box(SERVICE)).unwrap())
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| SystemdUnitRef::load_from_str(black_box(SERVICE)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
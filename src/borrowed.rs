@@ -0,0 +1,206 @@
+use ordered_multimap::ListOrderedMultimap;
+use std::borrow::Cow;
+use super::{
+    parse_bool, parser, unquote_value, unquote_value_relaxed, EntryValue, Error, SystemdUnit,
+    UnquoteMode,
+};
+
+/// A unit borrowing its keys and values from the parsed input.
+///
+/// Keys and values are only copied if line continuations force it, unquoting is deferred until a
+/// value is looked up. Use [`SystemdUnitRef::to_unit()`] to get an owned [`SystemdUnit`].
+#[derive(Debug, Default, PartialEq)]
+pub struct SystemdUnitRef<'a> {
+    pub(crate) sections: ListOrderedMultimap<Cow<'a, str>, EntriesRef<'a>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntriesRef<'a> {
+    pub data: ListOrderedMultimap<Cow<'a, str>, EntryValueRef<'a>>,
+}
+
+/// A borrowed raw value, which is unquoted lazily
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryValueRef<'a> {
+    raw: Cow<'a, str>,
+}
+
+impl<'a> EntryValueRef<'a> {
+    pub fn from_raw<S: Into<Cow<'a, str>>>(raw: S) -> Self {
+        Self { raw: raw.into() }
+    }
+
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn to_bool(&self) -> Result<bool, Error> {
+        let trimmed = self.raw.trim();
+        if trimmed.is_empty() {
+            return Ok(false);
+        }
+
+        parse_bool(trimmed)
+    }
+
+    /// Unquote the value, borrowing it if there's nothing to unquote
+    pub fn try_unquote(&self) -> Result<Cow<'_, str>, Error> {
+        if self.raw.contains(['\\', '"', '\'']) {
+            unquote_value(&self.raw).map(Cow::Owned)
+        } else {
+            Ok(Cow::Borrowed(&self.raw))
+        }
+    }
+
+    pub fn unquote_with(&self, mode: UnquoteMode) -> Result<Cow<'_, str>, Error> {
+        match mode {
+            UnquoteMode::Strict => self.try_unquote(),
+            UnquoteMode::Relaxed if self.raw.contains(['\\', '"', '\'']) => {
+                Ok(Cow::Owned(unquote_value_relaxed(&self.raw)))
+            }
+            UnquoteMode::Relaxed => Ok(Cow::Borrowed(&self.raw)),
+        }
+    }
+
+    pub fn to_value(&self) -> Result<EntryValue, Error> {
+        EntryValue::try_from_raw(self.raw.as_ref())
+    }
+}
+
+impl<'a> SystemdUnitRef<'a> {
+    pub fn has_key(&self, section: &str, key: &str) -> bool {
+        self.sections
+            .get(section)
+            .is_some_and(|e| e.data.contains_key(key))
+    }
+
+    /// Retrun `true` if there's an (non-empty) instance of section `name`
+    pub fn has_section(&self, name: &str) -> bool {
+        self.sections.contains_key(name)
+    }
+
+    /// Return `true` if there are no sections
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Number of unique sections (i.e. with different names)
+    pub fn len(&self) -> usize {
+        self.sections.keys_len()
+    }
+
+    /// Load from a string without copying keys and values
+    ///
+    /// NOTE: contrary to [`SystemdUnit::load_from_str()`] values aren't validated until they are unquoted.
+    pub fn load_from_str(data: &'a str) -> Result<Self, Error> {
+        let mut parser = parser::Parser::new(data);
+        let unit = parser.parse_borrowed()?;
+
+        Ok(unit)
+    }
+
+    /// Get an interator of values for all `key`s in all instances of `section`
+    pub fn lookup_all_values<'s>(
+        &'s self,
+        section: &str,
+        key: &str,
+    ) -> impl DoubleEndedIterator<Item = &'s EntryValueRef<'a>> {
+        self.sections
+            .get(section)
+            .into_iter()
+            .flat_map(move |e| e.data.get_all(key))
+    }
+
+    // Get the last value for `key` in all instances of `section`
+    pub fn lookup_last_value(&self, section: &str, key: &str) -> Option<&EntryValueRef<'a>> {
+        self.lookup_all_values(section, key).next_back()
+    }
+
+    pub fn section_entry_values<'s>(
+        &'s self,
+        name: &str,
+    ) -> impl DoubleEndedIterator<Item = (&'s str, &'s EntryValueRef<'a>)> {
+        self.sections
+            .get(name)
+            .into_iter()
+            .flat_map(|e| e.data.iter())
+            .map(|(k, v)| (k.as_ref(), v))
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(|k| k.as_ref())
+    }
+
+    /// Copy into an owned [`SystemdUnit`], unquoting all values
+    pub fn to_unit(&self) -> Result<SystemdUnit, Error> {
+        let mut unit = SystemdUnit::new();
+
+        for (section, entries) in self.sections.iter() {
+            unit.sections
+                .entry(section.to_string())
+                .or_insert(Default::default());
+            for (key, value) in entries.data.iter() {
+                unit.append_entry_value(section.as_ref(), key.as_ref(), value.to_value()?);
+            }
+        }
+
+        Ok(unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn borrows() {
+        let unit = SystemdUnitRef::load_from_str("[Section]\nkey=value\ncontinued=a\\\nb\n").unwrap();
+        assert!(matches!(unit.sections.keys().next(), Some(Cow::Borrowed("Section"))));
+
+        let value = unit.lookup_last_value("Section", "key").unwrap();
+        assert!(matches!(value.raw, Cow::Borrowed("value")));
+        // a line continuation has to be copied
+        let value = unit.lookup_last_value("Section", "continued").unwrap();
+        assert!(matches!(&value.raw, Cow::Owned(v) if v == "a b"));
+    }
+
+    #[test]
+    fn unquote() {
+        let unit = SystemdUnitRef::load_from_str("[Section]\nplain=a b\nquoted=\"a  b\" c\n").unwrap();
+        let plain = unit.lookup_last_value("Section", "plain").unwrap();
+        assert!(matches!(plain.try_unquote(), Ok(Cow::Borrowed("a b"))));
+        let quoted = unit.lookup_last_value("Section", "quoted").unwrap();
+        assert_eq!(quoted.try_unquote().unwrap(), "a  b c");
+        assert_eq!(quoted.unquote_with(UnquoteMode::Relaxed).unwrap(), "a  b c");
+    }
+
+    #[test]
+    fn invalid_value() {
+        // values are only validated when they're unquoted
+        let unit = SystemdUnitRef::load_from_str("[Section]\nkey=\\x4g\n").unwrap();
+        let value = unit.lookup_last_value("Section", "key").unwrap();
+        assert_eq!(value.try_unquote().unwrap_err().kind(), ErrorKind::Escape);
+        assert_eq!(value.unquote_with(UnquoteMode::Relaxed).unwrap(), "\\x4g");
+        assert_eq!(unit.to_unit().unwrap_err().kind(), ErrorKind::Escape);
+    }
+
+    #[test]
+    fn to_bool() {
+        let unit = SystemdUnitRef::load_from_str("[Section]\nyes=yes\nempty=\ninvalid=maybe\n").unwrap();
+        let value = |key| unit.lookup_last_value("Section", key).unwrap();
+        assert!(value("yes").to_bool().unwrap());
+        assert!(!value("empty").to_bool().unwrap());
+        assert_eq!(value("invalid").to_bool().unwrap_err().kind(), ErrorKind::Bool);
+    }
+
+    #[test]
+    fn lookup() {
+        let unit = SystemdUnitRef::load_from_str("[A]\nk=1\n[B]\nk=2\n[A]\nk=3\n").unwrap();
+        assert_eq!(unit.len(), 2);
+        assert_eq!(unit.sections().collect::<Vec<_>>(), ["A", "B"]);
+        let values: Vec<&str> = unit.lookup_all_values("A", "k").map(EntryValueRef::raw).collect();
+        assert_eq!(values, ["1", "3"]);
+        assert!(unit.has_key("B", "k") && !unit.has_key("B", "x") && !unit.has_section("C"));
+    }
+}
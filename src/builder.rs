@@ -0,0 +1,507 @@
+use std::fmt;
+use super::*;
+
+/// The `Type=` of a service
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ServiceType {
+    Simple,
+    Exec,
+    Forking,
+    Oneshot,
+    Dbus,
+    Notify,
+    NotifyReload,
+    Idle,
+}
+
+impl fmt::Display for ServiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ServiceType::Simple => "simple",
+            ServiceType::Exec => "exec",
+            ServiceType::Forking => "forking",
+            ServiceType::Oneshot => "oneshot",
+            ServiceType::Dbus => "dbus",
+            ServiceType::Notify => "notify",
+            ServiceType::NotifyReload => "notify-reload",
+            ServiceType::Idle => "idle",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The `Restart=` of a service
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Restart {
+    No,
+    Always,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+    OnAbort,
+    OnWatchdog,
+}
+
+impl fmt::Display for Restart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Restart::No => "no",
+            Restart::Always => "always",
+            Restart::OnSuccess => "on-success",
+            Restart::OnFailure => "on-failure",
+            Restart::OnAbnormal => "on-abnormal",
+            Restart::OnAbort => "on-abort",
+            Restart::OnWatchdog => "on-watchdog",
+        };
+        f.write_str(s)
+    }
+}
+
+// see unit_name_is_valid() in systemd
+fn is_valid_unit_name(name: &str) -> bool {
+    let Some((stem, _)) = name.rsplit_once('.') else {
+        return false;
+    };
+    name.len() <= 255
+        && !stem.is_empty()
+        && !stem.starts_with('@')
+        && stem.matches('@').count() <= 1
+        && stem.chars().all(|c| c.is_ascii_alphanumeric() || ":-_.\\@".contains(c))
+}
+
+fn bool_value(value: bool) -> EntryValue {
+    EntryValue::from_raw(if value { "yes" } else { "no" })
+}
+
+fn words<I, S>(words: I) -> EntryValue
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let words: Vec<S> = words.into_iter().collect();
+    EntryValue::from_raw(quote_words(words.iter().map(AsRef::as_ref)))
+}
+
+/// Builds a unit of a given type, only accepting keys valid for that type
+///
+/// E.g. `UnitBuilder::service("foo").description("Foo").exec_start(["/usr/bin/foo"]).wanted_by("multi-user.target").build()`
+#[derive(Debug)]
+pub struct UnitBuilder {
+    name: String,
+    unit_type: UnitType,
+    unit: SystemdUnit,
+    errors: Vec<String>,
+}
+
+impl UnitBuilder {
+    /// Build unit `name`, the suffix of `unit_type` is added unless `name` already has it
+    ///
+    /// A name with the suffix of another unit type (e.g. `foo.socket` for a service) fails the build.
+    pub fn new(unit_type: UnitType, name: &str) -> Self {
+        let mut errors = Vec::new();
+        let name_type = UnitType::ALL
+            .into_iter()
+            .find(|t| name.strip_suffix(t.suffix()).is_some_and(|stem| stem.ends_with('.')));
+        let name = match name_type {
+            Some(t) if t == unit_type => name.to_string(),
+            Some(t) => {
+                errors.push(format!("a {} unit can't be named {name:?} (a {} name)", unit_type.suffix(), t.suffix()));
+                name.to_string()
+            }
+            None => format!("{name}.{}", unit_type.suffix()),
+        };
+
+        Self {
+            name,
+            unit_type,
+            unit: SystemdUnit::new(),
+            errors,
+        }
+    }
+
+    pub fn service(name: &str) -> Self {
+        Self::new(UnitType::Service, name)
+    }
+
+    pub fn socket(name: &str) -> Self {
+        Self::new(UnitType::Socket, name)
+    }
+
+    pub fn target(name: &str) -> Self {
+        Self::new(UnitType::Target, name)
+    }
+
+    pub fn timer(name: &str) -> Self {
+        Self::new(UnitType::Timer, name)
+    }
+
+    pub fn path(name: &str) -> Self {
+        Self::new(UnitType::Path, name)
+    }
+
+    /// The unit name, including its suffix
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unit_type(&self) -> UnitType {
+        self.unit_type
+    }
+
+    /// Append `key=value` to `section`, the value is quoted as needed
+    pub fn entry<V: AsRef<str>>(self, section: &str, key: &str, value: V) -> Self {
+        self.entry_value(section, key, EntryValue::from_unquoted(value.as_ref()))
+    }
+
+    /// Append `key=value` to `section`, failing the build if the key isn't valid there
+    pub fn entry_value(mut self, section: &str, key: &str, value: EntryValue) -> Self {
+        if !self.unit_type.is_known_section(section) {
+            self.errors
+                .push(format!("section [{section}] isn't valid in {} units", self.unit_type));
+        } else if !self.unit_type.is_known_key(section, key) {
+            self.errors
+                .push(format!("{key}= isn't valid in section [{section}] of {} units", self.unit_type));
+        } else {
+            self.unit.append_entry_value(section, key, value);
+        }
+        self
+    }
+
+    // append to the type specific section (e.g. `[Service]`)
+    fn type_entry(mut self, key: &str, value: EntryValue) -> Self {
+        match self.unit_type.section() {
+            Some(section) => self.entry_value(section, key, value),
+            None => {
+                self.errors.push(format!("{key}= isn't valid in {} units", self.unit_type));
+                self
+            }
+        }
+    }
+
+    // [Unit]
+
+    pub fn description<V: AsRef<str>>(self, description: V) -> Self {
+        self.entry(UNIT_SECTION, "Description", description)
+    }
+
+    pub fn documentation<V: AsRef<str>>(self, url: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Documentation", words([url]))
+    }
+
+    pub fn requires<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Requires", words([unit]))
+    }
+
+    pub fn requisite<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Requisite", words([unit]))
+    }
+
+    pub fn wants<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Wants", words([unit]))
+    }
+
+    pub fn binds_to<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "BindsTo", words([unit]))
+    }
+
+    pub fn part_of<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "PartOf", words([unit]))
+    }
+
+    pub fn conflicts<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Conflicts", words([unit]))
+    }
+
+    pub fn after<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "After", words([unit]))
+    }
+
+    pub fn before<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(UNIT_SECTION, "Before", words([unit]))
+    }
+
+    pub fn default_dependencies(self, enabled: bool) -> Self {
+        self.entry_value(UNIT_SECTION, "DefaultDependencies", bool_value(enabled))
+    }
+
+    // [Install]
+
+    pub fn wanted_by<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(INSTALL_SECTION, "WantedBy", words([unit]))
+    }
+
+    pub fn required_by<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(INSTALL_SECTION, "RequiredBy", words([unit]))
+    }
+
+    pub fn alias<V: AsRef<str>>(self, name: V) -> Self {
+        self.entry_value(INSTALL_SECTION, "Alias", words([name]))
+    }
+
+    pub fn also<V: AsRef<str>>(self, unit: V) -> Self {
+        self.entry_value(INSTALL_SECTION, "Also", words([unit]))
+    }
+
+    pub fn default_instance<V: AsRef<str>>(self, instance: V) -> Self {
+        self.entry(INSTALL_SECTION, "DefaultInstance", instance)
+    }
+
+    // [Service], [Socket], [Mount] and [Swap]
+
+    pub fn service_type(self, service_type: ServiceType) -> Self {
+        self.type_entry("Type", EntryValue::from_raw(service_type.to_string()))
+    }
+
+    /// `ExecStart=` with the command line `argv`, whose first word may have prefixes like `-`
+    pub fn exec_start<I, S>(self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.type_entry("ExecStart", words(argv))
+    }
+
+    pub fn exec_start_pre<I, S>(self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.type_entry("ExecStartPre", words(argv))
+    }
+
+    pub fn exec_start_post<I, S>(self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.type_entry("ExecStartPost", words(argv))
+    }
+
+    pub fn exec_reload<I, S>(self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.type_entry("ExecReload", words(argv))
+    }
+
+    pub fn exec_stop<I, S>(self, argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.type_entry("ExecStop", words(argv))
+    }
+
+    pub fn restart(self, restart: Restart) -> Self {
+        self.type_entry("Restart", EntryValue::from_raw(restart.to_string()))
+    }
+
+    /// `RestartSec=`, a time span like `5s`
+    pub fn restart_sec<V: AsRef<str>>(self, span: V) -> Self {
+        self.type_entry("RestartSec", EntryValue::from_unquoted(span.as_ref()))
+    }
+
+    pub fn remain_after_exit(self, enabled: bool) -> Self {
+        self.type_entry("RemainAfterExit", bool_value(enabled))
+    }
+
+    pub fn pid_file<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("PIDFile", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    pub fn user<V: AsRef<str>>(self, user: V) -> Self {
+        self.type_entry("User", EntryValue::from_unquoted(user.as_ref()))
+    }
+
+    pub fn group<V: AsRef<str>>(self, group: V) -> Self {
+        self.type_entry("Group", EntryValue::from_unquoted(group.as_ref()))
+    }
+
+    pub fn working_directory<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("WorkingDirectory", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    /// `Environment=` setting variable `name` to `value`
+    pub fn environment<N: AsRef<str>, V: AsRef<str>>(self, name: N, value: V) -> Self {
+        let assignment = format!("{}={}", name.as_ref(), value.as_ref());
+        self.type_entry("Environment", words([assignment]))
+    }
+
+    pub fn environment_file<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("EnvironmentFile", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    // [Socket]
+
+    /// `ListenStream=`, an address like `/run/foo.sock` or `[::]:80`
+    pub fn listen_stream<V: AsRef<str>>(self, address: V) -> Self {
+        self.type_entry("ListenStream", EntryValue::from_unquoted(address.as_ref()))
+    }
+
+    pub fn listen_datagram<V: AsRef<str>>(self, address: V) -> Self {
+        self.type_entry("ListenDatagram", EntryValue::from_unquoted(address.as_ref()))
+    }
+
+    pub fn accept(self, enabled: bool) -> Self {
+        self.type_entry("Accept", bool_value(enabled))
+    }
+
+    // [Timer]
+
+    /// `OnCalendar=`, a calendar event like `daily` or `Mon *-*-* 04:00`
+    pub fn on_calendar<V: AsRef<str>>(self, event: V) -> Self {
+        self.type_entry("OnCalendar", EntryValue::from_unquoted(event.as_ref()))
+    }
+
+    pub fn on_boot_sec<V: AsRef<str>>(self, span: V) -> Self {
+        self.type_entry("OnBootSec", EntryValue::from_unquoted(span.as_ref()))
+    }
+
+    pub fn on_unit_active_sec<V: AsRef<str>>(self, span: V) -> Self {
+        self.type_entry("OnUnitActiveSec", EntryValue::from_unquoted(span.as_ref()))
+    }
+
+    pub fn persistent(self, enabled: bool) -> Self {
+        self.type_entry("Persistent", bool_value(enabled))
+    }
+
+    // [Path]
+
+    pub fn path_exists<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("PathExists", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    pub fn path_changed<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("PathChanged", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    pub fn path_modified<V: AsRef<str>>(self, path: V) -> Self {
+        self.type_entry("PathModified", EntryValue::from_unquoted(path.as_ref()))
+    }
+
+    /// The unit a timer, path or socket (`Service=`) activates, instead of the one with the same name
+    pub fn activates<V: AsRef<str>>(self, unit: V) -> Self {
+        let key = match self.unit_type {
+            UnitType::Socket => "Service",
+            _ => "Unit",
+        };
+        self.type_entry(key, EntryValue::from_unquoted(unit.as_ref()))
+    }
+
+    // the keys units of this type can't do without, see e.g. timer_verify() in systemd
+    fn missing_settings(&self) -> Option<String> {
+        let section = self.unit_type.section()?;
+        let (keys, what): (&[&str], _) = match self.unit_type {
+            UnitType::Timer => (
+                &["OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "OnCalendar"],
+                "timer",
+            ),
+            UnitType::Socket => (
+                &[
+                    "ListenStream", "ListenDatagram", "ListenSequentialPacket", "ListenFIFO", "ListenSpecial",
+                    "ListenNetlink", "ListenMessageQueue", "ListenUSBFunction",
+                ],
+                "Listen*=",
+            ),
+            UnitType::Path => (
+                &["PathExists", "PathExistsGlob", "PathChanged", "PathModified", "DirectoryNotEmpty"],
+                "path",
+            ),
+            _ => return None,
+        };
+
+        let missing = keys
+            .iter()
+            .all(|key| self.unit.lookup_all_with_reset(section, *key).is_empty());
+        missing.then(|| format!("{} units need a {what} setting", self.unit_type))
+    }
+
+    /// Build the unit, failing with all invalid keys and combinations (e.g. `Restart=always` with `Type=oneshot`)
+    pub fn build(self) -> Result<SystemdUnit, Error> {
+        let mut problems = Vec::new();
+        if !is_valid_unit_name(&self.name) {
+            problems.push(format!("{:?} isn't a valid unit name", self.name));
+        }
+        problems.extend(self.errors.iter().cloned());
+        problems.extend(self.missing_settings());
+        let report = Linter::new().lint_named(&self.name, &self.unit);
+        problems.extend(
+            report
+                .diagnostics
+                .into_iter()
+                .filter(|d| d.severity == Severity::Error)
+                .map(|d| d.message),
+        );
+
+        if !problems.is_empty() {
+            return Err(Error::new(
+                ErrorKind::Validation,
+                format!("{}: {}", self.name, problems.join("; ")),
+            ));
+        }
+        Ok(self.unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oneshot_restart() {
+        let build = |restart| {
+            UnitBuilder::service("x")
+                .service_type(ServiceType::Oneshot)
+                .exec_start(["/bin/true"])
+                .restart(restart)
+                .build()
+        };
+
+        for restart in [Restart::No, Restart::OnFailure, Restart::OnAbnormal, Restart::OnAbort, Restart::OnWatchdog] {
+            assert!(build(restart).is_ok(), "Restart={restart}");
+        }
+        for restart in [Restart::Always, Restart::OnSuccess] {
+            let err = build(restart).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Validation, "Restart={restart}");
+            assert!(err.message().starts_with("x.service: "), "{err}");
+        }
+    }
+
+    #[test]
+    fn name() {
+        assert_eq!(UnitBuilder::service("foo").name(), "foo.service");
+        assert_eq!(UnitBuilder::service("foo.service").name(), "foo.service");
+        assert_eq!(UnitBuilder::service("foo.bar").name(), "foo.bar.service");
+
+        let err = UnitBuilder::new(UnitType::Service, "foo.socket")
+            .exec_start(["/bin/true"])
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Validation);
+        assert!(err.message().contains("foo.socket"), "{err}");
+    }
+
+    #[test]
+    fn build() {
+        let unit = UnitBuilder::service("foo")
+            .description("Foo")
+            .after("network.target")
+            .exec_start(["/usr/bin/foo", "--flag", "a b"])
+            .wanted_by("multi-user.target")
+            .build()
+            .unwrap();
+        assert_eq!(unit.lookup_last(UNIT_SECTION, "Description").as_deref(), Some("Foo"));
+        assert_eq!(unit.lookup_last_value(SERVICE_SECTION, "ExecStart").unwrap().raw(), "/usr/bin/foo --flag \"a b\"");
+        assert_eq!(unit.lookup_last(INSTALL_SECTION, "WantedBy").as_deref(), Some("multi-user.target"));
+    }
+
+    #[test]
+    fn rejects() {
+        let err = UnitBuilder::service("foo").entry(SERVICE_SECTION, "ExecStrat", "/bin/true").build().unwrap_err();
+        assert!(err.message().contains("ExecStrat"), "{err}");
+
+        assert!(UnitBuilder::timer("foo").description("no trigger").build().is_err());
+        assert!(UnitBuilder::service("foo").description("no ExecStart=").build().is_err());
+    }
+}
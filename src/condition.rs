@@ -0,0 +1,623 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use super::*;
+
+// virtualization IDs of containers (see `systemd-detect-virt --list`)
+const CONTAINERS: &[&str] = &[
+    "openvz", "lxc", "lxc-libvirt", "systemd-nspawn", "docker", "podman", "rkt", "wsl", "proot", "pouch",
+];
+
+/// The machine `Condition…=` and `Assert…=` settings are evaluated on
+///
+/// Checks without a method here (e.g. `ConditionPathIsMountPoint=` or `ConditionMemory=`) are passed to
+/// [`ConditionEnvironment::check()`].
+pub trait ConditionEnvironment {
+    /// The root of the file system, paths in path checks are relative to it
+    fn root(&self) -> &Path;
+    fn kernel_command_line(&self) -> &str;
+    fn kernel_version(&self) -> &str;
+    fn hostname(&self) -> &str;
+    fn machine_id(&self) -> Option<&str>;
+    /// The architecture as named by systemd (e.g. `x86-64` or `arm64`)
+    fn architecture(&self) -> &str;
+    /// The virtualization as named by `systemd-detect-virt` (e.g. `kvm` or `docker`), `None` on bare metal
+    fn virtualization(&self) -> Option<&str>;
+    /// The value of environment variable `name` of the service manager
+    fn env_var(&self, name: &str) -> Option<&str>;
+    fn is_first_boot(&self) -> bool;
+
+    /// The fields of the `os-release` file below [`ConditionEnvironment::root()`]
+    fn os_release(&self) -> BTreeMap<String, String> {
+        ["etc/os-release", "usr/lib/os-release"]
+            .iter()
+            .find_map(|p| fs::read_to_string(self.root().join(p)).ok())
+            .map(|data| parse_os_release(&data))
+            .unwrap_or_default()
+    }
+
+    /// Evaluate any other check, `None` if the result can't be known
+    fn check(&self, _check: &str, _value: &str) -> Option<bool> {
+        None
+    }
+}
+
+/// A [`ConditionEnvironment`] described by its fields, e.g. to predict how a unit behaves on another machine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineProfile {
+    pub root: PathBuf,
+    pub kernel_command_line: String,
+    pub kernel_version: String,
+    pub hostname: String,
+    pub machine_id: Option<String>,
+    pub architecture: String,
+    pub virtualization: Option<String>,
+    pub environment: BTreeMap<String, String>,
+    pub first_boot: bool,
+    /// Results of other checks by name (e.g. `PathIsMountPoint`) and value
+    pub checks: BTreeMap<(String, String), bool>,
+}
+
+impl Default for MachineProfile {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            kernel_command_line: String::new(),
+            kernel_version: String::new(),
+            hostname: "localhost".into(),
+            machine_id: None,
+            architecture: native_architecture().into(),
+            virtualization: None,
+            environment: BTreeMap::new(),
+            first_boot: false,
+            checks: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConditionEnvironment for MachineProfile {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn kernel_command_line(&self) -> &str {
+        &self.kernel_command_line
+    }
+
+    fn kernel_version(&self) -> &str {
+        &self.kernel_version
+    }
+
+    fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    fn machine_id(&self) -> Option<&str> {
+        self.machine_id.as_deref()
+    }
+
+    fn architecture(&self) -> &str {
+        &self.architecture
+    }
+
+    fn virtualization(&self) -> Option<&str> {
+        self.virtualization.as_deref()
+    }
+
+    fn env_var(&self, name: &str) -> Option<&str> {
+        self.environment.get(name).map(String::as_str)
+    }
+
+    fn is_first_boot(&self) -> bool {
+        self.first_boot
+    }
+
+    fn check(&self, check: &str, value: &str) -> Option<bool> {
+        self.checks.get(&(check.to_string(), value.to_string())).copied()
+    }
+}
+
+/// The architecture this crate is compiled for, as named by systemd
+fn native_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x86-64",
+        "aarch64" => "arm64",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64-le",
+        "powerpc" => "ppc",
+        "powerpc64" => "ppc64",
+        "sparc64" => "sparc64",
+        "mips" => "mips",
+        arch => arch,
+    }
+}
+
+fn parse_os_release(data: &str) -> BTreeMap<String, String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), unquote_value_relaxed(v.trim())))
+        .collect()
+}
+
+/// Match `s` against shell-style `pattern` with `*`, `?` and `[…]` like `fnmatch()`
+pub(crate) fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    fn matches(p: &[char], s: &[char]) -> bool {
+        match p.first() {
+            None => s.is_empty(),
+            Some('*') => (0..=s.len()).any(|i| matches(&p[1..], &s[i..])),
+            Some('?') => !s.is_empty() && matches(&p[1..], &s[1..]),
+            Some('[') => {
+                let Some(c) = s.first() else {
+                    return false;
+                };
+                let Some(end) = p.iter().skip(2).position(|c| *c == ']').map(|i| i + 2) else {
+                    return *c == '[' && matches(&p[1..], &s[1..]);
+                };
+                let (negate, set) = match p[1] {
+                    '!' | '^' => (true, &p[2..end]),
+                    _ => (false, &p[1..end]),
+                };
+                let mut found = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if i + 2 < set.len() && set[i + 1] == '-' {
+                        found |= (set[i]..=set[i + 2]).contains(c);
+                        i += 3;
+                    } else {
+                        found |= set[i] == *c;
+                        i += 1;
+                    }
+                }
+                found != negate && matches(&p[end + 1..], &s[1..])
+            }
+            Some('\\') if p.len() > 1 => s.first() == Some(&p[1]) && matches(&p[2..], &s[1..]),
+            Some(c) => s.first() == Some(c) && matches(&p[1..], &s[1..]),
+        }
+    }
+
+    matches(&pattern, &s)
+}
+
+/// Compare versions like `strverscmp()`, by their numeric and non-numeric parts
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    fn parts(s: &str) -> Vec<(bool, &str)> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(c) = rest.chars().next() {
+            let numeric = c.is_ascii_digit();
+            let len = rest
+                .find(|c: char| c.is_ascii_digit() != numeric)
+                .unwrap_or(rest.len());
+            parts.push((numeric, &rest[..len]));
+            rest = &rest[len..];
+        }
+        parts
+    }
+
+    for (a, b) in parts(a).into_iter().zip(parts(b)) {
+        let ordering = match (a, b) {
+            ((true, a), (true, b)) => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            ((_, a), (_, b)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    parts(a).len().cmp(&parts(b).len())
+}
+
+// the comparison operators of `ConditionKernelVersion=` and `ConditionOSRelease=`, longest first
+const OPERATORS: &[&str] = &["!$=", "<=", ">=", "!=", "$=", "<", ">", "="];
+
+/// Compare `actual` to `expected` with `operator`, `$=` is a glob match
+fn compare(actual: &str, operator: &str, expected: &str) -> bool {
+    match operator {
+        "$=" => glob_match(expected, actual),
+        "!$=" => !glob_match(expected, actual),
+        _ => {
+            let ordering = version_cmp(actual, expected);
+            match operator {
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                ">=" => ordering.is_ge(),
+                "!=" => ordering.is_ne(),
+                _ => ordering.is_eq(),
+            }
+        }
+    }
+}
+
+fn kernel_version_check(env: &dyn ConditionEnvironment, value: &str) -> bool {
+    SplitWord::new(value).all(|word| {
+        match OPERATORS.iter().find(|op| word.starts_with(*op)) {
+            Some(op) => compare(env.kernel_version(), op, word[op.len()..].trim_start()),
+            None => glob_match(&word, env.kernel_version()),
+        }
+    })
+}
+
+fn os_release_check(env: &dyn ConditionEnvironment, value: &str) -> Option<bool> {
+    let os_release = env.os_release();
+    SplitWord::new(value)
+        .map(|word| {
+            let (key, op, expected) = word.char_indices().find_map(|(i, _)| {
+                OPERATORS
+                    .iter()
+                    .find(|op| word[i..].starts_with(*op))
+                    .map(|op| (&word[..i], *op, &word[i + op.len()..]))
+            })?;
+            let actual = os_release.get(key).map_or("", String::as_str);
+            Some(compare(actual, op, expected))
+        })
+        .try_fold(true, |all, result| result.map(|r| all && r))
+}
+
+fn kernel_command_line_check(env: &dyn ConditionEnvironment, value: &str) -> bool {
+    SplitWord::new(env.kernel_command_line()).any(|word| {
+        if value.contains('=') {
+            word == value
+        } else {
+            word == value || word.strip_prefix(value).is_some_and(|rest| rest.starts_with('='))
+        }
+    })
+}
+
+fn virtualization_check(env: &dyn ConditionEnvironment, value: &str) -> bool {
+    let virt = env.virtualization();
+    let is_container = virt.is_some_and(|v| CONTAINERS.contains(&v));
+    match value {
+        "vm" => virt.is_some() && !is_container,
+        "container" => is_container,
+        _ => match parse_bool(value) {
+            Ok(expected) => virt.is_some() == expected,
+            Err(_) => virt == Some(value),
+        },
+    }
+}
+
+/// The paths below `root` matching glob `pattern`
+fn path_glob_exists(root: &Path, pattern: &str) -> bool {
+    fn walk(dir: &Path, components: &[&str]) -> bool {
+        let Some((first, rest)) = components.split_first() else {
+            return dir.exists();
+        };
+        if !first.contains(['*', '?', '[']) {
+            return walk(&dir.join(first), rest);
+        }
+        let Ok(entries) = fs::read_dir(dir) else {
+            return false;
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|e| e.file_name().to_str().is_some_and(|n| glob_match(first, n)))
+            .any(|e| walk(&e.path(), rest))
+    }
+
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    walk(root, &components)
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Evaluate check `check` (e.g. `PathExists`) with `value` (without `|` and `!`), `None` if it can't be known
+fn evaluate(env: &dyn ConditionEnvironment, check: &str, value: &str) -> Option<bool> {
+    let path = || {
+        let path = Path::new(value);
+        path.is_absolute()
+            .then(|| env.root().join(path.strip_prefix("/").unwrap_or(path)))
+    };
+
+    let result = match check {
+        "Architecture" => {
+            let expected = if value == "native" { native_architecture() } else { value };
+            env.architecture() == expected
+        }
+        "Virtualization" => virtualization_check(env, value),
+        "Host" => {
+            let is_machine_id = value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit());
+            if is_machine_id {
+                env.machine_id().is_some_and(|id| id.eq_ignore_ascii_case(value))
+            } else {
+                glob_match(value, env.hostname())
+            }
+        }
+        "KernelCommandLine" => kernel_command_line_check(env, value),
+        "KernelVersion" => kernel_version_check(env, value),
+        "OSRelease" => return os_release_check(env, value),
+        "Environment" => match value.split_once('=') {
+            Some((name, expected)) => env.env_var(name) == Some(expected),
+            None => env.env_var(value).is_some(),
+        },
+        "FirstBoot" => parse_bool(value).ok()? == env.is_first_boot(),
+        "PathExists" => path()?.exists(),
+        "PathExistsGlob" => Path::new(value).is_absolute() && path_glob_exists(env.root(), value),
+        "PathIsDirectory" => path()?.is_dir(),
+        "PathIsSymbolicLink" => path()?.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()),
+        "DirectoryNotEmpty" => fs::read_dir(path()?).is_ok_and(|mut entries| {
+            entries.any(|e| e.is_ok_and(|e| !e.file_name().to_string_lossy().starts_with('.')))
+        }),
+        "FileNotEmpty" => path()?.metadata().is_ok_and(|m| m.is_file() && m.len() > 0),
+        "FileIsExecutable" => path()?.metadata().is_ok_and(|m| m.is_file() && is_executable(&m)),
+        _ => return env.check(check, value),
+    };
+
+    Some(result)
+}
+
+/// The result of a single `Condition…=` or `Assert…=` setting
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConditionCheck {
+    /// The key, e.g. `ConditionPathExists`
+    pub key: String,
+    /// The value without the `|` and `!` prefixes
+    pub value: String,
+    /// Prefixed with `|`: only one of the triggering checks has to pass
+    pub trigger: bool,
+    /// Prefixed with `!`
+    pub negate: bool,
+    /// `None` if the result can't be known in the environment
+    pub result: Option<bool>,
+}
+
+impl fmt::Display for ConditionCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trigger = if self.trigger { "|" } else { "" };
+        let negate = if self.negate { "!" } else { "" };
+        let result = match self.result {
+            Some(true) => "succeeded",
+            Some(false) => "failed",
+            None => "unknown",
+        };
+        write!(f, "{}={trigger}{negate}{} {result}", self.key, self.value)
+    }
+}
+
+/// What happens when a unit is started
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionOutcome {
+    /// All conditions and asserts pass
+    Start,
+    /// A condition fails, so the unit is skipped
+    Skip,
+    /// An assert fails, so the start job fails
+    Fail,
+    /// The outcome depends on checks whose result can't be known
+    Unknown,
+}
+
+/// The results of the `Condition…=` and `Assert…=` settings of a unit, see [`SystemdUnit::evaluate_conditions()`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConditionEvaluation {
+    pub conditions: Vec<ConditionCheck>,
+    pub asserts: Vec<ConditionCheck>,
+}
+
+// three-valued logic, `None` is unknown
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// Combine checks like systemd: all non-triggering checks and one of the triggering checks (if any) have to pass
+fn combine(checks: &[ConditionCheck]) -> Option<bool> {
+    let mut result = Some(true);
+    // `None` without triggering checks
+    let mut triggered: Option<Option<bool>> = None;
+    for check in checks {
+        if check.trigger {
+            triggered = Some(or(triggered.unwrap_or(Some(false)), check.result));
+        } else {
+            result = and(result, check.result);
+        }
+    }
+
+    and(result, triggered.unwrap_or(Some(true)))
+}
+
+impl ConditionEvaluation {
+    /// Whether the conditions pass, `None` if that can't be known
+    pub fn conditions_met(&self) -> Option<bool> {
+        combine(&self.conditions)
+    }
+
+    /// Whether the asserts pass, `None` if that can't be known
+    pub fn asserts_met(&self) -> Option<bool> {
+        combine(&self.asserts)
+    }
+
+    /// Conditions are checked before asserts, like systemd does, so a unit whose conditions fail is skipped even if
+    /// an assert fails too
+    pub fn outcome(&self) -> ConditionOutcome {
+        match (self.conditions_met(), self.asserts_met()) {
+            (Some(false), _) => ConditionOutcome::Skip,
+            (None, _) => ConditionOutcome::Unknown,
+            (_, Some(false)) => ConditionOutcome::Fail,
+            (_, None) => ConditionOutcome::Unknown,
+            (Some(true), Some(true)) => ConditionOutcome::Start,
+        }
+    }
+}
+
+impl SystemdUnit {
+    /// Evaluate the `Condition…=` and `Assert…=` settings of the `[Unit]` section in `env`
+    ///
+    /// Like systemd, assigning the empty string to any condition (or assert) resets all previous conditions (or
+    /// asserts).
+    pub fn evaluate_conditions(&self, env: &dyn ConditionEnvironment) -> ConditionEvaluation {
+        let mut evaluation = ConditionEvaluation::default();
+
+        for (key, value) in self.section_entry_values(UNIT_SECTION) {
+            let (checks, check) = if let Some(check) = key.strip_prefix("Condition") {
+                (&mut evaluation.conditions, check)
+            } else if let Some(check) = key.strip_prefix("Assert") {
+                (&mut evaluation.asserts, check)
+            } else {
+                continue;
+            };
+            if !is_condition_key(key) {
+                continue;
+            }
+
+            let value = value.unquote_relaxed().trim();
+            if value.is_empty() {
+                checks.clear();
+                continue;
+            }
+            let (trigger, value) = match value.strip_prefix('|') {
+                Some(value) => (true, value.trim_start()),
+                None => (false, value),
+            };
+            let (negate, value) = match value.strip_prefix('!') {
+                Some(value) => (true, value.trim_start()),
+                None => (false, value),
+            };
+
+            let result = evaluate(env, check, value).map(|r| r != negate);
+            checks.push(ConditionCheck {
+                key: key.to_string(),
+                value: value.to_string(),
+                trigger,
+                negate,
+                result,
+            });
+        }
+
+        evaluation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> MachineProfile {
+        MachineProfile {
+            hostname: "web1".into(),
+            kernel_command_line: "quiet debug=1".into(),
+            architecture: "x86-64".into(),
+            virtualization: Some("kvm".into()),
+            environment: BTreeMap::from([("MODE".into(), "prod".into())]),
+            ..MachineProfile::default()
+        }
+    }
+
+    fn evaluate(data: &str) -> ConditionEvaluation {
+        SystemdUnit::load_from_str(&format!("[Unit]\n{data}")).unwrap().evaluate_conditions(&profile())
+    }
+
+    fn results(checks: &[ConditionCheck]) -> Vec<Option<bool>> {
+        checks.iter().map(|c| c.result).collect()
+    }
+
+    #[test]
+    fn checks() {
+        let evaluation = evaluate(
+            "ConditionHost=web*\nConditionArchitecture=x86-64\nConditionVirtualization=vm\n\
+             ConditionKernelCommandLine=debug\nConditionKernelCommandLine=quiet=1\nConditionEnvironment=MODE=prod\n\
+             ConditionEnvironment=MISSING\nConditionPathIsMountPoint=/srv\n",
+        );
+        assert_eq!(
+            results(&evaluation.conditions),
+            [Some(true), Some(true), Some(true), Some(true), Some(false), Some(true), Some(false), None]
+        );
+    }
+
+    #[test]
+    fn negation() {
+        let evaluation =
+            evaluate("ConditionHost=!web1\nConditionVirtualization=!container\nConditionPathIsMountPoint=!/srv\n");
+        assert_eq!(results(&evaluation.conditions), [Some(false), Some(true), None]);
+        assert!(evaluation.conditions.iter().all(|c| c.negate && !c.trigger));
+        assert_eq!(evaluation.conditions[0].value, "web1");
+        assert_eq!(evaluation.conditions[0].to_string(), "ConditionHost=!web1 failed");
+        assert_eq!(evaluation.conditions_met(), Some(false));
+    }
+
+    #[test]
+    fn triggers() {
+        // one of the triggering checks has to pass
+        let evaluation = evaluate("ConditionHost=|db*\nConditionHost=|web*\n");
+        assert_eq!(evaluation.conditions_met(), Some(true));
+        assert!(evaluation.conditions[1].trigger);
+
+        let evaluation = evaluate("ConditionHost=|db*\nConditionHost=|!web*\n");
+        assert_eq!(evaluation.conditions_met(), Some(false));
+        assert_eq!(evaluation.conditions[1].to_string(), "ConditionHost=|!web* failed");
+
+        // and all the others
+        let evaluation = evaluate("ConditionHost=|web*\nConditionArchitecture=arm64\n");
+        assert_eq!(evaluation.conditions_met(), Some(false));
+
+        // an unknown triggering check doesn't matter once another one passes
+        let evaluation = evaluate("ConditionPathIsMountPoint=|/srv\nConditionHost=|web*\n");
+        assert_eq!(evaluation.conditions_met(), Some(true));
+        let evaluation = evaluate("ConditionPathIsMountPoint=|/srv\nConditionHost=|db*\n");
+        assert_eq!(evaluation.conditions_met(), None);
+    }
+
+    #[test]
+    fn reset() {
+        let evaluation = evaluate("ConditionHost=db*\nConditionArchitecture=\nConditionHost=web*\nAssertHost=db*\n");
+        assert_eq!(evaluation.conditions.len(), 1);
+        assert_eq!(evaluation.asserts.len(), 1);
+        assert_eq!(evaluate("AssertHost=db*\nAssertHost=\n").asserts_met(), Some(true));
+    }
+
+    #[test]
+    fn asserts() {
+        let evaluation = evaluate("AssertHost=web*\nAssertArchitecture=!arm64\n");
+        assert!(evaluation.conditions.is_empty());
+        assert_eq!(evaluation.asserts_met(), Some(true));
+        assert_eq!(evaluation.outcome(), ConditionOutcome::Start);
+
+        assert_eq!(evaluate("AssertHost=db*\n").outcome(), ConditionOutcome::Fail);
+        assert_eq!(evaluate("AssertPathIsMountPoint=/srv\n").outcome(), ConditionOutcome::Unknown);
+    }
+
+    #[test]
+    fn outcome() {
+        assert_eq!(evaluate("").outcome(), ConditionOutcome::Start);
+        assert_eq!(evaluate("ConditionHost=db*\n").outcome(), ConditionOutcome::Skip);
+        // conditions are checked first, so a failing condition skips the unit even if an assert fails
+        assert_eq!(evaluate("AssertHost=db*\nConditionHost=db*\n").outcome(), ConditionOutcome::Skip);
+        assert_eq!(evaluate("AssertHost=db*\nConditionHost=web*\n").outcome(), ConditionOutcome::Fail);
+        // a failing assert can't be known to matter while a condition is unknown
+        assert_eq!(
+            evaluate("AssertHost=db*\nConditionPathIsMountPoint=/srv\n").outcome(),
+            ConditionOutcome::Unknown
+        );
+        assert_eq!(
+            evaluate("AssertPathIsMountPoint=/srv\nConditionHost=db*\n").outcome(),
+            ConditionOutcome::Skip
+        );
+    }
+}
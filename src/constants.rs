@@ -2,3 +2,11 @@
 pub const UNIT_SECTION: &str = "Unit";
 pub const INSTALL_SECTION: &str = "Install";
 pub const SERVICE_SECTION: &str = "Service";
+pub const SOCKET_SECTION: &str = "Socket";
+pub const MOUNT_SECTION: &str = "Mount";
+pub const AUTOMOUNT_SECTION: &str = "Automount";
+pub const SWAP_SECTION: &str = "Swap";
+pub const TIMER_SECTION: &str = "Timer";
+pub const PATH_SECTION: &str = "Path";
+pub const SLICE_SECTION: &str = "Slice";
+pub const SCOPE_SECTION: &str = "Scope";
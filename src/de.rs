@@ -0,0 +1,551 @@
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+use super::*;
+
+// the newtype name `EntryValue`s (de)serialize as, so units keep their values raw
+pub(crate) const ENTRY_VALUE: &str = "EntryValue";
+// the newtype name of all values of a key
+const ENTRY_VALUES: &str = "EntryValues";
+
+/// Deserialize `T` (a struct or map of sections, each a struct or map of keys) from the text of a unit file
+pub fn from_str<T: DeserializeOwned>(data: &str) -> Result<T, Error> {
+    let unit = SystemdUnit::load_from_str(data)?;
+    from_unit(&unit)
+}
+
+/// Deserialize `T` (a struct or map of sections, each a struct or map of keys) from a unit
+///
+/// Instances of a section are merged. A `Vec` gets the values of a key left after applying resets (see
+/// [`KeyKind`]), any other type only the last one, `Option` is `None` for absent keys (and sections) and bools are
+/// parsed with [`parse_bool()`].
+pub fn from_unit<'a, T: Deserialize<'a>>(unit: &'a SystemdUnit) -> Result<T, Error> {
+    T::deserialize(UnitDeserializer { unit })
+}
+
+fn invalid(what: &str, value: &str) -> Error {
+    Error::new(ErrorKind::Conversion, format!("{value:?} isn't {what}"))
+}
+
+struct UnitDeserializer<'a> {
+    unit: &'a SystemdUnit,
+}
+
+impl<'de> de::Deserializer<'de> for UnitDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let sections = self.unit.section_names().into_iter();
+        visitor.visit_map(MapAccess {
+            entries: sections.map(|name| (name, SectionDeserializer { unit: self.unit, name })),
+            value: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+// the entries of a map, whose values are deserialized by `D`
+struct MapAccess<'de, I: Iterator<Item = (&'de str, D)>, D> {
+    entries: I,
+    value: Option<D>,
+}
+
+impl<'de, I, D> de::MapAccess<'de> for MapAccess<'de, I, D>
+where
+    I: Iterator<Item = (&'de str, D)>,
+    D: de::Deserializer<'de, Error = Error>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(de::value::BorrowedStrDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::Conversion, "value without a key"))?;
+        seed.deserialize(value)
+    }
+}
+
+struct SectionDeserializer<'a> {
+    unit: &'a SystemdUnit,
+    name: &'a str,
+}
+
+impl<'de> de::Deserializer<'de> for SectionDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let entries = self.unit.grouped_entries(self.name).into_iter();
+        visitor.visit_map(MapAccess {
+            entries: entries.map(|(key, values)| {
+                let kind = key_kind(self.name, key);
+                (key, ValueDeserializer { values, kind, raw: false })
+            }),
+            value: None,
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+// the values of a key, `raw` values are passed on without being unquoted (or reset)
+struct ValueDeserializer<'a> {
+    values: Vec<&'a EntryValue>,
+    kind: KeyKind,
+    raw: bool,
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let s = self.last()?;
+                visitor.$visit(s.parse().map_err(|_| invalid("a number", s))?)
+            }
+        )*
+    };
+}
+
+impl<'a> ValueDeserializer<'a> {
+    fn last(&self) -> Result<&'a str, Error> {
+        let value = self
+            .values
+            .last()
+            .ok_or_else(|| Error::new(ErrorKind::Conversion, "key without a value"))?;
+        if self.raw {
+            Ok(value.raw())
+        } else {
+            value.unquote_with(UnquoteMode::Strict)
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.values.len() > 1 {
+            self.deserialize_seq(visitor)
+        } else {
+            visitor.visit_borrowed_str(self.last()?)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(parse_bool(self.last()?)?)
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let s = self.last()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(invalid("a single character", s)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.last()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        match name {
+            ENTRY_VALUE => visitor.visit_newtype_struct(ValueDeserializer { raw: true, ..self }),
+            ENTRY_VALUES => visitor.visit_seq(SeqAccess {
+                values: self.values.into_iter(),
+                raw: true,
+            }),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // a sequence is a list even if the key is a scalar one, so an empty assignment resets it
+        let values = match self.kind {
+            _ if self.raw => self.values,
+            KeyKind::Dependency => KeyKind::Dependency.effective(self.values),
+            _ => KeyKind::List.effective(self.values),
+        };
+        visitor.visit_seq(SeqAccess {
+            values: values.into_iter(),
+            raw: self.raw,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.last()?.into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf map struct
+    }
+}
+
+struct SeqAccess<'a> {
+    values: std::vec::IntoIter<&'a EntryValue>,
+    raw: bool,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        let Some(value) = self.values.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(ValueDeserializer {
+            values: vec![value],
+            kind: KeyKind::Scalar,
+            raw: self.raw,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct EntryValueVisitor;
+
+impl<'de> Visitor<'de> for EntryValueVisitor {
+    type Value = EntryValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a raw value")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<EntryValue, E> {
+        EntryValue::try_from_raw(v).map_err(E::custom)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<EntryValue, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryValue {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(ENTRY_VALUE, EntryValueVisitor)
+    }
+}
+
+// the values of a key, as a single value or a sequence
+struct EntryValues(Vec<EntryValue>);
+
+struct EntryValuesVisitor;
+
+impl<'de> Visitor<'de> for EntryValuesVisitor {
+    type Value = EntryValues;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a raw value or a sequence of raw values")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<EntryValues, E> {
+        EntryValueVisitor.visit_str(v).map(|v| EntryValues(vec![v]))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<EntryValues, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(EntryValues(values))
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<EntryValues, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryValues {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(ENTRY_VALUES, EntryValuesVisitor)
+    }
+}
+
+// the keys of a section, in order
+struct SectionEntries(Vec<(String, EntryValues)>);
+
+impl<'de> Deserialize<'de> for SectionEntries {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SectionVisitor;
+
+        impl<'de> Visitor<'de> for SectionVisitor {
+            type Value = SectionEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of keys")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<SectionEntries, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(SectionEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(SectionVisitor)
+    }
+}
+
+/// A map of sections, each a map of keys to a raw value or a sequence of raw values
+impl<'de> Deserialize<'de> for SystemdUnit {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UnitVisitor;
+
+        impl<'de> Visitor<'de> for UnitVisitor {
+            type Value = SystemdUnit;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of sections")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<SystemdUnit, A::Error> {
+                let mut unit = SystemdUnit::new();
+                while let Some((section, entries)) = map.next_entry::<String, SectionEntries>()? {
+                    for (key, values) in entries.0 {
+                        for value in values.0 {
+                            unit.append_entry_value(section.as_str(), key.as_str(), value);
+                        }
+                    }
+                }
+                Ok(unit)
+            }
+        }
+
+        deserializer.deserialize_map(UnitVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct Unit {
+        description: Option<String>,
+        after: Vec<String>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct Service {
+        exec_start: Vec<String>,
+        environment: Vec<String>,
+        remain_after_exit: bool,
+        restart_sec: Option<u32>,
+        nice: Option<i8>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct UnitFile {
+        unit: Unit,
+        service: Option<Service>,
+    }
+
+    #[test]
+    fn structs() {
+        let data = "[Unit]\nDescription=\"Foo Bar\"\nAfter=a.service\n[Service]\nExecStart=/bin/foo\nNice=-5\n";
+        let file: UnitFile = from_str(data).unwrap();
+        assert_eq!(file.unit.description.as_deref(), Some("Foo Bar"));
+        assert_eq!(file.unit.after, ["a.service"]);
+
+        let service = file.service.unwrap();
+        assert_eq!(service.exec_start, ["/bin/foo"]);
+        assert_eq!(service.nice, Some(-5));
+        assert_eq!(service.restart_sec, None);
+        assert!(!service.remain_after_exit);
+    }
+
+    #[test]
+    fn options() {
+        let file: UnitFile = from_str("[Unit]\n").unwrap();
+        assert_eq!(file.unit, Unit::default());
+        assert_eq!(file.service, None);
+    }
+
+    #[test]
+    fn merged_sections() {
+        let data = "[Unit]\nAfter=a.service\n[Service]\nNice=1\n[Unit]\nAfter=b.service\nDescription=foo\n";
+        let file: UnitFile = from_str(data).unwrap();
+        assert_eq!(file.unit.after, ["a.service", "b.service"]);
+        assert_eq!(file.unit.description.as_deref(), Some("foo"));
+    }
+
+    #[test]
+    fn vec_resets() {
+        let data = "[Service]\nExecStart=a\nExecStart=\nExecStart=b\nEnvironment=A=1\nEnvironment=\n";
+        let file: UnitFile = from_str(data).unwrap();
+        let service = file.service.unwrap();
+        assert_eq!(service.exec_start, ["b"]);
+        assert!(service.environment.is_empty());
+
+        // empty dependencies are ignored, they don't reset the list
+        let file: UnitFile = from_str("[Unit]\nAfter=a.service\nAfter=\nAfter=b.service\n").unwrap();
+        assert_eq!(file.unit.after, ["a.service", "b.service"]);
+
+        // a scalar key deserialized into a `Vec` is treated as a list
+        #[derive(Deserialize)]
+        struct Section {
+            #[serde(rename = "Foo")]
+            foo: Vec<String>,
+        }
+        let map: BTreeMap<String, Section> = from_str("[X]\nFoo=a\nFoo=\nFoo=b\nFoo=c\n").unwrap();
+        assert_eq!(map["X"].foo, ["b", "c"]);
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let file: UnitFile = from_str("[Unit]\nDescription=a\nDescription=b\n").unwrap();
+        assert_eq!(file.unit.description.as_deref(), Some("b"));
+
+        // unlike a `Vec`, a scalar is reset to the empty value
+        let file: UnitFile = from_str("[Unit]\nDescription=a\nDescription=\n").unwrap();
+        assert_eq!(file.unit.description.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn bools_and_numbers() {
+        for (value, expected) in [("yes", true), ("on", true), ("1", true), ("no", false), ("off", false)] {
+            let file: UnitFile = from_str(&format!("[Service]\nRemainAfterExit={value}\n")).unwrap();
+            assert_eq!(file.service.unwrap().remain_after_exit, expected, "{value}");
+        }
+
+        let file: UnitFile = from_str("[Service]\nRestartSec=30\nNice=\"-20\"\n").unwrap();
+        let service = file.service.unwrap();
+        assert_eq!(service.restart_sec, Some(30));
+        assert_eq!(service.nice, Some(-20));
+    }
+
+    #[test]
+    fn maps() {
+        let map: BTreeMap<String, BTreeMap<String, String>> = from_str("[A]\nx=1\ny=\"a b\"\n[B]\nz=2\n").unwrap();
+        assert_eq!(map["A"]["x"], "1");
+        assert_eq!(map["A"]["y"], "a b");
+        assert_eq!(map["B"]["z"], "2");
+    }
+
+    #[test]
+    fn errors() {
+        let e = from_str::<UnitFile>("[Service]\nRemainAfterExit=maybe\n").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Bool);
+
+        let e = from_str::<UnitFile>("[Service]\nRestartSec=30s\n").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Conversion);
+        assert_eq!(e.message(), "\"30s\" isn't a number");
+
+        let e = from_str::<UnitFile>("[Service]\nNice=200\n").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Conversion);
+
+        let e = from_str::<UnitFile>("[Unit]\nDescription=\"foo\\q\"\n").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Escape);
+
+        // syntax errors of the unit file are passed on
+        let e = from_str::<UnitFile>("[Unit\n").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Syntax);
+    }
+
+    #[test]
+    fn raw_values() {
+        let data = "[Service]\nExecStart=a\nExecStart=\nExecStart=\"b c\"\n";
+        let unit: SystemdUnit = from_str(data).unwrap();
+        let values: Vec<_> = unit.lookup_all_values("Service", "ExecStart").map(EntryValue::raw).collect();
+        assert_eq!(values, ["a", "", "\"b c\""]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use super::*;
+
+/// How the effective values of a key changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+    /// The values of a list are the same, but in another order
+    Reordered,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+            ChangeKind::Reordered => "reordered",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A key whose effective values (see [`SystemdUnit::effective_values()`]) differ, values are unquoted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyChange {
+    pub kind: ChangeKind,
+    pub section: String,
+    pub key: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl KeyChange {
+    /// Render as a JSON object
+    pub fn to_json(&self) -> String {
+        let values = |values: &[String]| {
+            let values: Vec<String> = values.iter().map(|v| json_string(Some(v))).collect();
+            format!("[{}]", values.join(","))
+        };
+        format!(
+            "{{\"kind\":{},\"section\":{},\"key\":{},\"old\":{},\"new\":{}}}",
+            json_string(Some(&self.kind.to_string())),
+            json_string(Some(&self.section)),
+            json_string(Some(&self.key)),
+            values(&self.old),
+            values(&self.new),
+        )
+    }
+}
+
+/// The semantic differences between two units, see [`diff()`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitDiff {
+    pub added_sections: Vec<String>,
+    pub removed_sections: Vec<String>,
+    pub changes: Vec<KeyChange>,
+}
+
+impl UnitDiff {
+    /// Return `true` if the units are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.added_sections.is_empty() && self.removed_sections.is_empty() && self.changes.is_empty()
+    }
+
+    /// Render as a JSON object with `added_sections`, `removed_sections` and `changes`
+    pub fn to_json(&self) -> String {
+        let names = |names: &[String]| {
+            let names: Vec<String> = names.iter().map(|n| json_string(Some(n))).collect();
+            format!("[{}]", names.join(","))
+        };
+        let changes: Vec<String> = self.changes.iter().map(KeyChange::to_json).collect();
+        format!(
+            "{{\"added_sections\":{},\"removed_sections\":{},\"changes\":[{}]}}",
+            names(&self.added_sections),
+            names(&self.removed_sections),
+            changes.join(","),
+        )
+    }
+}
+
+/// Like a diff of the unit files: `+`/`-` for added and removed values (and sections), `~` for reordered values
+impl fmt::Display for UnitDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sections: Vec<&str> = Vec::new();
+        let names = self.added_sections.iter().chain(&self.removed_sections);
+        for section in self.changes.iter().map(|c| &c.section).chain(names) {
+            let section = section.as_str();
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+
+        for section in sections {
+            if self.added_sections.iter().any(|s| s == section) {
+                writeln!(f, "+[{section}]")?;
+            } else if self.removed_sections.iter().any(|s| s == section) {
+                writeln!(f, "-[{section}]")?;
+            } else {
+                writeln!(f, "[{section}]")?;
+            }
+
+            for change in self.changes.iter().filter(|c| c.section == section) {
+                let key = &change.key;
+                if change.kind == ChangeKind::Reordered {
+                    for value in &change.new {
+                        writeln!(f, "~{key}={value}")?;
+                    }
+                    continue;
+                }
+                for value in &change.old {
+                    writeln!(f, "-{key}={value}")?;
+                }
+                for value in &change.new {
+                    writeln!(f, "+{key}={value}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compare the effective values of all keys of `old` and `new`, ignoring formatting, comments and overridden
+/// assignments
+///
+/// The order of dependencies (e.g. `After=`) doesn't matter, nor does how they're split across lines.
+pub fn diff(old: &SystemdUnit, new: &SystemdUnit) -> UnitDiff {
+    let mut result = UnitDiff::default();
+
+    let mut sections = old.section_names();
+    for section in new.section_names() {
+        if !sections.contains(&section) {
+            sections.push(section);
+        }
+    }
+
+    for section in sections {
+        match (old.has_section(section), new.has_section(section)) {
+            (true, false) => result.removed_sections.push(section.to_string()),
+            (false, true) => result.added_sections.push(section.to_string()),
+            _ => {}
+        }
+
+        let mut keys: Vec<&str> = Vec::new();
+        let grouped = old.grouped_entries(section).into_iter().chain(new.grouped_entries(section));
+        for (key, _) in grouped {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        for key in keys {
+            let old_effective = old.effective_values(section, key);
+            let new_effective = new.effective_values(section, key);
+            let unquoted = |values: &[&EntryValue]| -> Vec<String> {
+                values.iter().map(|v| v.unquote_relaxed().to_string()).collect()
+            };
+            let (old_values, new_values) = (unquoted(&old_effective), unquoted(&new_effective));
+
+            let kind = if old_values == new_values {
+                continue;
+            } else if old_values.is_empty() {
+                ChangeKind::Added
+            } else if new_values.is_empty() {
+                ChangeKind::Removed
+            } else if key_kind(section, key) == KeyKind::Dependency {
+                // like systemd, split the raw values (splitting unquotes the words)
+                let words = |values: &[&EntryValue]| -> BTreeSet<String> {
+                    values.iter().flat_map(|v| SplitWord::new(v.raw())).collect()
+                };
+                if words(&old_effective) == words(&new_effective) {
+                    continue;
+                }
+                ChangeKind::Changed
+            } else {
+                let (mut old_sorted, mut new_sorted) = (old_values.clone(), new_values.clone());
+                old_sorted.sort();
+                new_sorted.sort();
+                if old_sorted == new_sorted {
+                    ChangeKind::Reordered
+                } else {
+                    ChangeKind::Changed
+                }
+            };
+
+            result.changes.push(KeyChange {
+                kind,
+                section: section.to_string(),
+                key: key.to_string(),
+                old: old_values,
+                new: new_values,
+            });
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff_str(old: &str, new: &str) -> UnitDiff {
+        diff(&SystemdUnit::load_from_str(old).unwrap(), &SystemdUnit::load_from_str(new).unwrap())
+    }
+
+    fn kinds(diff: &UnitDiff) -> Vec<(&str, ChangeKind)> {
+        diff.changes.iter().map(|c| (c.key.as_str(), c.kind)).collect()
+    }
+
+    #[test]
+    fn equivalent() {
+        let old = "[Unit]\nDescription=a\nDescription=b\n# comment\n[Service]\nExecStart=/bin/a\n";
+        let new = "[Unit]\nDescription=\"b\"\n\n[Service]\nExecStart=/bin/b\nExecStart=\nExecStart=/bin/a\n";
+        assert!(diff_str(old, new).is_empty());
+    }
+
+    #[test]
+    fn changes() {
+        let old = "[Unit]\nDescription=old\nDocumentation=man:a man:b\n[Service]\nUser=a\n";
+        let new = "[Unit]\nDescription=new\nWants=a.service\n[Service]\nEnvironment=A=1\nEnvironment=B=2\n";
+        let diff = diff_str(old, new);
+        assert_eq!(
+            kinds(&diff),
+            [
+                ("Description", ChangeKind::Changed),
+                ("Documentation", ChangeKind::Removed),
+                ("Wants", ChangeKind::Added),
+                ("User", ChangeKind::Removed),
+                ("Environment", ChangeKind::Added),
+            ]
+        );
+        assert_eq!(diff.changes[0].old, ["old"]);
+        assert_eq!(diff.changes[0].new, ["new"]);
+        assert_eq!(diff.changes[4].new, ["A=1", "B=2"]);
+    }
+
+    #[test]
+    fn reordered() {
+        let old = "[Service]\nExecStartPre=/bin/a\nExecStartPre=/bin/b\n";
+        let new = "[Service]\nExecStartPre=/bin/b\nExecStartPre=/bin/a\n";
+        let diff = diff_str(old, new);
+        assert_eq!(kinds(&diff), [("ExecStartPre", ChangeKind::Reordered)]);
+        assert_eq!(diff.to_string(), "[Service]\n~ExecStartPre=/bin/b\n~ExecStartPre=/bin/a\n");
+    }
+
+    #[test]
+    fn dependencies() {
+        let old = "[Unit]\nAfter=a.service b.service\nWants=a.service\n";
+        let new = "[Unit]\nAfter=b.service\nAfter=a.service\nWants=a.service c.service\n";
+        let diff = diff_str(old, new);
+        assert_eq!(kinds(&diff), [("Wants", ChangeKind::Changed)]);
+    }
+
+    #[test]
+    fn quoted_dependencies() {
+        // a quoted value is a single word, it isn't unquoted before it's split
+        let old = "[Unit]\nAfter=\"a.service b.service\"\n";
+        let new = "[Unit]\nAfter=a.service\nAfter=b.service\n";
+        assert_eq!(kinds(&diff_str(old, new)), [("After", ChangeKind::Changed)]);
+    }
+
+    #[test]
+    fn sections() {
+        let diff = diff_str("[Unit]\n[Install]\nWantedBy=a.target\n", "[Unit]\n[Service]\n");
+        assert_eq!(diff.added_sections, ["Service"]);
+        assert_eq!(diff.removed_sections, ["Install"]);
+        assert_eq!(diff.to_string(), "-[Install]\n-WantedBy=a.target\n+[Service]\n");
+    }
+}
@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use super::*;
+use super::parser::{check_key, check_section_name};
+
+/// A unit as a structured document, e.g. for JSON, which converts back to the same unit
+///
+/// Unlike the [`Serialize`] implementation of [`SystemdUnit`], this keeps the order of sections and keys as well as
+/// the quoting of values.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitDocument {
+    pub sections: Vec<DocumentSection>,
+}
+
+/// A section of a [`UnitDocument`]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentSection {
+    pub name: String,
+    #[serde(default)]
+    pub entries: Vec<DocumentEntry>,
+}
+
+/// A `key=value` line of a [`DocumentSection`]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentEntry {
+    pub key: String,
+    /// The unquoted value
+    #[serde(default)]
+    pub value: String,
+    /// The value as written in the unit file, only if quoting `value` doesn't result in it
+    ///
+    /// When converting back, this takes precedence over `value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<String>,
+}
+
+impl From<&EntryValue> for DocumentEntry {
+    fn from(value: &EntryValue) -> Self {
+        let unquoted = value.unquote_relaxed();
+        let raw = (EntryValue::from_unquoted(unquoted).raw() != value.raw()).then(|| value.raw().clone());
+        Self {
+            key: String::new(),
+            value: unquoted.to_string(),
+            raw,
+        }
+    }
+}
+
+impl DocumentEntry {
+    /// The value of this entry in a unit
+    pub fn to_value(&self) -> Result<EntryValue, Error> {
+        match &self.raw {
+            Some(raw) if raw.contains(['\n', '\r']) => Err(Error::new(
+                ErrorKind::Conversion,
+                "raw values can't contain line breaks",
+            )),
+            Some(raw) => EntryValue::try_from_raw(raw.as_str()),
+            None => Ok(EntryValue::from_unquoted(self.value.as_str())),
+        }
+    }
+}
+
+impl From<&SystemdUnit> for UnitDocument {
+    fn from(unit: &SystemdUnit) -> Self {
+        let sections = unit
+            .sections
+            .iter()
+            .map(|(name, entries)| DocumentSection {
+                name: name.clone(),
+                entries: entries
+                    .data
+                    .iter()
+                    .map(|(key, value)| DocumentEntry {
+                        key: key.clone(),
+                        ..value.into()
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self { sections }
+    }
+}
+
+impl UnitDocument {
+    /// Convert back to a unit, failing on section names, keys and values the parser would reject
+    pub fn to_unit(&self) -> Result<SystemdUnit, Error> {
+        let mut unit = SystemdUnit::new();
+        for section in &self.sections {
+            check_section_name(&section.name)
+                .map_err(|e| Error::new(ErrorKind::Conversion, e.message()).with_source(e))?;
+            // make sure there's a section entry (even if it has no entries)
+            unit.sections.entry(section.name.clone()).or_insert(Entries::default());
+            for entry in &section.entries {
+                check_key(&entry.key).map_err(|e| {
+                    let msg = format!("[{}] {}", section.name, e.message());
+                    Error::new(ErrorKind::Conversion, msg).with_source(e)
+                })?;
+                let value = entry.to_value().map_err(|e| {
+                    let msg = format!("[{}] {}: {}", section.name, entry.key, e.message());
+                    Error::new(ErrorKind::Conversion, msg)
+                })?;
+                unit.append_entry_value(section.name.as_str(), entry.key.as_str(), value);
+            }
+        }
+        Ok(unit)
+    }
+}
+
+/// A representation of units [`convert()`] reads and writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// The unit file syntax
+    Unit,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl Format {
+    /// The format of a file with extension `ext` (e.g. `json`), unit file extensions (e.g. `service`) are
+    /// [`Format::Unit`]
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            #[cfg(feature = "json")]
+            "json" => Some(Format::Json),
+            #[cfg(feature = "toml")]
+            "toml" => Some(Format::Toml),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Format::Yaml),
+            "conf" => Some(Format::Unit),
+            _ if UnitType::ALL.iter().any(|t| t.suffix() == ext) => Some(Format::Unit),
+            _ => None,
+        }
+    }
+
+    /// Read a unit in this format
+    pub fn parse(self, data: &str) -> Result<SystemdUnit, Error> {
+        match self {
+            Format::Unit => SystemdUnit::load_from_str(data),
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::from_str::<UnitDocument>(data)
+                .map_err(|e| conversion_error(self, e))?
+                .to_unit(),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::from_str::<UnitDocument>(data)
+                .map_err(|e| conversion_error(self, e))?
+                .to_unit(),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::from_str::<UnitDocument>(data)
+                .map_err(|e| conversion_error(self, e))?
+                .to_unit(),
+        }
+    }
+
+    /// Write `unit` in this format
+    pub fn write(self, unit: &SystemdUnit) -> Result<String, Error> {
+        #[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+        let document = UnitDocument::from(unit);
+        match self {
+            Format::Unit => {
+                let mut buf = Vec::new();
+                unit.write_to(&mut buf)?;
+                Ok(String::from_utf8_lossy(&buf).into_owned())
+            }
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(&document).map_err(|e| conversion_error(self, e)),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::to_string(&document).map_err(|e| conversion_error(self, e)),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(&document).map_err(|e| conversion_error(self, e)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Format::Unit => "unit",
+            #[cfg(feature = "json")]
+            Format::Json => "JSON",
+            #[cfg(feature = "toml")]
+            Format::Toml => "TOML",
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "YAML",
+        };
+        f.write_str(s)
+    }
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn conversion_error<E>(format: Format, e: E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Error::new(ErrorKind::Conversion, format!("{format}: {e}")).with_source(e)
+}
+
+/// Convert a unit from one format to another, like `systemd-unit convert`
+pub fn convert(data: &str, from: Format, to: Format) -> Result<String, Error> {
+    to.write(&from.parse(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty section, repeated keys and values whose raw form isn't what quoting the unquoted value results in
+    const UNIT: &str = "\
+[Unit]
+Description=\"quoted description\"
+After=a.service
+After=b.service
+
+[Install]
+
+[Service]
+ExecStart=/bin/echo 'a  b' \"c\\\\d\"
+Environment=A=1
+Environment=\"B=x y\"
+Plain=value with  spaces
+";
+
+    fn round_trip(format: Format) {
+        let unit = SystemdUnit::load_from_str(UNIT).unwrap();
+        let data = format.write(&unit).unwrap();
+        let parsed = format.parse(&data).unwrap();
+        assert_eq!(parsed, unit, "{format}:\n{data}");
+        assert_eq!(Format::Unit.write(&parsed).unwrap(), Format::Unit.write(&unit).unwrap());
+    }
+
+    #[test]
+    fn document() {
+        let unit = SystemdUnit::load_from_str(UNIT).unwrap();
+        let document = UnitDocument::from(&unit);
+        let names: Vec<&str> = document.sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Unit", "Install", "Service"]);
+        assert!(document.sections[1].entries.is_empty());
+
+        let description = &document.sections[0].entries[0];
+        assert_eq!(description.value, "quoted description");
+        assert_eq!(description.raw.as_deref(), Some("\"quoted description\""));
+        let after = &document.sections[0].entries[1];
+        assert_eq!((after.value.as_str(), after.raw.as_deref()), ("a.service", None));
+
+        assert_eq!(document.to_unit().unwrap(), unit);
+    }
+
+    #[test]
+    fn empty_section() {
+        let document = UnitDocument {
+            sections: vec![DocumentSection {
+                name: "Install".into(),
+                entries: vec![],
+            }],
+        };
+        let unit = document.to_unit().unwrap();
+        assert!(unit.has_section("Install"));
+        assert_eq!(UnitDocument::from(&unit), document);
+    }
+
+    #[test]
+    fn invalid_raw_value() {
+        let entry = DocumentEntry {
+            key: "Description".into(),
+            value: String::new(),
+            raw: Some("a\nb".into()),
+        };
+        assert_eq!(entry.to_value().unwrap_err().kind(), ErrorKind::Conversion);
+    }
+
+    #[test]
+    fn invalid_names() {
+        let document = |section: &str, key: &str| UnitDocument {
+            sections: vec![DocumentSection {
+                name: section.into(),
+                entries: vec![DocumentEntry {
+                    key: key.into(),
+                    value: "x".into(),
+                    raw: None,
+                }],
+            }],
+        };
+        assert!(document("Service", "Foo").to_unit().is_ok());
+
+        for key in ["Foo\nBar", "", "Foo=Bar", " Foo", "#Foo", "[Foo"] {
+            let e = document("Service", key).to_unit().unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Conversion, "{key:?}");
+            assert!(e.message().starts_with("[Service] key "), "{}", e.message());
+        }
+        for section in ["Foo\nBar", "", "Foo\"Bar"] {
+            let e = document(section, "Foo").to_unit().unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Conversion, "{section:?}");
+            assert!(e.message().starts_with("section name "), "{}", e.message());
+        }
+    }
+
+    #[test]
+    fn unit_round_trip() {
+        round_trip(Format::Unit);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        round_trip(Format::Json);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trip() {
+        round_trip(Format::Toml);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_round_trip() {
+        round_trip(Format::Yaml);
+    }
+}
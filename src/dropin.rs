@@ -0,0 +1,162 @@
+use std::collections::BTreeSet;
+use super::*;
+
+fn unquoted<'a>(values: &[&'a EntryValue]) -> Vec<&'a str> {
+    values.iter().map(|v| v.unquote_relaxed()).collect()
+}
+
+// the effective values of `key` in `section` of `unit`, including the ones assigned through an alias of `key`
+fn effective_values<'a>(unit: &'a SystemdUnit, section: &str, key: &str) -> Vec<&'a EntryValue> {
+    let options = LookupOptions { aliases: true, ..Default::default() };
+    let values = unit.lookup_all_matching(section, key, options).into_iter().map(|m| m.value);
+    key_kind(section, key).effective(values)
+}
+
+fn words(values: &[&EntryValue]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    values
+        .iter()
+        .flat_map(|v| SplitWord::new(v.raw()))
+        .filter(|w| seen.insert(w.clone()))
+        .collect()
+}
+
+impl SystemdUnit {
+    /// The smallest drop-in (e.g. `foo.service.d/override.conf`) which turns this (vendor) unit into `desired`
+    ///
+    /// Keys are compared by their effective values (see [`SystemdUnit::effective_values()`]), including the ones
+    /// assigned through an alias (see [`KEY_ALIASES`]), and set under their canonical name. A scalar is set to its
+    /// new value (or reset with an empty assignment), a list is extended if the desired values start with the
+    /// current ones and otherwise cleared and set again, like `ExecStart=` has to be. Dependencies can't be removed
+    /// by a drop-in, so that's an error.
+    pub fn override_drop_in(&self, desired: &SystemdUnit) -> Result<SystemdUnit, Error> {
+        let mut drop_in = SystemdUnit::new();
+
+        // keys are compared under their canonical names, e.g. `BindTo=` in one unit and `BindsTo=` in the other
+        let mut keys: Vec<(&str, &str)> = Vec::new();
+        for unit in [self, desired] {
+            for section in unit.section_names() {
+                for (key, _) in unit.grouped_entries(section) {
+                    let canonical = canonical_key(section, key);
+                    if !keys.contains(&canonical) {
+                        keys.push(canonical);
+                    }
+                }
+            }
+        }
+
+        for (section, key) in keys {
+            let current = effective_values(self, section, key);
+            let wanted = effective_values(desired, section, key);
+            if unquoted(&current) == unquoted(&wanted) {
+                continue;
+            }
+
+            match key_kind(section, key) {
+                KeyKind::Scalar => {
+                    let value = wanted.last().map_or_else(|| EntryValue::from_raw(""), |v| (*v).clone());
+                    drop_in.append_entry_value(section, key, value);
+                }
+                KeyKind::List => {
+                    let appended = match wanted.get(..current.len()) {
+                        Some(start) if unquoted(start) == unquoted(&current) => &wanted[current.len()..],
+                        _ => {
+                            drop_in.append_entry_value(section, key, EntryValue::from_raw(""));
+                            &wanted[..]
+                        }
+                    };
+                    for value in appended {
+                        drop_in.append_entry_value(section, key, (*value).clone());
+                    }
+                }
+                KeyKind::Dependency => {
+                    let (current, wanted) = (words(&current), words(&wanted));
+                    if let Some(removed) = current.iter().find(|w| !wanted.contains(w)) {
+                        return Err(Error::new(
+                            ErrorKind::Validation,
+                            format!("[{section}] {key}={removed} can't be removed by a drop-in"),
+                        ));
+                    }
+                    let added: Vec<&str> = wanted
+                        .iter()
+                        .filter(|w| !current.contains(w))
+                        .map(String::as_str)
+                        .collect();
+                    if !added.is_empty() {
+                        let value = EntryValue::from_raw(quote_words(added.into_iter()));
+                        drop_in.append_entry_value(section, key, value);
+                    }
+                }
+            }
+        }
+
+        Ok(drop_in)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(data: &str) -> SystemdUnit {
+        SystemdUnit::load_from_str(data).unwrap()
+    }
+
+    fn drop_in(vendor: &str, desired: &str) -> Result<SystemdUnit, Error> {
+        unit(vendor).override_drop_in(&unit(desired))
+    }
+
+    #[test]
+    fn unchanged() {
+        let data = "[Unit]\nAfter=a.service\n[Service]\nExecStart=/bin/true\n";
+        assert_eq!(drop_in(data, data).unwrap(), SystemdUnit::new());
+    }
+
+    #[test]
+    fn exec_start() {
+        let vendor = "[Service]\nExecStart=/bin/a\n";
+        let desired = "[Service]\nExecStart=/bin/b --flag\n";
+        assert_eq!(drop_in(vendor, desired).unwrap(), unit("[Service]\nExecStart=\nExecStart=/bin/b --flag\n"));
+    }
+
+    #[test]
+    fn list() {
+        let vendor = "[Service]\nEnvironment=A=1\n";
+        let extended = "[Service]\nEnvironment=A=1\nEnvironment=B=2\n";
+        assert_eq!(drop_in(vendor, extended).unwrap(), unit("[Service]\nEnvironment=B=2\n"));
+
+        let replaced = "[Service]\nEnvironment=B=2\nEnvironment=A=1\n";
+        assert_eq!(
+            drop_in(vendor, replaced).unwrap(),
+            unit("[Service]\nEnvironment=\nEnvironment=B=2\nEnvironment=A=1\n")
+        );
+    }
+
+    #[test]
+    fn scalar() {
+        let vendor = "[Unit]\nDescription=Vendor\n[Service]\nUser=nobody\n";
+        let desired = "[Unit]\nDescription=Local\n[Service]\n";
+        assert_eq!(drop_in(vendor, desired).unwrap(), unit("[Unit]\nDescription=Local\n\n[Service]\nUser=\n"));
+    }
+
+    #[test]
+    fn dependency() {
+        let vendor = "[Unit]\nAfter=a.service b.service\n";
+        let added = "[Unit]\nAfter=a.service\nAfter=c.service b.service\n";
+        assert_eq!(drop_in(vendor, added).unwrap(), unit("[Unit]\nAfter=c.service\n"));
+
+        let err = drop_in(vendor, "[Unit]\nAfter=a.service\n").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Validation);
+        assert!(err.message().contains("After=b.service"), "{err}");
+    }
+
+    #[test]
+    fn aliases() {
+        let vendor = "[Unit]\nBindTo=a.service\n[Service]\nStartLimitBurst=5\n";
+        let desired = "[Unit]\nBindsTo=a.service\nStartLimitBurst=5\n";
+        assert_eq!(drop_in(vendor, desired).unwrap(), SystemdUnit::new());
+
+        let desired = "[Unit]\nBindsTo=a.service b.service\n";
+        assert_eq!(drop_in(vendor, desired).unwrap(), unit("[Unit]\nBindsTo=b.service\nStartLimitBurst=\n"));
+    }
+}
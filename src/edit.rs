@@ -0,0 +1,442 @@
+use super::*;
+use super::parser::{check_key, check_raw_value, check_section_name};
+
+// check `key=value` in `section` against the same rules as the parser
+fn check_entry(section: &str, key: &str, value: &EntryValue) -> Result<(), Error> {
+    check_section_name(section)?;
+    check_key(key)?;
+    check_raw_value(key, value)
+}
+
+impl Entries {
+    /// Remove all values of `key`, returning them in order
+    pub fn remove_entry(&mut self, key: &str) -> Vec<EntryValue> {
+        self.data.remove_all(key).collect()
+    }
+
+    /// Remove the values of `key` for which `predicate` returns `true`, returning how many were removed
+    pub fn remove_value_matching<F>(&mut self, key: &str, mut predicate: F) -> usize
+    where
+        F: FnMut(&EntryValue) -> bool,
+    {
+        let before = self.data.values_len();
+        self.data.retain(|k, v| k != key || !predicate(v));
+        before - self.data.values_len()
+    }
+
+    /// Insert `key` before the first value of `anchor`, returning `false` (without inserting) if there's none
+    pub fn insert_before<K: Into<String>>(&mut self, anchor: &str, key: K, value: EntryValue) -> bool {
+        match self.position(anchor, false) {
+            Some(index) => {
+                self.insert_at(index, key.into(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Insert `key` after the last value of `anchor`, returning `false` (without inserting) if there's none
+    pub fn insert_after<K: Into<String>>(&mut self, anchor: &str, key: K, value: EntryValue) -> bool {
+        match self.position(anchor, true) {
+            Some(index) => {
+                self.insert_at(index + 1, key.into(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep only the values for which `predicate` returns `true`
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&str, &EntryValue) -> bool,
+    {
+        self.data.retain(|k, v| predicate(k, v));
+    }
+
+    // index of the first (or last) value of `key` in file order
+    fn position(&self, key: &str, last: bool) -> Option<usize> {
+        let mut positions = self.data.iter().enumerate().filter(|(_, (k, _))| *k == key).map(|(i, _)| i);
+        if last { positions.next_back() } else { positions.next() }
+    }
+
+    // ListOrderedMultimap can only append, so rebuild it with the new value in place
+    fn insert_at(&mut self, index: usize, key: String, value: EntryValue) {
+        let mut pairs: Vec<(EntryKey, EntryValue)> = std::mem::take(&mut self.data).into_iter().collect();
+        pairs.insert(index.min(pairs.len()), (key, value));
+        for (k, v) in pairs {
+            self.data.append(k, v);
+        }
+    }
+}
+
+impl SystemdUnit {
+    /// Remove all values of `key` in `section`, returning them in order
+    pub fn remove_entry<S, K>(&mut self, section: S, key: K) -> Vec<EntryValue>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        let key = key.into();
+        self.sections
+            .get_all_mut(&section.into())
+            .flat_map(|entries| entries.remove_entry(&key))
+            .collect()
+    }
+
+    /// Remove the values of `key` in `section` for which `predicate` returns `true`, returning how many were removed
+    pub fn remove_value_matching<S, K, F>(&mut self, section: S, key: K, mut predicate: F) -> usize
+    where
+        S: Into<String>,
+        K: Into<String>,
+        F: FnMut(&EntryValue) -> bool,
+    {
+        let key = key.into();
+        self.sections
+            .get_all_mut(&section.into())
+            .map(|entries| entries.remove_value_matching(&key, &mut predicate))
+            .sum()
+    }
+
+    /// Remove all instances of section `name`, returning `false` if there was none
+    pub fn remove_section<S: Into<String>>(&mut self, name: S) -> bool {
+        self.sections.remove_all(&name.into()).count() > 0
+    }
+
+    /// Insert `key` in `section` before the first value of `anchor`, returning `false` (without inserting) if
+    /// there's none
+    pub fn insert_before<S, A, K>(&mut self, section: S, anchor: A, key: K, value: EntryValue) -> bool
+    where
+        S: Into<String>,
+        A: Into<String>,
+        K: Into<String>,
+    {
+        let anchor = anchor.into();
+        match self.sections.get_all_mut(&section.into()).find(|e| e.data.contains_key(&anchor)) {
+            Some(entries) => entries.insert_before(&anchor, key, value),
+            None => false,
+        }
+    }
+
+    /// Insert `key` in `section` after the last value of `anchor`, returning `false` (without inserting) if
+    /// there's none
+    pub fn insert_after<S, A, K>(&mut self, section: S, anchor: A, key: K, value: EntryValue) -> bool
+    where
+        S: Into<String>,
+        A: Into<String>,
+        K: Into<String>,
+    {
+        let anchor = anchor.into();
+        match self.sections.get_all_mut(&section.into()).rfind(|e| e.data.contains_key(&anchor)) {
+            Some(entries) => entries.insert_after(&anchor, key, value),
+            None => false,
+        }
+    }
+
+    /// Keep only the values for which `predicate` (called with section, key and value) returns `true`
+    ///
+    /// Sections are kept even if all of their values are removed, use [`SystemdUnit::remove_section()`] for that.
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&str, &str, &EntryValue) -> bool,
+    {
+        for (section, entries) in self.sections.iter_mut() {
+            entries.retain(|k, v| predicate(section, k, v));
+        }
+    }
+
+    /// Move all instances of section `name` to `index` (counted in section instances, clamped to the end),
+    /// returning `false` if there's no such section
+    pub fn move_section<S: Into<String>>(&mut self, name: S, index: usize) -> bool {
+        let name = name.into();
+        if !self.sections.contains_key(&name) {
+            return false;
+        }
+
+        let (moved, mut rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.sections)
+            .into_iter()
+            .partition(|(section, _)| *section == name);
+        let index = index.min(rest.len());
+        rest.splice(index..index, moved);
+
+        for (section, entries) in rest {
+            self.sections.append(section, entries);
+        }
+        true
+    }
+
+    /// Same as [`SystemdUnit::append_entry()`], but fail instead of adding an entry the parser would reject or
+    /// read back differently
+    pub fn try_append_entry<S, K, V>(&mut self, section: S, key: K, value: V) -> Result<(), Error>
+    where
+        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.try_append_entry_value(section, key, EntryValue::from_unquoted(value))
+    }
+
+    /// Same as [`SystemdUnit::append_entry_value()`], but validate section, key and value first
+    pub fn try_append_entry_value<S, K>(&mut self, section: S, key: K, value: EntryValue) -> Result<(), Error>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        let (section, key) = (section.into(), key.into());
+        check_entry(&section, &key, &value)?;
+        self.append_entry_value(section, key, value);
+        Ok(())
+    }
+
+    /// Same as [`SystemdUnit::rename_section()`], but fail if `to` isn't a valid section name
+    pub fn try_rename_section<F, T>(&mut self, from: F, to: T) -> Result<(), Error>
+    where
+        F: Into<String>,
+        T: Into<String>,
+    {
+        let to = to.into();
+        check_section_name(&to)?;
+        self.rename_section(from.into(), to);
+        Ok(())
+    }
+
+    /// Same as [`SystemdUnit::set_entry()`], but validate section, key and value first
+    pub fn try_set_entry<S, K, V>(&mut self, section: S, key: K, value: V) -> Result<(), Error>
+    where
+        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.try_set_entry_value(section, key, EntryValue::from_unquoted(value))
+    }
+
+    /// Same as [`SystemdUnit::set_entry_raw()`], but fail on malformed quoting or escapes instead of storing them
+    pub fn try_set_entry_raw<S, K, V>(&mut self, section: S, key: K, value: V) -> Result<(), Error>
+    where
+        S: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.try_set_entry_value(section, key, EntryValue::from_raw(value))
+    }
+
+    /// Same as [`SystemdUnit::set_entry_value()`], but validate section, key and value first
+    pub fn try_set_entry_value<S, K>(&mut self, section: S, key: K, value: EntryValue) -> Result<(), Error>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        let (section, key) = (section.into(), key.into());
+        check_entry(&section, &key, &value)?;
+        self.set_entry_value(section, key, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(data: &str) -> SystemdUnit {
+        SystemdUnit::load_from_str(data).unwrap()
+    }
+
+    // a unit with a separate instance for each section, which the parser would merge
+    fn instances(sections: &[(&str, &[(&str, &str)])]) -> SystemdUnit {
+        let mut unit = SystemdUnit::new();
+        for (name, entries) in sections {
+            let mut instance = Entries::default();
+            for (key, value) in *entries {
+                instance.data.append(key.to_string(), EntryValue::from_raw(*value));
+            }
+            unit.sections.append(name.to_string(), instance);
+        }
+        unit
+    }
+
+    // the section headers and entries in file order, one per line
+    fn dump(unit: &SystemdUnit) -> String {
+        let mut s = String::new();
+        for (section, entries) in unit.sections.iter() {
+            s.push_str(&format!("[{section}]\n"));
+            for (key, value) in entries.data.iter() {
+                s.push_str(&format!("{key}={}\n", value.raw()));
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn remove_entry() {
+        let mut u = instances(&[
+            ("A", &[("x", "1"), ("y", "2"), ("x", "3")]),
+            ("B", &[("x", "4")]),
+            ("A", &[("x", "5")]),
+        ]);
+        let removed: Vec<_> = u.remove_entry("A", "x").iter().map(|v| v.raw().to_string()).collect();
+        assert_eq!(removed, ["1", "3", "5"]);
+        assert_eq!(dump(&u), "[A]\ny=2\n[B]\nx=4\n[A]\n");
+
+        assert!(u.remove_entry("A", "x").is_empty());
+        assert!(u.remove_entry("C", "x").is_empty());
+    }
+
+    #[test]
+    fn remove_value_matching() {
+        let mut u = instances(&[
+            ("A", &[("x", "a"), ("x", "b")]),
+            ("B", &[("x", "a")]),
+            ("A", &[("x", "a"), ("y", "a")]),
+        ]);
+        assert_eq!(u.remove_value_matching("A", "x", |v| v.raw() == "a"), 2);
+        assert_eq!(dump(&u), "[A]\nx=b\n[B]\nx=a\n[A]\ny=a\n");
+        assert_eq!(u.remove_value_matching("A", "x", |v| v.raw() == "a"), 0);
+        assert_eq!(u.remove_value_matching("C", "x", |_| true), 0);
+    }
+
+    #[test]
+    fn remove_section() {
+        let mut u = instances(&[("A", &[("x", "1")]), ("B", &[("x", "2")]), ("A", &[("x", "3")])]);
+        assert!(u.remove_section("A"));
+        assert_eq!(dump(&u), "[B]\nx=2\n");
+        assert!(!u.remove_section("A"));
+    }
+
+    #[test]
+    fn insert_before() {
+        let mut u = unit("[A]\nx=1\ny=2\nx=3\n");
+        assert!(u.insert_before("A", "x", "w", EntryValue::from_raw("0")));
+        assert_eq!(dump(&u), "[A]\nw=0\nx=1\ny=2\nx=3\n");
+
+        assert!(!u.insert_before("A", "z", "w", EntryValue::from_raw("0")));
+        assert!(!u.insert_before("B", "x", "w", EntryValue::from_raw("0")));
+        assert_eq!(dump(&u), "[A]\nw=0\nx=1\ny=2\nx=3\n");
+    }
+
+    #[test]
+    fn insert_after() {
+        let mut u = unit("[A]\nx=1\ny=2\nx=3\ny=4\n");
+        assert!(u.insert_after("A", "x", "z", EntryValue::from_raw("5")));
+        assert_eq!(dump(&u), "[A]\nx=1\ny=2\nx=3\nz=5\ny=4\n");
+
+        assert!(!u.insert_after("A", "w", "z", EntryValue::from_raw("5")));
+        assert!(!u.insert_after("B", "x", "z", EntryValue::from_raw("5")));
+    }
+
+    #[test]
+    fn insert_in_section_instances() {
+        // before goes into the first instance with the anchor, after into the last one
+        let mut u = instances(&[
+            ("A", &[("y", "1")]),
+            ("B", &[("x", "2")]),
+            ("A", &[("x", "3")]),
+            ("A", &[("x", "4")]),
+            ("A", &[("y", "5")]),
+        ]);
+        assert!(u.insert_before("A", "x", "w", EntryValue::from_raw("0")));
+        assert!(u.insert_after("A", "x", "z", EntryValue::from_raw("9")));
+        assert_eq!(dump(&u), "[A]\ny=1\n[B]\nx=2\n[A]\nw=0\nx=3\n[A]\nx=4\nz=9\n[A]\ny=5\n");
+    }
+
+    #[test]
+    fn retain() {
+        let mut u = instances(&[("A", &[("x", "1"), ("y", "2")]), ("B", &[("x", "3")]), ("A", &[("x", "4")])]);
+        u.retain(|section, key, value| section == "B" || key != "x" || value.raw() == "4");
+        // sections are kept even if they end up empty
+        assert_eq!(dump(&u), "[A]\ny=2\n[B]\nx=3\n[A]\nx=4\n");
+
+        u.retain(|section, _, _| section != "A");
+        assert_eq!(dump(&u), "[A]\n[B]\nx=3\n[A]\n");
+    }
+
+    #[test]
+    fn move_section() {
+        let mut u = unit("[A]\nx=1\n[B]\nx=2\n[C]\nx=3\n");
+        assert!(u.move_section("C", 0));
+        assert_eq!(dump(&u), "[C]\nx=3\n[A]\nx=1\n[B]\nx=2\n");
+        assert!(u.move_section("C", 100));
+        assert_eq!(dump(&u), "[A]\nx=1\n[B]\nx=2\n[C]\nx=3\n");
+        assert!(!u.move_section("D", 0));
+    }
+
+    #[test]
+    fn move_section_instances() {
+        // all instances move together and keep their order, the index counts the other instances
+        let mut u = instances(&[
+            ("A", &[("x", "1")]),
+            ("B", &[("x", "2")]),
+            ("A", &[("x", "3")]),
+            ("C", &[("x", "4")]),
+            ("B", &[("x", "5")]),
+        ]);
+        assert!(u.move_section("A", 2));
+        assert_eq!(dump(&u), "[B]\nx=2\n[C]\nx=4\n[A]\nx=1\n[A]\nx=3\n[B]\nx=5\n");
+        assert!(u.move_section("B", 1));
+        assert_eq!(dump(&u), "[C]\nx=4\n[B]\nx=2\n[B]\nx=5\n[A]\nx=1\n[A]\nx=3\n");
+    }
+
+    #[test]
+    fn try_set_entry() {
+        let mut u = SystemdUnit::new();
+        u.try_set_entry("Service", "ExecStart", "/bin/echo \"a b\"\nc").unwrap();
+        assert_eq!(u.lookup_last("Service", "ExecStart").unwrap(), "/bin/echo \"a b\"\nc");
+
+        for key in ["", "a=b", "a\nb", " a", "a\t", "#a", ";a", "[a"] {
+            let e = u.try_set_entry("Service", key, "x").unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Validation, "{key:?}");
+        }
+        for section in ["", "a]\n[b", "a\"b", "a\\b"] {
+            let e = u.try_set_entry(section, "Key", "x").unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Validation, "{section:?}");
+        }
+        assert_eq!(dump(&u), "[Service]\nExecStart=/bin/echo \\\"a b\\\"\\nc\n");
+    }
+
+    #[test]
+    fn try_set_entry_raw() {
+        let mut u = SystemdUnit::new();
+        u.try_set_entry_raw("Service", "ExecStart", "/bin/echo \"a b\"").unwrap();
+        assert_eq!(u.lookup_last("Service", "ExecStart").unwrap(), "/bin/echo a b");
+
+        for value in ["a\nb", "a\rb", " a", "a ", "a\\"] {
+            let e = u.try_set_entry_raw("Service", "ExecStart", value).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Validation, "{value:?}");
+        }
+        let e = u.try_set_entry_raw("Service", "ExecStart", "a\\qb").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Escape);
+        assert_eq!(e.message(), "invalid value for key \"ExecStart\"");
+
+        assert_eq!(u.lookup_last("Service", "ExecStart").unwrap(), "/bin/echo a b");
+    }
+
+    #[test]
+    fn try_append_entry_value() {
+        let mut u = SystemdUnit::new();
+        u.try_append_entry_value("Unit", "After", EntryValue::from_raw("a.service")).unwrap();
+        u.try_append_entry("Unit", "After", "b c.service").unwrap();
+
+        let e = u.try_append_entry_value("Unit", "After", EntryValue::from_raw("a\nb")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Validation);
+        let e = u.try_append_entry_value("Unit", "After", EntryValue::from_raw("\\x4")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Escape);
+        let e = u.try_append_entry_value("Unit", "A=B", EntryValue::from_raw("a")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Validation);
+        let e = u.try_append_entry_value("Un\nit", "After", EntryValue::from_raw("a")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Validation);
+
+        assert_eq!(dump(&u), "[Unit]\nAfter=a.service\nAfter=b c.service\n");
+    }
+
+    #[test]
+    fn try_rename_section() {
+        let mut u = unit("[A]\nx=1\n");
+        for name in ["", "B\nC", "B\"", "B\\C", "B\x7f"] {
+            let e = u.try_rename_section("A", name.to_string()).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::Validation, "{name:?}");
+        }
+        assert_eq!(dump(&u), "[A]\nx=1\n");
+
+        u.try_rename_section("A", String::from("B")).unwrap();
+        assert_eq!(dump(&u), "[B]\nx=1\n");
+    }
+}
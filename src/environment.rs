@@ -0,0 +1,435 @@
+use std::fmt;
+use super::*;
+use super::parser::WHITESPACE;
+
+// same as systemd's SHELL_NEED_ESCAPE, escaping anything else in double quotes keeps the backslash
+const SHELL_NEED_ESCAPE: [char; 4] = ['"', '\\', '`', '$'];
+
+/// Same as systemd's `env_name_is_valid()`: letters, digits and `_`, not starting with a digit
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Same as systemd's `env_value_is_valid()`: no control characters except tab and newline
+fn is_valid_value(value: &str) -> bool {
+    !value.chars().any(|c| c.is_control() && c != '\t' && c != '\n')
+}
+
+/// Where a variable of a [`ServiceEnvironment`] was assigned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnvironmentSource {
+    /// An `Environment=` setting
+    Environment,
+    /// A file loaded by `EnvironmentFile=`
+    EnvironmentFile(PathBuf),
+}
+
+impl fmt::Display for EnvironmentSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentSource::Environment => f.write_str("Environment="),
+            EnvironmentSource::EnvironmentFile(path) => write!(f, "EnvironmentFile={}", path.display()),
+        }
+    }
+}
+
+/// A step of computing a [`ServiceEnvironment`], in the order systemd takes them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnvironmentEvent {
+    /// `name` was set to `value`, replacing any earlier value
+    Set {
+        name: String,
+        value: String,
+        source: EnvironmentSource,
+    },
+    /// An invalid assignment (or `UnsetEnvironment=` word) was ignored like systemd does
+    Ignored {
+        assignment: String,
+        source: Option<EnvironmentSource>,
+    },
+    /// An optional (`-` prefixed) `EnvironmentFile=` doesn't exist
+    MissingFile(PathBuf),
+    /// `name` was removed by the `UnsetEnvironment=` word `pattern`
+    Unset { name: String, pattern: String },
+}
+
+impl EnvironmentEvent {
+    /// The variable this event is about, if any
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            EnvironmentEvent::Set { name, .. } | EnvironmentEvent::Unset { name, .. } => Some(name),
+            EnvironmentEvent::Ignored { assignment, .. } => {
+                Some(assignment.split_once('=').map_or(assignment.as_str(), |(name, _)| name))
+            }
+            EnvironmentEvent::MissingFile(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for EnvironmentEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentEvent::Set { name, value, source } => write!(f, "{source}: set {name}={value:?}"),
+            EnvironmentEvent::Ignored { assignment, source: Some(source) } => {
+                write!(f, "{source}: ignoring invalid assignment {assignment:?}")
+            }
+            EnvironmentEvent::Ignored { assignment, source: None } => {
+                write!(f, "UnsetEnvironment=: ignoring invalid variable {assignment:?}")
+            }
+            EnvironmentEvent::MissingFile(path) => {
+                write!(f, "EnvironmentFile=-{}: file doesn't exist, skipping", path.display())
+            }
+            EnvironmentEvent::Unset { name, pattern } => write!(f, "UnsetEnvironment={pattern}: unset {name}"),
+        }
+    }
+}
+
+/// The environment variables a service gets from its unit, see [`SystemdUnit::environment()`]
+///
+/// Variables of the service manager (e.g. `PassEnvironment=`) and the ones systemd sets itself (e.g. `$USER` or
+/// `$INVOCATION_ID`) aren't included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServiceEnvironment {
+    /// The final variables, in the order of their first assignment
+    pub variables: Vec<(String, String)>,
+    /// Everything that happened to get there
+    pub events: Vec<EnvironmentEvent>,
+}
+
+impl ServiceEnvironment {
+    /// The final value of variable `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// The events concerning variable `name`, i.e. why it is (or isn't) set
+    pub fn history(&self, name: &str) -> Vec<&EnvironmentEvent> {
+        self.events.iter().filter(|e| e.name() == Some(name)).collect()
+    }
+
+    fn assign(&mut self, assignment: String, source: EnvironmentSource) {
+        let Some((name, value)) = assignment
+            .split_once('=')
+            .filter(|(name, value)| is_valid_name(name) && is_valid_value(value))
+        else {
+            self.events.push(EnvironmentEvent::Ignored { assignment, source: Some(source) });
+            return;
+        };
+
+        match self.variables.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.variables.push((name.to_string(), value.to_string())),
+        }
+        self.events.push(EnvironmentEvent::Set {
+            name: name.to_string(),
+            value: value.to_string(),
+            source,
+        });
+    }
+
+    // like systemd's `env_match()`: a name removes the variable, an assignment only if the value matches, too
+    fn unset(&mut self, pattern: String) {
+        let (name, value) = match pattern.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (pattern.as_str(), None),
+        };
+        if !is_valid_name(name) || !value.is_none_or(is_valid_value) {
+            self.events.push(EnvironmentEvent::Ignored { assignment: pattern, source: None });
+            return;
+        }
+
+        let before = self.variables.len();
+        self.variables.retain(|(n, v)| n != name || value.is_some_and(|value| value != v));
+        if self.variables.len() != before {
+            let name = name.to_string();
+            self.events.push(EnvironmentEvent::Unset { name, pattern });
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    PreKey,
+    Key,
+    PreValue,
+    Value,
+    ValueEscape,
+    SingleQuoteValue,
+    DoubleQuoteValue,
+    DoubleQuoteValueEscape,
+    Comment,
+    CommentEscape,
+}
+
+/// Parse the `NAME=value` assignments of an environment file like systemd's `load_env_file()`
+///
+/// Values may be quoted (`'…'` or `"…"`), backslashes escape the next character (a newline continues the value
+/// on the next line) and lines starting with `#` or `;` are comments. Assignments aren't validated, so names may
+/// be invalid (systemd ignores those).
+pub fn parse_environment_file(data: &str) -> Vec<(String, String)> {
+    let mut assignments = Vec::new();
+    let mut state = State::PreKey;
+    let (mut key, mut value) = (String::new(), String::new());
+    // length of `value` before its trailing whitespace (only unquoted trailing whitespace is dropped)
+    let mut value_end: Option<usize> = None;
+
+    let mut push = |key: &mut String, value: &mut String, value_end: &mut Option<usize>| {
+        if let Some(end) = value_end.take() {
+            value.truncate(end);
+        }
+        let name = key.trim_end_matches(WHITESPACE).to_string();
+        assignments.push((name, std::mem::take(value)));
+        key.clear();
+    };
+
+    for c in data.chars() {
+        let newline = c == '\n' || c == '\r';
+        state = match state {
+            State::PreKey if c == '#' || c == ';' => State::Comment,
+            State::PreKey if WHITESPACE.contains(&c) => State::PreKey,
+            State::PreKey => {
+                key.push(c);
+                State::Key
+            }
+            State::Key if newline => {
+                key.clear();
+                State::PreKey
+            }
+            State::Key if c == '=' => State::PreValue,
+            State::Key => {
+                key.push(c);
+                State::Key
+            }
+            State::PreValue if newline => {
+                push(&mut key, &mut value, &mut value_end);
+                State::PreKey
+            }
+            State::PreValue if c == '\'' => State::SingleQuoteValue,
+            State::PreValue if c == '"' => State::DoubleQuoteValue,
+            State::PreValue if WHITESPACE.contains(&c) => State::PreValue,
+            State::PreValue | State::Value if !newline && c != '\\' => {
+                if !WHITESPACE.contains(&c) {
+                    value_end = None;
+                } else if value_end.is_none() {
+                    value_end = Some(value.len());
+                }
+                value.push(c);
+                State::Value
+            }
+            State::Value if newline => {
+                push(&mut key, &mut value, &mut value_end);
+                State::PreKey
+            }
+            State::PreValue | State::Value => {
+                value_end = None;
+                State::ValueEscape
+            }
+            State::ValueEscape => {
+                // an escaped newline is dropped entirely
+                if !newline {
+                    value.push(c);
+                }
+                State::Value
+            }
+            State::SingleQuoteValue if c == '\'' => State::PreValue,
+            State::SingleQuoteValue => {
+                value.push(c);
+                State::SingleQuoteValue
+            }
+            State::DoubleQuoteValue if c == '"' => State::PreValue,
+            State::DoubleQuoteValue if c == '\\' => State::DoubleQuoteValueEscape,
+            State::DoubleQuoteValue => {
+                value.push(c);
+                State::DoubleQuoteValue
+            }
+            State::DoubleQuoteValueEscape => {
+                if SHELL_NEED_ESCAPE.contains(&c) {
+                    value.push(c);
+                } else if c != '\n' {
+                    value.push('\\');
+                    value.push(c);
+                }
+                State::DoubleQuoteValue
+            }
+            State::Comment if c == '\\' => State::CommentEscape,
+            // since systemd v254 a backslash doesn't continue a comment on the next line
+            State::Comment | State::CommentEscape if newline => State::PreKey,
+            State::Comment | State::CommentEscape => State::Comment,
+        };
+    }
+
+    match state {
+        State::PreKey | State::Key | State::Comment | State::CommentEscape => {}
+        _ => push(&mut key, &mut value, &mut value_end),
+    }
+
+    assignments
+}
+
+impl SystemdUnit {
+    // the section with the exec settings, e.g. `[Socket]` for sockets
+    fn exec_section(&self) -> &'static str {
+        self.unit_type()
+            .and_then(|t| t.section())
+            .unwrap_or(SERVICE_SECTION)
+    }
+
+    /// The words of all effective `Environment=` settings (i.e. `NAME=value` assignments), unquoted and unescaped
+    pub fn environment_assignments(&self) -> Vec<String> {
+        // systemd splits the raw value, so quotes around single assignments are kept apart
+        self.effective_values(self.exec_section(), "Environment")
+            .into_iter()
+            .flat_map(|v| SplitWord::new(v.raw()))
+            .collect()
+    }
+
+    /// The files of all effective `EnvironmentFile=` settings and whether they're optional (i.e. `-` prefixed)
+    pub fn environment_files(&self) -> Vec<(PathBuf, bool)> {
+        self.effective_values(self.exec_section(), "EnvironmentFile")
+            .into_iter()
+            .map(|v| {
+                let path = v.unquote_relaxed();
+                match path.strip_prefix('-') {
+                    Some(path) => (PathBuf::from(path), true),
+                    None => (PathBuf::from(path), false),
+                }
+            })
+            .collect()
+    }
+
+    /// Compute the environment the service gets from `Environment=`, `EnvironmentFile=` and `UnsetEnvironment=`
+    ///
+    /// Like systemd, files override `Environment=` and are read in order, then `UnsetEnvironment=` removes
+    /// variables. Files are looked up below `root` (e.g. `/`), a missing or relative file which isn't optional is an
+    /// error.
+    pub fn environment<P: AsRef<Path>>(&self, root: P) -> Result<ServiceEnvironment, Error> {
+        let mut env = ServiceEnvironment::default();
+
+        for assignment in self.environment_assignments() {
+            env.assign(assignment, EnvironmentSource::Environment);
+        }
+
+        for (path, optional) in self.environment_files() {
+            let source = EnvironmentSource::EnvironmentFile(path.clone());
+            if !path.is_absolute() {
+                if !optional {
+                    let msg = format!("EnvironmentFile= path {path:?} isn't absolute");
+                    return Err(Error::new(ErrorKind::Validation, msg));
+                }
+                let assignment = format!("-{}", path.display());
+                env.events.push(EnvironmentEvent::Ignored { assignment, source: Some(source) });
+                continue;
+            }
+
+            let file = root.as_ref().join(path.strip_prefix("/").unwrap_or(&path));
+            let data = match fs::read_to_string(&file) {
+                Ok(data) => data,
+                Err(e) if optional && e.kind() == io::ErrorKind::NotFound => {
+                    env.events.push(EnvironmentEvent::MissingFile(path));
+                    continue;
+                }
+                Err(e) => return Err(Error::from(e).in_file(file)),
+            };
+
+            for (name, value) in parse_environment_file(&data) {
+                env.assign(format!("{name}={value}"), source.clone());
+            }
+        }
+
+        for value in self.effective_values(self.exec_section(), "UnsetEnvironment") {
+            for pattern in SplitWord::new(value.raw()) {
+                env.unset(pattern);
+            }
+        }
+
+        Ok(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> Vec<(String, String)> {
+        parse_environment_file(data)
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    // a fresh directory below the system's temporary directory to use as `root`
+    fn root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("systemd-unit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn quoting() {
+        let data = "A='x  y'\nB=\"a\\\"b\\$c\\\\d\\n\"\nC=  unquoted  \nD=\"quoted  \"\nE=a\\ b\n";
+        assert_eq!(
+            parse(data),
+            pairs(&[("A", "x  y"), ("B", "a\"b$c\\d\\n"), ("C", "unquoted"), ("D", "quoted  "), ("E", "a b")])
+        );
+    }
+
+    #[test]
+    fn continuation() {
+        assert_eq!(parse("A=1\\\n2\nB=\"x\\\ny\"\n"), pairs(&[("A", "12"), ("B", "xy")]));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(parse("# A=1\n  ; B=2\nC=3 # not a comment\n"), pairs(&[("C", "3 # not a comment")]));
+        // a backslash doesn't continue a comment
+        assert_eq!(parse("# comment \\\nA=1\n#\\\nB=2"), pairs(&[("A", "1"), ("B", "2")]));
+    }
+
+    #[test]
+    fn environment_file() {
+        let root = root("environment-file");
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(root.join("etc/env"), "A=file\nB=2\n").unwrap();
+
+        let data = "[Service]\nEnvironment=A=1 C=3\nEnvironmentFile=/etc/env\nEnvironmentFile=-/etc/missing\n";
+        let unit = SystemdUnit::load_from_str(data).unwrap();
+        let env = unit.environment(&root).unwrap();
+        assert_eq!(env.variables, pairs(&[("A", "file"), ("C", "3"), ("B", "2")]));
+        assert!(env.events.contains(&EnvironmentEvent::MissingFile("/etc/missing".into())));
+        assert_eq!(env.history("A").len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn required_environment_file() {
+        let root = root("required-environment-file");
+        let unit = SystemdUnit::load_from_str("[Service]\nEnvironmentFile=/etc/missing\n").unwrap();
+        assert_eq!(unit.environment(&root).unwrap_err().kind(), ErrorKind::Io);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn relative_environment_file() {
+        let unit = SystemdUnit::load_from_str("[Service]\nEnvironmentFile=etc/env\n").unwrap();
+        assert_eq!(unit.environment("/").unwrap_err().kind(), ErrorKind::Validation);
+
+        let unit = SystemdUnit::load_from_str("[Service]\nEnvironmentFile=-etc/env\n").unwrap();
+        let env = unit.environment("/").unwrap();
+        assert!(matches!(&env.events[..], [EnvironmentEvent::Ignored { assignment, .. }] if assignment == "-etc/env"));
+    }
+
+    #[test]
+    fn unset_environment() {
+        let data = "[Service]\nEnvironment=A=1 B=2 C=3\nUnsetEnvironment=A=1 B=3 C 1X\n";
+        let env = SystemdUnit::load_from_str(data).unwrap().environment("/").unwrap();
+        assert_eq!(env.variables, pairs(&[("B", "2")]));
+        assert_eq!(env.get("A"), None);
+        assert!(env.events.contains(&EnvironmentEvent::Ignored { assignment: "1X".into(), source: None }));
+    }
+}
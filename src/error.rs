@@ -0,0 +1,249 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The category of an [`Error`], modelled after [`std::num::IntErrorKind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The unit file doesn't follow the `systemd.syntax` grammar
+    Syntax,
+    /// A quoted value is malformed (e.g. unbalanced quotes)
+    Quoting,
+    /// A value contains an invalid escape sequence
+    Escape,
+    /// A value isn't a valid boolean (see [`parse_bool()`](crate::parse_bool))
+    Bool,
+    /// Reading or writing a unit file failed
+    Io,
+    /// A key, section name or value was rejected by validation
+    Validation,
+    /// A key isn't known in the section it appears in
+    UnknownKey,
+    /// Converting a unit from or to another representation failed
+    Conversion,
+    /// The unit is masked (i.e. linked to `/dev/null`)
+    Masked,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ErrorKind::Syntax => "syntax error",
+            ErrorKind::Quoting => "quoting error",
+            ErrorKind::Escape => "invalid escape sequence",
+            ErrorKind::Bool => "invalid boolean",
+            ErrorKind::Io => "I/O error",
+            ErrorKind::Validation => "validation error",
+            ErrorKind::UnknownKey => "unknown key",
+            ErrorKind::Conversion => "conversion error",
+            ErrorKind::Masked => "unit is masked",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The error type of this crate.
+///
+/// Use [`Error::kind()`] to match on the category of the failure.
+/// Errors raised while parsing carry the position (and, if known, the file) where they occurred.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    msg: String,
+    path: Option<PathBuf>,
+    line: Option<usize>,
+    column: Option<usize>,
+    source: Option<Box<dyn StdError + Send + Sync + 'static>>,
+}
+
+impl Error {
+    pub(crate) fn new<M: Into<String>>(kind: ErrorKind, msg: M) -> Self {
+        Self {
+            kind,
+            msg: msg.into(),
+            path: None,
+            line: None,
+            column: None,
+            source: None,
+        }
+    }
+
+    pub(crate) fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    pub(crate) fn in_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub(crate) fn with_source<E>(mut self, source: E) -> Self
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// The category of this error
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The error message without any location information
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    /// The file the error occurred in (if known)
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The (1-based) line the error occurred on (if known)
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// The (1-based) column the error occurred at (if known)
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(col)) => write!(f, "{line}:{col}: ")?,
+            (Some(line), None) => write!(f, "{line}: ")?,
+            _ if self.path.is_some() => write!(f, " ")?,
+            _ => {}
+        }
+        write!(f, "{}: {}", self.kind, self.msg)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|e| e as &(dyn StdError + 'static))
+    }
+}
+
+/// Errors are equal if they are of the same kind, have the same message and location.
+/// The `source` isn't compared.
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.msg == other.msg
+            && self.path == other.path
+            && self.line == other.line
+            && self.column == other.column
+    }
+}
+
+/// The message only names the [`io::ErrorKind`], the [`io::Error`] itself is the source
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::new(ErrorKind::Io, e.kind().to_string()).with_source(e)
+    }
+}
+
+/// The underlying [`io::Error`] of an I/O error, any other error is wrapped as [`io::ErrorKind::Other`]
+impl From<Error> for io::Error {
+    fn from(mut e: Error) -> Self {
+        match e.source.take().map(|source| source.downcast::<io::Error>()) {
+            Some(Ok(source)) => *source,
+            Some(Err(source)) => {
+                e.source = Some(source);
+                io::Error::other(e)
+            }
+            None => io::Error::other(e),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Conversion, msg.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Conversion, msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let e = Error::new(ErrorKind::Syntax, "invalid section header");
+        assert_eq!(e.to_string(), "syntax error: invalid section header");
+
+        let e = e.at(3, 5);
+        assert_eq!((e.line(), e.column()), (Some(3), Some(5)));
+        assert_eq!(e.to_string(), "3:5: syntax error: invalid section header");
+
+        let e = e.in_file("/etc/systemd/system/foo.service");
+        assert_eq!(e.path(), Some(Path::new("/etc/systemd/system/foo.service")));
+        assert_eq!(e.to_string(), "/etc/systemd/system/foo.service:3:5: syntax error: invalid section header");
+    }
+
+    #[test]
+    fn display_without_position() {
+        let e = Error::new(ErrorKind::Validation, "key \"\" is empty").in_file("foo.service");
+        assert_eq!(e.to_string(), "foo.service: validation error: key \"\" is empty");
+    }
+
+    #[test]
+    fn io_error() {
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "No such file or directory"));
+        assert_eq!(e.kind(), ErrorKind::Io);
+        assert_eq!(e.to_string(), "I/O error: entity not found");
+
+        // the I/O error is only printed once when walking the chain
+        let source = e.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::NotFound);
+        assert_eq!(source.to_string(), "No such file or directory");
+    }
+
+    #[test]
+    fn into_io_error() {
+        let e = Error::from(io::Error::new(io::ErrorKind::NotFound, "No such file or directory")).in_file("foo");
+        let e = io::Error::from(e);
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert_eq!(e.to_string(), "No such file or directory");
+
+        let e = io::Error::from(Error::new(ErrorKind::Syntax, "invalid section header").at(1, 1));
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        assert_eq!(e.to_string(), "1:1: syntax error: invalid section header");
+
+        let source = Error::new(ErrorKind::Validation, "bad").with_source(fmt::Error);
+        let e = io::Error::from(source);
+        assert_eq!(e.kind(), io::ErrorKind::Other);
+        let inner = e.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+        assert!(inner.source().is_some_and(|s| s.is::<fmt::Error>()));
+    }
+
+    #[test]
+    fn equality_ignores_source() {
+        let a = Error::new(ErrorKind::Quoting, "unbalanced quotes").at(1, 1);
+        let b = Error::new(ErrorKind::Quoting, "unbalanced quotes").at(1, 1).with_source(io::Error::other("x"));
+        assert_eq!(a, b);
+        assert_ne!(a, Error::new(ErrorKind::Quoting, "unbalanced quotes").at(2, 1));
+    }
+}
@@ -0,0 +1,858 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use super::*;
+
+/// A dependency between two units
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DependencyKind {
+    Requires,
+    Requisite,
+    Wants,
+    BindsTo,
+    PartOf,
+    Conflicts,
+    After,
+    Before,
+}
+
+impl DependencyKind {
+    pub const ALL: [DependencyKind; 8] = [
+        DependencyKind::Requires,
+        DependencyKind::Requisite,
+        DependencyKind::Wants,
+        DependencyKind::BindsTo,
+        DependencyKind::PartOf,
+        DependencyKind::Conflicts,
+        DependencyKind::After,
+        DependencyKind::Before,
+    ];
+
+    /// The dependencies which pull in other units when starting a unit
+    pub const REQUIREMENTS: [DependencyKind; 4] = [
+        DependencyKind::Requires,
+        DependencyKind::Requisite,
+        DependencyKind::Wants,
+        DependencyKind::BindsTo,
+    ];
+
+    /// The `[Unit]` key of this dependency
+    pub fn key(&self) -> &'static str {
+        match self {
+            DependencyKind::Requires => "Requires",
+            DependencyKind::Requisite => "Requisite",
+            DependencyKind::Wants => "Wants",
+            DependencyKind::BindsTo => "BindsTo",
+            DependencyKind::PartOf => "PartOf",
+            DependencyKind::Conflicts => "Conflicts",
+            DependencyKind::After => "After",
+            DependencyKind::Before => "Before",
+        }
+    }
+
+    pub fn is_ordering(&self) -> bool {
+        matches!(self, DependencyKind::After | DependencyKind::Before)
+    }
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.key())
+    }
+}
+
+/// Where a dependency comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DependencyOrigin {
+    /// A key in the `[Unit]` section
+    Unit,
+    /// `WantedBy=`/`RequiredBy=` in the `[Install]` section (only effective once the unit is enabled)
+    Install,
+    /// A symlink in a `.wants/` or `.requires/` directory
+    Symlink,
+    /// Added by systemd (e.g. by `DefaultDependencies=yes`)
+    Implicit,
+}
+
+/// An edge of a [`UnitGraph`]: `from` has a `kind` dependency on `to`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dependency<'a> {
+    pub from: &'a str,
+    pub kind: DependencyKind,
+    pub to: &'a str,
+    pub origin: DependencyOrigin,
+}
+
+/// A cycle of ordering dependencies, which systemd breaks by dropping a job
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderingCycle {
+    /// The units in start order, the first unit is repeated at the end
+    pub units: Vec<String>,
+}
+
+impl fmt::Display for OrderingCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Found ordering cycle: {}", self.units.join(" -> "))
+    }
+}
+
+// (prefix, instance, suffix), e.g. ("getty", "tty1", "service") for `getty@tty1.service`
+pub(crate) fn split_instance(name: &str) -> Option<(&str, &str, &str)> {
+    let (stem, suffix) = name.rsplit_once('.')?;
+    let (prefix, instance) = stem.split_once('@')?;
+    Some((prefix, instance, suffix))
+}
+
+/// The template of an instance name, e.g. `getty@.service` for `getty@tty1.service`
+pub(crate) fn template_name(name: &str) -> Option<String> {
+    split_instance(name)
+        .filter(|(_, instance, _)| !instance.is_empty())
+        .map(|(prefix, _, suffix)| format!("{prefix}@.{suffix}"))
+}
+
+pub(crate) fn is_template(name: &str) -> bool {
+    split_instance(name).is_some_and(|(_, instance, _)| instance.is_empty())
+}
+
+/// Resolve the specifiers used in dependency names (`%n`, `%N`, `%p`, `%i`, `%I` and `%%`)
+pub(crate) fn expand_specifiers(name: &str, word: &str) -> String {
+    if !word.contains('%') {
+        return word.to_string();
+    }
+
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let (prefix, instance) = stem.split_once('@').unwrap_or((stem, ""));
+
+    let mut expanded = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => expanded.push_str(name),
+            Some('N') => expanded.push_str(stem),
+            Some('p') => expanded.push_str(prefix),
+            Some('i') | Some('I') => expanded.push_str(instance),
+            Some('%') => expanded.push('%'),
+            Some(c) => {
+                expanded.push('%');
+                expanded.push(c);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+// (kind, unit) added by `DefaultDependencies=yes`
+fn default_dependencies(unit_type: UnitType, unit: &SystemdUnit) -> Vec<(DependencyKind, &'static str)> {
+    use DependencyKind::*;
+
+    let shutdown = [(Conflicts, "shutdown.target"), (Before, "shutdown.target")];
+    let umount = [(Conflicts, "umount.target"), (Before, "umount.target")];
+    let sysinit = [(Requires, "sysinit.target"), (After, "sysinit.target")];
+
+    match unit_type {
+        UnitType::Service => [&sysinit[..], &[(After, "basic.target")], &shutdown].concat(),
+        UnitType::Socket => [&sysinit[..], &[(Before, "sockets.target")], &shutdown].concat(),
+        UnitType::Timer => {
+            let mut deps = [&sysinit[..], &[(Before, "timers.target"), (After, "time-set.target")], &shutdown].concat();
+            if unit.has_key(TIMER_SECTION, "OnCalendar") {
+                deps.push((After, "time-sync.target"));
+            }
+            deps
+        }
+        UnitType::Path => [&sysinit[..], &[(Before, "paths.target")], &shutdown].concat(),
+        UnitType::Target | UnitType::Slice | UnitType::Scope => shutdown.to_vec(),
+        UnitType::Mount => [&[(After, "local-fs-pre.target"), (Before, "local-fs.target")], &umount[..]].concat(),
+        UnitType::Automount => [&[(Before, "local-fs.target")], &umount[..]].concat(),
+        UnitType::Swap => [&[(Before, "swap.target")], &umount[..]].concat(),
+        UnitType::Device => Vec::new(),
+    }
+}
+
+/// The dependency graph of a set of units, like systemd builds it when loading them
+#[derive(Debug, Default)]
+pub struct UnitGraph {
+    units: BTreeMap<String, SystemdUnit>,
+    edges: BTreeMap<(String, DependencyKind, String), DependencyOrigin>,
+}
+
+impl UnitGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load all units of a unit directory
+    ///
+    /// Drop-ins from `<unit>.d/*.conf` are merged into their unit, and the symlinks in `<unit>.wants/` and
+    /// `<unit>.requires/` are added as dependencies. Units masked by a symlink to `/dev/null` are skipped.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let mut graph = Self::new();
+        let mut drop_in_dirs = Vec::new();
+        let mut link_dirs = Vec::new();
+
+        let mut entries = fs::read_dir(dir)
+            .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| Error::from(e).in_file(dir))?;
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+
+            if path.is_dir() {
+                if let Some(unit) = name.strip_suffix(".d") {
+                    drop_in_dirs.push((unit.to_string(), path));
+                } else if let Some(unit) = name.strip_suffix(".wants") {
+                    link_dirs.push((unit.to_string(), DependencyKind::Wants, path));
+                } else if let Some(unit) = name.strip_suffix(".requires") {
+                    link_dirs.push((unit.to_string(), DependencyKind::Requires, path));
+                }
+                continue;
+            }
+
+            if UnitType::from_name(&name).is_none() {
+                continue;
+            }
+            if fs::read_link(&path).is_ok_and(|target| target == Path::new("/dev/null")) {
+                continue;
+            }
+            graph.units.insert(name, SystemdUnit::load_from_file(&path)?);
+        }
+
+        for (name, path) in drop_in_dirs {
+            let Some(unit) = graph.units.get_mut(&name) else {
+                continue;
+            };
+            let mut confs = fs::read_dir(&path)
+                .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| Error::from(e).in_file(&path))?;
+            confs.sort_by_key(|e| e.file_name());
+            for conf in confs {
+                if conf.path().extension().is_some_and(|ext| ext == "conf") {
+                    unit.merge_from(&SystemdUnit::load_from_file(conf.path())?);
+                }
+            }
+        }
+
+        let names: Vec<String> = graph.units.keys().cloned().collect();
+        for name in names {
+            graph.add_unit_dependencies(&name, &name);
+        }
+
+        for (name, kind, path) in link_dirs {
+            let links = fs::read_dir(&path).map_err(|e| Error::from(e).in_file(&path))?;
+            for link in links {
+                let link = link.map_err(|e| Error::from(e).in_file(&path))?;
+                if let Some(to) = link.file_name().to_str() {
+                    graph.add_dependency(&name, kind, to, DependencyOrigin::Symlink);
+                }
+            }
+        }
+
+        graph.add_implicit_dependencies();
+
+        Ok(graph)
+    }
+
+    /// Add a unit and the dependencies it declares in its `[Unit]` and `[Install]` sections
+    ///
+    /// Call [`UnitGraph::add_implicit_dependencies()`] once all units are added.
+    pub fn insert<N: Into<String>>(&mut self, name: N, unit: SystemdUnit) {
+        let name = name.into();
+        self.units.insert(name.clone(), unit);
+        self.add_unit_dependencies(&name, &name);
+    }
+
+    /// Add a single dependency (e.g. for a symlink in a `.wants/` directory)
+    pub fn add_dependency(&mut self, from: &str, kind: DependencyKind, to: &str, origin: DependencyOrigin) {
+        self.edges
+            .entry((from.to_string(), kind, to.to_string()))
+            .or_insert(origin);
+    }
+
+    /// Add the dependencies systemd adds on its own
+    ///
+    /// This adds the dependencies of instances of templates, those from `DefaultDependencies=yes`, the ordering of
+    /// sockets, timers and paths before the unit they trigger, and the ordering of targets after the units they
+    /// pull in.
+    pub fn add_implicit_dependencies(&mut self) {
+        // instances referenced by other units get the dependencies of their template
+        let mut resolved: BTreeSet<String> = BTreeSet::new();
+        loop {
+            let instances: Vec<(String, String)> = self
+                .names()
+                .into_iter()
+                .filter(|n| !self.units.contains_key(*n) && !resolved.contains(*n))
+                .filter_map(|n| template_name(n).filter(|t| self.units.contains_key(t)).map(|t| (n.to_string(), t)))
+                .collect();
+            if instances.is_empty() {
+                break;
+            }
+            for (instance, template) in instances {
+                self.add_unit_dependencies(&instance, &template);
+                resolved.insert(instance);
+            }
+        }
+
+        let loaded: Vec<(String, UnitType)> = self
+            .names()
+            .into_iter()
+            .filter(|n| !is_template(n))
+            .filter_map(|n| self.unit(n).and(UnitType::from_name(n)).map(|t| (n.to_string(), t)))
+            .collect();
+
+        for (name, unit_type) in &loaded {
+            let Some(unit) = self.unit(name) else {
+                continue;
+            };
+
+            // the unit a socket, timer or path triggers
+            let triggers = match unit_type {
+                UnitType::Socket => Some(SOCKET_SECTION),
+                UnitType::Timer => Some(TIMER_SECTION),
+                UnitType::Path => Some(PATH_SECTION),
+                _ => None,
+            }
+            .map(|section| {
+                unit.lookup_last(section, if section == SOCKET_SECTION { "Service" } else { "Unit" })
+                    .unwrap_or_else(|| {
+                        let stem = name.rsplit_once('.').map_or(name.as_str(), |(stem, _)| stem);
+                        format!("{stem}.service")
+                    })
+            });
+
+            let deps = if self.has_default_dependencies(name) {
+                default_dependencies(*unit_type, unit)
+            } else {
+                Vec::new()
+            };
+
+            if let Some(triggers) = triggers {
+                self.add_dependency(name, DependencyKind::Before, &triggers, DependencyOrigin::Implicit);
+            }
+            for (kind, to) in deps {
+                if to != name {
+                    self.add_dependency(name, kind, to, DependencyOrigin::Implicit);
+                }
+            }
+        }
+
+        // targets are ordered after the units they pull in, unless that would create a loop
+        for (name, unit_type) in &loaded {
+            if *unit_type != UnitType::Target || !self.has_default_dependencies(name) {
+                continue;
+            }
+            let others: Vec<String> = self
+                .dependencies(name)
+                .filter(|d| DependencyKind::REQUIREMENTS.contains(&d.kind))
+                .map(|d| d.to.to_string())
+                .filter(|other| self.unit(other).is_some() && self.has_default_dependencies(other))
+                .collect();
+            for other in others {
+                if !self.is_ordered_before(name, &other) {
+                    self.add_dependency(name, DependencyKind::After, &other, DependencyOrigin::Implicit);
+                }
+            }
+        }
+    }
+
+    // add the dependencies `unit` declares as dependencies of `name`
+    fn add_unit_dependencies(&mut self, name: &str, unit: &str) {
+        if is_template(name) {
+            return;
+        }
+        let Some(unit) = self.units.get(unit) else {
+            return;
+        };
+
+        let options = LookupOptions {
+            aliases: true,
+            ..LookupOptions::default()
+        };
+        let words = |section: &str, key: &str| -> Vec<String> {
+            unit.lookup_all_matching(section, key, options)
+                .iter()
+                .flat_map(|m| SplitWord::new(m.value.raw()).collect::<Vec<_>>())
+                .map(|w| expand_specifiers(name, &w))
+                .collect()
+        };
+
+        let mut edges = Vec::new();
+        for kind in DependencyKind::ALL {
+            for to in words(UNIT_SECTION, kind.key()) {
+                edges.push((name.to_string(), kind, to, DependencyOrigin::Unit));
+            }
+        }
+        for (key, kind) in [("WantedBy", DependencyKind::Wants), ("RequiredBy", DependencyKind::Requires)] {
+            for from in words(INSTALL_SECTION, key) {
+                edges.push((from, kind, name.to_string(), DependencyOrigin::Install));
+            }
+        }
+
+        for (from, kind, to, origin) in edges {
+            self.add_dependency(&from, kind, &to, origin);
+        }
+    }
+
+    fn has_default_dependencies(&self, name: &str) -> bool {
+        self.unit(name)
+            .and_then(|u| u.lookup_last_value(UNIT_SECTION, "DefaultDependencies"))
+            .is_none_or(|v| v.to_bool().unwrap_or(true))
+    }
+
+    /// The names of all units in the graph, including units which are only referenced
+    pub fn names(&self) -> BTreeSet<&str> {
+        self.units
+            .keys()
+            .map(String::as_str)
+            .filter(|n| !is_template(n))
+            .chain(self.edges.keys().flat_map(|(from, _, to)| [from.as_str(), to.as_str()]))
+            .collect()
+    }
+
+    /// The unit called `name`, or its template if `name` is an instance
+    pub fn unit(&self, name: &str) -> Option<&SystemdUnit> {
+        self.units
+            .get(name)
+            .or_else(|| template_name(name).and_then(|t| self.units.get(&t)))
+    }
+
+    /// All dependencies in the graph
+    pub fn all_dependencies(&self) -> impl Iterator<Item = Dependency<'_>> {
+        self.edges.iter().map(|((from, kind, to), origin)| Dependency {
+            from,
+            kind: *kind,
+            to,
+            origin: *origin,
+        })
+    }
+
+    /// The dependencies of unit `name`
+    pub fn dependencies<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Dependency<'a>> {
+        self.all_dependencies().filter(move |d| d.from == name)
+    }
+
+    /// The dependencies other units have on unit `name` (e.g. the units which want it)
+    pub fn dependents<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Dependency<'a>> {
+        self.all_dependencies().filter(move |d| d.to == name)
+    }
+
+    /// All units reachable from unit `name` through dependencies of the given kinds, not including `name`
+    pub fn transitive_dependencies<'a>(&'a self, name: &'a str, kinds: &[DependencyKind]) -> BTreeSet<&'a str> {
+        let mut found = BTreeSet::new();
+        let mut queue = VecDeque::from([name]);
+
+        while let Some(current) = queue.pop_front() {
+            for d in self.dependencies(current) {
+                if kinds.contains(&d.kind) && d.to != name && found.insert(d.to) {
+                    queue.push_back(d.to);
+                }
+            }
+        }
+
+        found
+    }
+
+    // the units which start directly after each unit, from both `After=` and `Before=`
+    pub(crate) fn ordering(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut successors: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for d in self.all_dependencies() {
+            let (first, then) = match d.kind {
+                DependencyKind::Before => (d.from, d.to),
+                DependencyKind::After => (d.to, d.from),
+                _ => continue,
+            };
+            successors.entry(first).or_default().insert(then);
+        }
+        successors
+    }
+
+    /// Whether unit `a` is (transitively) ordered before unit `b`
+    pub fn is_ordered_before(&self, a: &str, b: &str) -> bool {
+        let successors = self.ordering();
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([a]);
+
+        while let Some(current) = queue.pop_front() {
+            for &next in successors.get(current).into_iter().flatten() {
+                if next == b {
+                    return true;
+                }
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Find the cycles in the ordering dependencies, one per set of units ordered against each other
+    pub fn ordering_cycles(&self) -> Vec<OrderingCycle> {
+        find_cycles(&self.ordering())
+    }
+
+    /// Export the graph in the DOT format, like `systemd-analyze dot`
+    ///
+    /// Like systemd, only ordering (green), `Requires=` (black), `Requisite=` (dark blue), `Wants=` (grey) and
+    /// `Conflicts=` (red) dependencies are drawn, ordering edges point from the later to the earlier unit.
+    pub fn to_dot(&self) -> String {
+        let mut lines = BTreeSet::new();
+        for d in self.all_dependencies() {
+            let (from, to, color) = match d.kind {
+                DependencyKind::After => (d.from, d.to, "green"),
+                DependencyKind::Before => (d.to, d.from, "green"),
+                DependencyKind::Requires => (d.from, d.to, "black"),
+                DependencyKind::Requisite => (d.from, d.to, "darkblue"),
+                DependencyKind::Wants => (d.from, d.to, "grey66"),
+                DependencyKind::Conflicts => (d.from, d.to, "red"),
+                DependencyKind::BindsTo | DependencyKind::PartOf => continue,
+            };
+            lines.insert(format!("\t\"{from}\"->\"{to}\" [color=\"{color}\"];\n"));
+        }
+
+        let mut dot = String::from("digraph systemd {\n");
+        dot.extend(lines);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// one cycle per strongly connected component of the ordering graph
+pub(crate) fn find_cycles(successors: &BTreeMap<&str, BTreeSet<&str>>) -> Vec<OrderingCycle> {
+    let mut cycles = Vec::new();
+
+    for component in strongly_connected_components(successors) {
+        let start = component[0];
+        let is_cycle = component.len() > 1 || successors.get(start).is_some_and(|s| s.contains(start));
+        if !is_cycle {
+            continue;
+        }
+
+        // the shortest path from `start` back to itself within the component
+        let members: BTreeSet<&str> = component.iter().copied().collect();
+        let mut previous: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut queue = VecDeque::from([start]);
+        'search: while let Some(current) = queue.pop_front() {
+            for &next in successors.get(current).into_iter().flatten() {
+                if !members.contains(next) || previous.contains_key(next) {
+                    continue;
+                }
+                previous.insert(next, current);
+                if next == start {
+                    break 'search;
+                }
+                queue.push_back(next);
+            }
+        }
+
+        let mut units = vec![start.to_string()];
+        let mut current = previous[start];
+        while current != start {
+            units.push(current.to_string());
+            current = previous[current];
+        }
+        units.push(start.to_string());
+        units.reverse();
+
+        cycles.push(OrderingCycle { units });
+    }
+
+    cycles
+}
+
+// Tarjan's algorithm without recursion (long chains of units would overflow the stack), each component is sorted
+fn strongly_connected_components<'a>(successors: &BTreeMap<&'a str, BTreeSet<&'a str>>) -> Vec<Vec<&'a str>> {
+    struct State<'a, 'g> {
+        successors: &'g BTreeMap<&'a str, BTreeSet<&'a str>>,
+        index: BTreeMap<&'a str, usize>,
+        low_link: BTreeMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: BTreeSet<&'a str>,
+        // the nodes being visited and their successors still to look at, in place of the call stack
+        calls: Vec<(&'a str, Vec<&'a str>)>,
+        components: Vec<Vec<&'a str>>,
+    }
+
+    impl<'a> State<'a, '_> {
+        fn enter(&mut self, node: &'a str) {
+            let index = self.index.len();
+            self.index.insert(node, index);
+            self.low_link.insert(node, index);
+            self.stack.push(node);
+            self.on_stack.insert(node);
+            let pending = self.successors.get(node).into_iter().flatten().rev().copied().collect();
+            self.calls.push((node, pending));
+        }
+
+        fn lower(&mut self, node: &'a str, low: usize) {
+            let low = self.low_link[node].min(low);
+            self.low_link.insert(node, low);
+        }
+
+        fn leave(&mut self, node: &'a str) {
+            if let Some(&(parent, _)) = self.calls.last() {
+                self.lower(parent, self.low_link[node]);
+            }
+
+            if self.low_link[node] == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut state = State {
+        successors,
+        index: BTreeMap::new(),
+        low_link: BTreeMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        calls: Vec::new(),
+        components: Vec::new(),
+    };
+    for &root in successors.keys() {
+        if state.index.contains_key(root) {
+            continue;
+        }
+
+        state.enter(root);
+        while let Some((node, pending)) = state.calls.last_mut() {
+            let (node, next) = (*node, pending.pop());
+            match next {
+                Some(next) if !state.index.contains_key(next) => state.enter(next),
+                Some(next) if state.on_stack.contains(next) => state.lower(node, state.index[next]),
+                Some(_) => {}
+                None => {
+                    state.calls.pop();
+                    state.leave(node);
+                }
+            }
+        }
+    }
+
+    state.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(units: &[(&str, &str)]) -> UnitGraph {
+        let mut graph = UnitGraph::new();
+        for (name, data) in units {
+            graph.insert(*name, SystemdUnit::load_from_str(data).unwrap());
+        }
+        graph
+    }
+
+    // (kind, to, origin) of the dependencies of `name`
+    fn deps<'a>(graph: &'a UnitGraph, name: &'a str) -> Vec<(DependencyKind, &'a str, DependencyOrigin)> {
+        graph.dependencies(name).map(|d| (d.kind, d.to, d.origin)).collect()
+    }
+
+    // a fresh directory below the system's temporary directory
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("systemd-unit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cycles(graph: &UnitGraph) -> Vec<Vec<String>> {
+        graph.ordering_cycles().into_iter().map(|c| c.units).collect()
+    }
+
+    #[test]
+    fn self_loop() {
+        let graph = graph(&[("a.service", "[Unit]\nAfter=a.service\n")]);
+        assert_eq!(cycles(&graph), [["a.service", "a.service"]]);
+    }
+
+    #[test]
+    fn no_cycle() {
+        let graph = graph(&[
+            ("a.service", "[Unit]\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nAfter=c.service\n"),
+            ("c.service", "[Unit]\nBefore=a.service\n"),
+        ]);
+        assert!(cycles(&graph).is_empty());
+        assert!(graph.is_ordered_before("c.service", "a.service"));
+        assert!(!graph.is_ordered_before("a.service", "c.service"));
+    }
+
+    #[test]
+    fn multiple_components() {
+        let graph = graph(&[
+            ("a.service", "[Unit]\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nAfter=a.service\n"),
+            ("c.service", "[Unit]\nAfter=d.service\nBefore=a.service\n"),
+            ("d.service", "[Unit]\nAfter=e.service\n"),
+            ("e.service", "[Unit]\nAfter=c.service\n"),
+            ("f.service", "[Unit]\nAfter=a.service\n"),
+        ]);
+        assert_eq!(
+            cycles(&graph),
+            [
+                vec!["a.service", "b.service", "a.service"],
+                vec!["c.service", "e.service", "d.service", "c.service"],
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_chain() {
+        let names: Vec<String> = (0..20_000).map(|i| format!("u{i:05}")).collect();
+        let mut successors: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for pair in names.windows(2) {
+            successors.entry(&pair[0]).or_default().insert(&pair[1]);
+        }
+        assert_eq!(strongly_connected_components(&successors).len(), names.len());
+        assert!(find_cycles(&successors).is_empty());
+
+        successors.entry(&names[names.len() - 1]).or_default().insert(&names[0]);
+        let cycles = find_cycles(&successors);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].units.len(), names.len() + 1);
+    }
+
+    #[test]
+    fn dot() {
+        let graph = graph(&[
+            ("a.service", "[Unit]\nRequires=b.service\nAfter=b.service\nWants=c.service\nConflicts=d.service\n"),
+            ("b.service", "[Unit]\nBefore=c.service\nPartOf=c.service\n"),
+        ]);
+        assert_eq!(
+            graph.to_dot(),
+            "digraph systemd {\n\
+             \t\"a.service\"->\"b.service\" [color=\"black\"];\n\
+             \t\"a.service\"->\"b.service\" [color=\"green\"];\n\
+             \t\"a.service\"->\"c.service\" [color=\"grey66\"];\n\
+             \t\"a.service\"->\"d.service\" [color=\"red\"];\n\
+             \t\"c.service\"->\"b.service\" [color=\"green\"];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn quoted_names() {
+        let graph = graph(&[("a.service", "[Unit]\nWants=\"my unit.service\" b.service\nAfter=%p-c.service\n")]);
+        assert_eq!(
+            deps(&graph, "a.service"),
+            [
+                (DependencyKind::Wants, "b.service", DependencyOrigin::Unit),
+                (DependencyKind::Wants, "my unit.service", DependencyOrigin::Unit),
+                (DependencyKind::After, "a-c.service", DependencyOrigin::Unit),
+            ]
+        );
+    }
+
+    #[test]
+    fn install_reverse_edges() {
+        let graph = graph(&[("a.service", "[Install]\nWantedBy=multi-user.target\nRequiredBy=b.target c.target\n")]);
+        assert!(deps(&graph, "a.service").is_empty());
+        assert_eq!(
+            deps(&graph, "multi-user.target"),
+            [(DependencyKind::Wants, "a.service", DependencyOrigin::Install)]
+        );
+        let dependents: Vec<&str> = graph.dependents("a.service").map(|d| d.from).collect();
+        assert_eq!(dependents, ["b.target", "c.target", "multi-user.target"]);
+    }
+
+    #[test]
+    fn default_dependencies() {
+        let mut graph = graph(&[
+            ("a.service", "[Unit]\nDescription=a\n"),
+            ("b.service", "[Unit]\nDefaultDependencies=no\n"),
+            ("app.target", "[Unit]\nWants=a.service b.service\n"),
+        ]);
+        graph.add_implicit_dependencies();
+
+        let a = deps(&graph, "a.service");
+        for dep in [
+            (DependencyKind::Requires, "sysinit.target"),
+            (DependencyKind::After, "sysinit.target"),
+            (DependencyKind::After, "basic.target"),
+            (DependencyKind::Conflicts, "shutdown.target"),
+            (DependencyKind::Before, "shutdown.target"),
+        ] {
+            assert!(a.contains(&(dep.0, dep.1, DependencyOrigin::Implicit)), "{dep:?}");
+        }
+        assert!(deps(&graph, "b.service").is_empty());
+
+        // targets are ordered after the units they pull in, if those have default dependencies
+        let target = deps(&graph, "app.target");
+        assert!(target.contains(&(DependencyKind::After, "a.service", DependencyOrigin::Implicit)));
+        assert!(!target.iter().any(|d| d.0 == DependencyKind::After && d.1 == "b.service"));
+    }
+
+    #[test]
+    fn templates() {
+        let mut graph = graph(&[
+            ("getty@.service", "[Unit]\nAfter=setup-%i.service\n"),
+            ("a.target", "[Unit]\nWants=getty@tty1.service\n"),
+        ]);
+        assert!(deps(&graph, "getty@.service").is_empty());
+        graph.add_implicit_dependencies();
+        assert!(deps(&graph, "getty@tty1.service").contains(&(
+            DependencyKind::After,
+            "setup-tty1.service",
+            DependencyOrigin::Unit
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_dir() {
+        let dir = dir("load-dir");
+        fs::write(dir.join("a.service"), "[Unit]\nRequires=b.service\n").unwrap();
+        fs::write(dir.join("b.service"), "[Unit]\nDefaultDependencies=no\n[Install]\nWantedBy=a.target\n").unwrap();
+        fs::write(dir.join("a.target"), "[Unit]\nDefaultDependencies=no\n").unwrap();
+        fs::write(dir.join("README"), "not a unit").unwrap();
+        std::os::unix::fs::symlink("/dev/null", dir.join("masked.service")).unwrap();
+        fs::create_dir(dir.join("a.service.d")).unwrap();
+        fs::write(dir.join("a.service.d/10-wants.conf"), "[Unit]\nWants=c.service\n").unwrap();
+        fs::write(dir.join("a.service.d/20-ignored.txt"), "[Unit]\nWants=d.service\n").unwrap();
+        fs::create_dir(dir.join("a.target.wants")).unwrap();
+        std::os::unix::fs::symlink("../a.service", dir.join("a.target.wants/a.service")).unwrap();
+        fs::create_dir(dir.join("a.target.requires")).unwrap();
+        std::os::unix::fs::symlink("../b.service", dir.join("a.target.requires/b.service")).unwrap();
+
+        let graph = UnitGraph::load_dir(&dir).unwrap();
+        assert!(graph.unit("a.service").is_some());
+        assert!(graph.unit("masked.service").is_none());
+        assert!(graph.unit("README").is_none());
+
+        let a = deps(&graph, "a.service");
+        assert!(a.contains(&(DependencyKind::Requires, "b.service", DependencyOrigin::Unit)));
+        assert!(a.contains(&(DependencyKind::Wants, "c.service", DependencyOrigin::Unit)));
+        assert!(!a.iter().any(|d| d.1 == "d.service"));
+        assert!(a.contains(&(DependencyKind::Requires, "sysinit.target", DependencyOrigin::Implicit)));
+
+        assert_eq!(
+            deps(&graph, "a.target"),
+            [
+                (DependencyKind::Requires, "b.service", DependencyOrigin::Symlink),
+                (DependencyKind::Wants, "a.service", DependencyOrigin::Symlink),
+                (DependencyKind::Wants, "b.service", DependencyOrigin::Install),
+            ]
+        );
+
+        assert_eq!(UnitGraph::load_dir(dir.join("missing")).unwrap_err().kind(), ErrorKind::Io);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use std::path::Path;
+use super::*;
+
+// settings which don't depend on what the service does
+const BASELINE: &[(&str, &str)] = &[
+    ("NoNewPrivileges", "yes"),
+    ("PrivateTmp", "yes"),
+    ("ProtectSystem", "strict"),
+    ("ProtectKernelTunables", "yes"),
+    ("ProtectKernelModules", "yes"),
+    ("ProtectKernelLogs", "yes"),
+    ("ProtectControlGroups", "yes"),
+    ("ProtectClock", "yes"),
+    ("ProtectHostname", "yes"),
+    ("ProtectProc", "invisible"),
+    ("ProcSubset", "pid"),
+    ("KeyringMode", "private"),
+    ("LockPersonality", "yes"),
+    ("MemoryDenyWriteExecute", "yes"),
+    ("RestrictRealtime", "yes"),
+    ("RestrictSUIDSGID", "yes"),
+    ("RestrictNamespaces", "yes"),
+    ("SystemCallArchitectures", "native"),
+    ("UMask", "0077"),
+];
+
+// (setting, base directory) of the directories systemd manages for a service
+const MANAGED_DIRECTORIES: &[(&str, &str)] = &[
+    ("RuntimeDirectory", "/run"),
+    ("StateDirectory", "/var/lib"),
+    ("CacheDirectory", "/var/cache"),
+    ("LogsDirectory", "/var/log"),
+];
+
+// (setting, capabilities systemd removes from the bounding set when it's enabled)
+const IMPLIED_CAPABILITY_DROPS: &[(&str, &[&str])] = &[
+    ("PrivateDevices", &["CAP_MKNOD", "CAP_SYS_RAWIO"]),
+    ("ProtectKernelModules", &["CAP_SYS_MODULE"]),
+    ("ProtectKernelLogs", &["CAP_SYSLOG"]),
+    ("ProtectClock", &["CAP_SYS_TIME", "CAP_WAKE_ALARM"]),
+];
+
+const HOME_DIRECTORIES: &[&str] = &["/home", "/root", "/run/user"];
+
+fn is_home_path(path: &str) -> bool {
+    let path = path.trim_start_matches(['-', '+', ':', '@', '!']);
+    HOME_DIRECTORIES.iter().any(|h| Path::new(path).starts_with(h))
+}
+
+impl SystemdUnit {
+    /// Propose sandboxing settings for the `[Service]` section, based on what the service declares.
+    ///
+    /// The proposal is returned as a separate drop-in (e.g. for `foo.service.d/50-hardening.conf`), `self` isn't
+    /// modified. Settings the service already configures are left alone.
+    pub fn harden(&self) -> SystemdUnit {
+        let mut drop_in = SystemdUnit::new();
+        let is_set = |key: &str| self.has_key(SERVICE_SECTION, key);
+        let values = |key: &str| -> Vec<String> {
+            self.effective_values(SERVICE_SECTION, key)
+                .into_iter()
+                .flat_map(|v| SplitWord::new(v.raw()))
+                .collect()
+        };
+        let propose = |drop_in: &mut SystemdUnit, key: &str, value: &str| {
+            if !is_set(key) {
+                drop_in.append_entry_value(SERVICE_SECTION, key, EntryValue::from_raw(value));
+            }
+        };
+
+        for (key, value) in BASELINE {
+            propose(&mut drop_in, key, value);
+        }
+
+        // devices have to stay accessible if the service asks for some
+        if !is_set("DeviceAllow") {
+            propose(&mut drop_in, "PrivateDevices", "yes");
+        }
+
+        // home directories have to stay accessible if the service uses them
+        let uses_home = ["WorkingDirectory", "ReadWritePaths", "ReadOnlyPaths", "BindPaths", "ExecStart"]
+            .iter()
+            .flat_map(|key| values(key))
+            .any(|p| is_home_path(&p));
+        let writes_home = values("ReadWritePaths").iter().any(|p| is_home_path(p));
+        if writes_home {
+            // ProtectHome= would break the service
+        } else if uses_home {
+            propose(&mut drop_in, "ProtectHome", "read-only");
+        } else {
+            propose(&mut drop_in, "ProtectHome", "yes");
+        }
+
+        // ProtectSystem=strict makes everything read-only, so allow writing where the service declares it writes
+        if !is_set("ProtectSystem") {
+            let mut writable: Vec<String> = Vec::new();
+            for (key, base) in MANAGED_DIRECTORIES {
+                for dir in values(key) {
+                    writable.push(format!("-{base}/{dir}"));
+                }
+            }
+            if let Some(pid_file) = self.lookup_last(SERVICE_SECTION, "PIDFile")
+                && let Some(dir) = Path::new(&pid_file).parent().and_then(|d| d.to_str())
+            {
+                writable.push(format!("-{dir}"));
+            }
+            if !writable.is_empty() {
+                drop_in.append_entry_value(
+                    SERVICE_SECTION,
+                    "ReadWritePaths",
+                    EntryValue::from_raw(quote_words(writable.iter().map(String::as_str))),
+                );
+            }
+        }
+
+        let private_network = self
+            .lookup_last_value(SERVICE_SECTION, "PrivateNetwork")
+            .is_some_and(|v| v.to_bool().unwrap_or(false));
+        if private_network {
+            propose(&mut drop_in, "RestrictAddressFamilies", "AF_UNIX");
+        } else {
+            propose(&mut drop_in, "RestrictAddressFamilies", "AF_UNIX AF_INET AF_INET6");
+        }
+
+        if !is_set("SystemCallFilter") {
+            drop_in.append_entry_value(SERVICE_SECTION, "SystemCallFilter", EntryValue::from_raw("@system-service"));
+            drop_in.append_entry_value(
+                SERVICE_SECTION,
+                "SystemCallFilter",
+                EntryValue::from_raw("~@privileged @resources"),
+            );
+            propose(&mut drop_in, "SystemCallErrorNumber", "EPERM");
+        }
+
+        let user = self.lookup_last(SERVICE_SECTION, "User").filter(|u| !u.is_empty());
+        let dynamic_user = self
+            .lookup_last_value(SERVICE_SECTION, "DynamicUser")
+            .is_some_and(|v| v.to_bool().unwrap_or(false));
+        let is_root = match &user {
+            Some(user) => user == "root" || user == "0",
+            None => !dynamic_user,
+        };
+
+        // the bounding set must at least contain the ambient capabilities
+        let ambient = values("AmbientCapabilities");
+        if !ambient.is_empty() {
+            propose(&mut drop_in, "CapabilityBoundingSet", &ambient.join(" "));
+        } else if !is_root {
+            // unprivileged services don't need any capabilities
+            propose(&mut drop_in, "CapabilityBoundingSet", "");
+        } else {
+            // root keeps its capabilities, except for those the sandboxing takes away anyway
+            let enabled = |key: &str| {
+                drop_in
+                    .lookup_last_value(SERVICE_SECTION, key)
+                    .or_else(|| self.lookup_last_value(SERVICE_SECTION, key))
+                    .is_some_and(|v| v.to_bool().unwrap_or(false))
+            };
+            let dropped: Vec<&str> = IMPLIED_CAPABILITY_DROPS
+                .iter()
+                .filter(|(key, _)| enabled(key))
+                .flat_map(|(_, caps)| caps.iter().copied())
+                .collect();
+            if !dropped.is_empty() {
+                propose(&mut drop_in, "CapabilityBoundingSet", &format!("~{}", dropped.join(" ")));
+            }
+        }
+
+        if !is_root {
+            propose(&mut drop_in, "RemoveIPC", "yes");
+        }
+
+        drop_in
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(data: &str) -> SystemdUnit {
+        SystemdUnit::load_from_str(data).unwrap()
+    }
+
+    // the service with the proposed drop-in applied
+    fn hardened(service: &SystemdUnit) -> SystemdUnit {
+        let mut merged = service.flatten();
+        merged.merge_effective(&service.harden());
+        merged
+    }
+
+    fn failed(assessment: &SecurityAssessment) -> Vec<&str> {
+        assessment.checks.iter().filter(|c| c.badness.is_some_and(|b| b > 0)).map(|c| c.id).collect()
+    }
+
+    #[test]
+    fn improves_exposure() {
+        let service = unit("[Service]\nExecStart=/usr/bin/foo\nDynamicUser=yes\n");
+        let before = service.security_assessment();
+        let after = hardened(&service).security_assessment();
+        assert!(after.exposure() < before.exposure(), "{after}");
+        assert!(after.level() <= ExposureLevel::Ok, "{after}");
+
+        let failed = failed(&after);
+        assert!(!failed.iter().any(|id| id.starts_with("CapabilityBoundingSet=")), "{failed:?}");
+        assert!(!failed.contains(&"SystemCallFilter=~@resources"), "{failed:?}");
+        assert!(!failed.contains(&"SystemCallFilter=~@privileged"), "{failed:?}");
+        assert!(!failed.contains(&"RestrictNamespaces=~user"), "{failed:?}");
+    }
+
+    #[test]
+    fn root() {
+        let service = unit("[Service]\nExecStart=/usr/bin/foo\n");
+        let before = service.security_assessment();
+        let after = hardened(&service).security_assessment();
+        assert!(after.exposure() < before.exposure(), "{after}");
+
+        // only the capabilities the sandboxing removes anyway
+        let drop_in = service.harden();
+        assert_eq!(
+            drop_in.lookup_last(SERVICE_SECTION, "CapabilityBoundingSet").unwrap(),
+            "~CAP_MKNOD CAP_SYS_RAWIO CAP_SYS_MODULE CAP_SYSLOG CAP_SYS_TIME CAP_WAKE_ALARM"
+        );
+        let failed = failed(&after);
+        assert!(!failed.contains(&"CapabilityBoundingSet=~CAP_SYS_MODULE"), "{failed:?}");
+        assert!(failed.contains(&"CapabilityBoundingSet=~CAP_SYS_ADMIN"), "{failed:?}");
+        assert!(!drop_in.has_key(SERVICE_SECTION, "RemoveIPC"));
+    }
+
+    #[test]
+    fn keeps_settings() {
+        let service = unit(
+            "[Service]\nUser=foo\nProtectSystem=full\nSystemCallFilter=@known\nAmbientCapabilities=CAP_NET_RAW\n\
+             DeviceAllow=/dev/null\nPrivateNetwork=yes\n",
+        );
+        let drop_in = service.harden();
+        assert!(!drop_in.has_key(SERVICE_SECTION, "ProtectSystem"));
+        assert!(!drop_in.has_key(SERVICE_SECTION, "SystemCallFilter"));
+        assert!(!drop_in.has_key(SERVICE_SECTION, "PrivateDevices"));
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "CapabilityBoundingSet").unwrap(), "CAP_NET_RAW");
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "RestrictAddressFamilies").unwrap(), "AF_UNIX");
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "RemoveIPC").unwrap(), "yes");
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "NoNewPrivileges").unwrap(), "yes");
+    }
+
+    #[test]
+    fn home() {
+        let drop_in = unit("[Service]\nExecStart=/usr/bin/foo\n").harden();
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "ProtectHome").unwrap(), "yes");
+
+        let drop_in = unit("[Service]\nExecStart=-/home/foo/bin/foo\n").harden();
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "ProtectHome").unwrap(), "read-only");
+
+        let drop_in = unit("[Service]\nReadWritePaths=/srv \"/home/foo/my data\"\n").harden();
+        assert!(!drop_in.has_key(SERVICE_SECTION, "ProtectHome"));
+
+        // a reset forgets the earlier paths
+        let drop_in = unit("[Service]\nReadWritePaths=/home/foo\nReadWritePaths=\n").harden();
+        assert_eq!(drop_in.lookup_last(SERVICE_SECTION, "ProtectHome").unwrap(), "yes");
+    }
+
+    #[test]
+    fn writable_directories() {
+        let drop_in = unit("[Service]\nStateDirectory=\"my app\" foo\nPIDFile=/run/foo/foo.pid\n").harden();
+        let paths = drop_in.lookup_last_value(SERVICE_SECTION, "ReadWritePaths").unwrap();
+        // the directories are split from the raw value, so quoted names stay intact
+        let paths: Vec<String> = SplitWord::new(paths.raw()).collect();
+        assert_eq!(paths, ["-/var/lib/my app", "-/var/lib/foo", "-/run/foo"]);
+    }
+}
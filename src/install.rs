@@ -0,0 +1,527 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use super::*;
+
+/// The directory `systemctl enable` creates its symlinks in
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/systemd/system";
+
+/// The directories system units are loaded from, in order of precedence
+pub const SYSTEM_UNIT_PATH: &[&str] = &[
+    "/etc/systemd/system",
+    "/run/systemd/system",
+    "/usr/local/lib/systemd/system",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+];
+
+/// A change to the file system, paths are relative to the root directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InstallChange {
+    Symlink { link: PathBuf, target: PathBuf },
+    Unlink { link: PathBuf },
+}
+
+impl fmt::Display for InstallChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallChange::Symlink { link, target } => {
+                write!(f, "Created symlink '{}' → '{}'.", link.display(), target.display())
+            }
+            InstallChange::Unlink { link } => write!(f, "Removed '{}'.", link.display()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "symlinks aren't supported"))
+}
+
+/// What `systemctl --root=DIR enable`, `disable`, `mask` and `unmask` do, without calling `systemctl`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstallRoot {
+    root: PathBuf,
+    config_dir: PathBuf,
+    unit_path: Vec<PathBuf>,
+}
+
+impl InstallRoot {
+    /// Use the system unit directories below `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            config_dir: SYSTEM_CONFIG_DIR.into(),
+            unit_path: SYSTEM_UNIT_PATH.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    /// Use other unit directories (e.g. for user units), `config_dir` is where symlinks are created
+    pub fn with_unit_path<P: Into<PathBuf>>(mut self, config_dir: P, unit_path: Vec<PathBuf>) -> Self {
+        self.config_dir = config_dir.into();
+        self.unit_path = unit_path;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where `path` (relative to the root directory) is on this machine
+    pub fn host_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    // the path (relative to the root directory) `path` links to, if it exists, resolving symlinks below the root
+    // directory like `systemctl --root=` does instead of following absolute links on this machine
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut path = path.to_path_buf();
+        // the kernel's limit of nested symlinks
+        for _ in 0..40 {
+            let host_path = self.host_path(&path);
+            match fs::read_link(&host_path) {
+                // an absolute target replaces the path
+                Ok(target) => path = path.parent().unwrap_or(Path::new("/")).join(target),
+                Err(_) => return host_path.exists().then_some(path),
+            }
+        }
+        None
+    }
+
+    fn is_masked_link(&self, link: &Path) -> bool {
+        fs::read_link(self.host_path(link)).is_ok_and(|target| target == Path::new("/dev/null"))
+    }
+
+    /// Whether unit `name` is masked
+    pub fn is_masked(&self, name: &str) -> bool {
+        self.is_masked_link(&self.config_dir.join(name))
+    }
+
+    /// The path (relative to the root directory) of the unit file of `name`, or of its template if `name` is an
+    /// instance
+    pub fn find_unit(&self, name: &str) -> Option<PathBuf> {
+        let candidates = [Some(name.to_string()), template_name(name)];
+        candidates.iter().flatten().find_map(|name| {
+            self.unit_path
+                .iter()
+                .map(|dir| dir.join(name))
+                .find(|path| self.resolve(path).is_some() && !self.is_masked_link(path))
+        })
+    }
+
+    /// The names of all unit files, without aliases and masked units
+    pub fn unit_files(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        for dir in &self.unit_path {
+            let Ok(entries) = fs::read_dir(self.host_path(dir)) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let is_file = entry.file_type().is_ok_and(|t| t.is_file());
+                let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                if is_file && UnitType::from_name(&name).is_some() && !self.is_masked(&name) {
+                    names.insert(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Load the unit file of `name` (see [`InstallRoot::find_unit()`])
+    pub fn load_unit(&self, name: &str) -> Result<SystemdUnit, Error> {
+        let path = self.find_unit(name).and_then(|path| self.resolve(&path)).ok_or_else(|| {
+            Error::new(ErrorKind::Io, format!("Unit file {name} does not exist."))
+                .with_source(io::Error::from(io::ErrorKind::NotFound))
+        })?;
+        SystemdUnit::load_from_file(self.host_path(path))
+    }
+
+    /// Create the symlinks for the `[Install]` section of unit `name`, following `Also=`
+    pub fn enable(&self, name: &str) -> Result<Vec<InstallChange>, Error> {
+        let mut changes = Vec::new();
+        let mut seen = BTreeSet::new();
+        self.enable_recursive(name, &mut seen, &mut changes)?;
+        Ok(changes)
+    }
+
+    fn enable_recursive(
+        &self,
+        name: &str,
+        seen: &mut BTreeSet<String>,
+        changes: &mut Vec<InstallChange>,
+    ) -> Result<(), Error> {
+        if !seen.insert(name.to_string()) {
+            return Ok(());
+        }
+        if self.is_masked(name) {
+            return Err(Error::new(ErrorKind::Masked, format!("Unit file {name} is masked.")));
+        }
+        let unit = self.load_unit(name)?;
+        changes.extend(self.enable_unit(name, &unit)?);
+
+        for also in unit.lookup_all_with_reset(INSTALL_SECTION, "Also") {
+            for also in SplitWord::new(also) {
+                self.enable_recursive(&expand_specifiers(name, &also), seen, changes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the symlinks for the `[Install]` section of `unit` called `name`, without following `Also=`
+    ///
+    /// A template (e.g. `foo@.service`) is enabled with its `DefaultInstance=`, if it has one.
+    pub fn enable_unit(&self, name: &str, unit: &SystemdUnit) -> Result<Vec<InstallChange>, Error> {
+        if self.is_masked(name) {
+            return Err(Error::new(ErrorKind::Masked, format!("Unit file {name} is masked.")));
+        }
+        let target = self.find_unit(name).unwrap_or_else(|| self.config_dir.join(name));
+
+        let instance = if is_template(name) {
+            match unit.lookup_last(INSTALL_SECTION, "DefaultInstance").filter(|i| !i.is_empty()) {
+                Some(instance) => {
+                    let (prefix, _, suffix) = split_instance(name).unwrap_or_default();
+                    format!("{prefix}@{instance}.{suffix}")
+                }
+                // there's nothing to link without an instance
+                None => return Ok(Vec::new()),
+            }
+        } else {
+            name.to_string()
+        };
+
+        let mut links = Vec::new();
+        for (key, dir) in [("WantedBy", "wants"), ("RequiredBy", "requires"), ("UpheldBy", "upholds")] {
+            for value in unit.lookup_all_with_reset(INSTALL_SECTION, key) {
+                for by in SplitWord::new(value) {
+                    let by = expand_specifiers(&instance, &by);
+                    links.push(self.config_dir.join(format!("{by}.{dir}")).join(&instance));
+                }
+            }
+        }
+        for value in unit.lookup_all_with_reset(INSTALL_SECTION, "Alias") {
+            for alias in SplitWord::new(value) {
+                links.push(self.config_dir.join(expand_specifiers(&instance, &alias)));
+            }
+        }
+
+        let mut changes = Vec::new();
+        for link in links {
+            let host_link = self.host_path(&link);
+            match fs::read_link(&host_link) {
+                Ok(existing) if existing == target => continue,
+                Err(_) if host_link.symlink_metadata().is_err() => {}
+                _ => {
+                    let e = io::Error::new(io::ErrorKind::AlreadyExists, "File exists");
+                    return Err(Error::from(e).in_file(&link));
+                }
+            }
+
+            if let Some(parent) = host_link.parent() {
+                fs::create_dir_all(parent).map_err(|e| Error::from(e).in_file(&link))?;
+            }
+            symlink(&target, &host_link).map_err(|e| Error::from(e).in_file(&link))?;
+            changes.push(InstallChange::Symlink {
+                link,
+                target: target.clone(),
+            });
+        }
+
+        Ok(changes)
+    }
+
+    /// Remove the symlinks to unit `name` (and its aliases and instances) and those of the units in its `Also=`
+    ///
+    /// Like `systemctl disable`, this doesn't unmask the unit.
+    pub fn disable(&self, name: &str) -> Result<Vec<InstallChange>, Error> {
+        let mut names = BTreeSet::new();
+        let mut queue = vec![name.to_string()];
+        while let Some(name) = queue.pop() {
+            if !names.insert(name.clone()) {
+                continue;
+            }
+            if let Ok(unit) = self.load_unit(&name) {
+                for value in unit.lookup_all_with_reset(INSTALL_SECTION, "Also") {
+                    queue.extend(SplitWord::new(value).map(|also| expand_specifiers(&name, &also)));
+                }
+                for value in unit.lookup_all_with_reset(INSTALL_SECTION, "Alias") {
+                    names.extend(SplitWord::new(value).map(|alias| expand_specifiers(&name, &alias)));
+                }
+            }
+        }
+
+        // links to the template are only removed when disabling the template, not one of its instances
+        let targets: BTreeSet<PathBuf> = names
+            .iter()
+            .filter(|n| template_name(n).is_none())
+            .filter_map(|n| self.find_unit(n))
+            .collect();
+        let matches = |link_name: &str, target: &Path| {
+            names.iter().any(|n| {
+                n == link_name
+                    || (is_template(n) && template_name(link_name).as_deref() == Some(n.as_str()))
+            }) || targets.contains(target)
+        };
+
+        let mut changes = Vec::new();
+        let mut dirs = vec![self.config_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = fs::read_dir(self.host_path(&dir)) else {
+                continue;
+            };
+            let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+            entries.sort_by_key(|e| e.file_name());
+
+            for entry in entries {
+                let link = dir.join(entry.file_name());
+                let Some(link_name) = entry.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                let Ok(target) = fs::read_link(entry.path()) else {
+                    // only the `.wants/`, `.requires/` and `.upholds/` directories below the config directory
+                    if dir == self.config_dir && entry.path().is_dir() {
+                        dirs.push(link);
+                    }
+                    continue;
+                };
+                if target == Path::new("/dev/null") || !matches(&link_name, &target) {
+                    continue;
+                }
+
+                fs::remove_file(entry.path()).map_err(|e| Error::from(e).in_file(&link))?;
+                changes.push(InstallChange::Unlink { link });
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Link unit `name` to `/dev/null` so it can't be started
+    pub fn mask(&self, name: &str) -> Result<Vec<InstallChange>, Error> {
+        let link = self.config_dir.join(name);
+        let host_link = self.host_path(&link);
+        if self.is_masked(name) {
+            return Ok(Vec::new());
+        }
+        if host_link.symlink_metadata().is_ok() {
+            let e = io::Error::new(io::ErrorKind::AlreadyExists, "File exists");
+            return Err(Error::from(e).in_file(&link));
+        }
+
+        if let Some(parent) = host_link.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::from(e).in_file(&link))?;
+        }
+        symlink(Path::new("/dev/null"), &host_link).map_err(|e| Error::from(e).in_file(&link))?;
+
+        Ok(vec![InstallChange::Symlink {
+            link,
+            target: "/dev/null".into(),
+        }])
+    }
+
+    /// Remove the `/dev/null` link of unit `name`
+    pub fn unmask(&self, name: &str) -> Result<Vec<InstallChange>, Error> {
+        if !self.is_masked(name) {
+            return Ok(Vec::new());
+        }
+        let link = self.config_dir.join(name);
+        fs::remove_file(self.host_path(&link)).map_err(|e| Error::from(e).in_file(&link))?;
+
+        Ok(vec![InstallChange::Unlink { link }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VENDOR_DIR: &str = "usr/lib/systemd/system";
+
+    // a fresh directory below the system's temporary directory with unit files `units` (name, contents)
+    fn root(name: &str, units: &[(&str, &str)]) -> InstallRoot {
+        let root = std::env::temp_dir().join(format!("systemd-unit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(VENDOR_DIR)).unwrap();
+        for (name, data) in units {
+            fs::write(root.join(VENDOR_DIR).join(name), data).unwrap();
+        }
+        InstallRoot::new(root)
+    }
+
+    fn symlink_change(link: &str, target: &str) -> InstallChange {
+        InstallChange::Symlink {
+            link: PathBuf::from(SYSTEM_CONFIG_DIR).join(link),
+            target: target.into(),
+        }
+    }
+
+    fn unlink_change(link: &str) -> InstallChange {
+        InstallChange::Unlink {
+            link: PathBuf::from(SYSTEM_CONFIG_DIR).join(link),
+        }
+    }
+
+    fn link_target(root: &InstallRoot, link: &str) -> Option<PathBuf> {
+        fs::read_link(root.host_path(Path::new(SYSTEM_CONFIG_DIR).join(link))).ok()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn enable_and_disable() {
+        let root = root(
+            "install-enable",
+            &[
+                (
+                    "foo.service",
+                    "[Install]\nWantedBy=multi-user.target\nRequiredBy=a.target\nAlias=bar.service\nAlso=foo.socket\n",
+                ),
+                ("foo.socket", "[Install]\nWantedBy=sockets.target\nAlso=foo.service\n"),
+            ],
+        );
+        let foo = "/usr/lib/systemd/system/foo.service";
+        let socket = "/usr/lib/systemd/system/foo.socket";
+
+        let changes = root.enable("foo.service").unwrap();
+        assert_eq!(
+            changes,
+            [
+                symlink_change("multi-user.target.wants/foo.service", foo),
+                symlink_change("a.target.requires/foo.service", foo),
+                symlink_change("bar.service", foo),
+                symlink_change("sockets.target.wants/foo.socket", socket),
+            ]
+        );
+        assert_eq!(link_target(&root, "bar.service"), Some(foo.into()));
+        assert_eq!(
+            changes[2].to_string(),
+            format!("Created symlink '/etc/systemd/system/bar.service' → '{foo}'.")
+        );
+
+        // the alias is found through its link to the unit below the root directory
+        assert_eq!(root.find_unit("bar.service"), Some(PathBuf::from(SYSTEM_CONFIG_DIR).join("bar.service")));
+        assert!(root.load_unit("bar.service").is_ok());
+        assert!(!root.unit_files().contains("bar.service"));
+
+        // enabling again changes nothing
+        assert!(root.enable("foo.service").unwrap().is_empty());
+
+        let mut changes = root.disable("foo.service").unwrap();
+        changes.sort_by_key(|c| c.to_string());
+        assert_eq!(
+            changes,
+            [
+                unlink_change("a.target.requires/foo.service"),
+                unlink_change("bar.service"),
+                unlink_change("multi-user.target.wants/foo.service"),
+                unlink_change("sockets.target.wants/foo.socket"),
+            ]
+        );
+        assert_eq!(link_target(&root, "bar.service"), None);
+        assert!(root.disable("foo.service").unwrap().is_empty());
+        assert_eq!(root.find_unit("bar.service"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn templates() {
+        let root = root(
+            "install-templates",
+            &[
+                ("getty@.service", "[Install]\nWantedBy=getty.target\nDefaultInstance=tty1\n"),
+                ("worker@.service", "[Install]\nWantedBy=multi-user.target\n"),
+            ],
+        );
+        let getty = "/usr/lib/systemd/system/getty@.service";
+
+        assert_eq!(
+            root.enable("getty@.service").unwrap(),
+            [symlink_change("getty.target.wants/getty@tty1.service", getty)]
+        );
+        assert_eq!(
+            root.enable("getty@tty2.service").unwrap(),
+            [symlink_change("getty.target.wants/getty@tty2.service", getty)]
+        );
+        assert_eq!(root.find_unit("getty@tty3.service"), Some(PathBuf::from(getty)));
+
+        // nothing to link without an instance
+        assert!(root.enable("worker@.service").unwrap().is_empty());
+
+        // disabling an instance keeps the others, disabling the template removes all
+        assert_eq!(
+            root.disable("getty@tty2.service").unwrap(),
+            [unlink_change("getty.target.wants/getty@tty2.service")]
+        );
+        assert!(link_target(&root, "getty.target.wants/getty@tty1.service").is_some());
+        assert_eq!(
+            root.disable("getty@.service").unwrap(),
+            [unlink_change("getty.target.wants/getty@tty1.service")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_links() {
+        let root = root(
+            "install-existing",
+            &[
+                ("foo.service", "[Install]\nAlias=bar.service\n"),
+                ("other.service", "[Install]\nAlias=baz.service\n"),
+            ],
+        );
+        let config_dir = root.host_path(SYSTEM_CONFIG_DIR);
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("bar.service"), "[Unit]\n").unwrap();
+        symlink(Path::new("/usr/lib/systemd/system/foo.service"), &config_dir.join("baz.service")).unwrap();
+
+        let e = root.enable("foo.service").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Io);
+        assert_eq!(e.path(), Some(Path::new("/etc/systemd/system/bar.service")));
+
+        // a link to another unit is a conflict too
+        let e = root.enable("other.service").unwrap_err();
+        assert_eq!(e.path(), Some(Path::new("/etc/systemd/system/baz.service")));
+
+        let e = root.enable("missing.service").unwrap_err();
+        assert_eq!(e.message(), "Unit file missing.service does not exist.");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mask_and_unmask() {
+        let root = root(
+            "install-mask",
+            &[("foo.service", "[Install]\nWantedBy=multi-user.target\nAlso=bar.service\n"), ("bar.service", "")],
+        );
+
+        assert_eq!(root.mask("bar.service").unwrap(), [symlink_change("bar.service", "/dev/null")]);
+        assert!(root.is_masked("bar.service"));
+        assert!(root.mask("bar.service").unwrap().is_empty());
+        // the masked unit's file is still found below the unit path
+        assert!(root.find_unit("bar.service").is_some());
+        assert!(!root.unit_files().contains("bar.service"));
+
+        assert_eq!(root.enable("bar.service").unwrap_err().kind(), ErrorKind::Masked);
+        // like systemctl, the links of the units before the masked one in `Also=` stay
+        assert_eq!(root.enable("foo.service").unwrap_err().kind(), ErrorKind::Masked);
+        assert!(link_target(&root, "multi-user.target.wants/foo.service").is_some());
+        // disabling doesn't unmask
+        assert!(root.disable("bar.service").unwrap().is_empty());
+        assert!(root.is_masked("bar.service"));
+
+        assert_eq!(root.unmask("bar.service").unwrap(), [unlink_change("bar.service")]);
+        assert!(!root.is_masked("bar.service"));
+        assert!(root.unmask("bar.service").unwrap().is_empty());
+        assert!(root.enable("foo.service").unwrap().is_empty());
+
+        // a unit file in the config directory can't be masked
+        fs::write(root.host_path("/etc/systemd/system/foo.service"), "").unwrap();
+        assert_eq!(root.mask("foo.service").unwrap_err().path(), Some(Path::new("/etc/systemd/system/foo.service")));
+    }
+}
@@ -1,55 +1,65 @@
+mod borrowed;
+mod builder;
+mod condition;
 mod constants;
+#[cfg(feature = "serde")]
+mod de;
+mod diff;
+#[cfg(feature = "serde")]
+mod document;
+mod dropin;
+mod edit;
+mod environment;
+mod error;
+mod graph;
+mod harden;
+mod install;
+mod lint;
+mod lookup;
 mod parser;
+mod preset;
 mod quoted;
+mod schema;
+mod security;
+#[cfg(feature = "serde")]
+mod ser;
 mod split;
+mod transaction;
+mod validate;
 mod value;
+mod writer;
+pub use self::borrowed::*;
+pub use self::builder::*;
+pub use self::condition::*;
 pub use self::constants::*;
+#[cfg(feature = "serde")]
+pub use self::de::*;
+pub use self::diff::*;
+#[cfg(feature = "serde")]
+pub use self::document::*;
+pub use self::environment::*;
+pub use self::error::*;
+pub use self::graph::*;
+pub use self::install::*;
+pub use self::lint::*;
+pub use self::lookup::*;
+pub use self::preset::*;
 pub use self::quoted::*;
+pub use self::schema::*;
+pub use self::security::*;
+#[cfg(feature = "serde")]
+pub use self::ser::*;
 pub use self::split::*;
+pub use self::transaction::*;
+pub use self::validate::*;
 pub use self::value::*;
+pub use self::writer::*;
 
 use ordered_multimap::list_ordered_multimap::ListOrderedMultimap;
-use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
-use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 
-// TODO: mimic https://doc.rust-lang.org/std/num/enum.IntErrorKind.html
-// TODO: use thiserror?
-#[derive(Debug, PartialEq)]
-#[non_exhaustive]
-pub enum Error {
-    ParseBool,
-    Unquoting(String),
-    Unit(parser::ParseError),
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Error::ParseBool => {
-                write!(
-                    f,
-                    "value must be one of `1`, `yes`, `true`, `on`, `0`, `no`, `false`, `off`"
-                )
-            }
-            Error::Unquoting(msg) => {
-                write!(f, "failed unquoting value: {msg}")
-            }
-            Error::Unit(e) => {
-                write!(f, "failed to parse unit file: {e}")
-            }
-        }
-    }
-}
-
-impl From<parser::ParseError> for Error {
-    fn from(e: parser::ParseError) -> Self {
-        Error::Unit(e)
-    }
-}
-
 pub fn parse_bool(s: &str) -> Result<bool, Error> {
     if ["1", "yes", "true", "on"].contains(&s) {
         return Ok(true);
@@ -57,10 +67,13 @@ pub fn parse_bool(s: &str) -> Result<bool, Error> {
         return Ok(false);
     }
 
-    Err(Error::ParseBool)
+    Err(Error::new(
+        ErrorKind::Bool,
+        format!("{s:?} isn't one of `1`, `yes`, `true`, `on`, `0`, `no`, `false`, `off`"),
+    ))
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct SystemdUnit {
     pub path: Option<PathBuf>,
     sections: ListOrderedMultimap<SectionKey, Entries>,
@@ -90,6 +103,39 @@ impl SystemdUnit {
             .append(key.into(), value);
     }
 
+    /// The values of `key` in `section` systemd uses, according to its [`key_kind()`]
+    ///
+    /// That's the last value of a scalar, the values after the last empty one of a list and the non-empty values of a
+    /// dependency. An empty value resets a scalar, so there's none.
+    pub fn effective_values<S, K>(&self, section: S, key: K) -> Vec<&EntryValue>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        let section = section.into();
+        let key = key.into();
+        key_kind(&section, &key).effective(self.lookup_all_values(section, key))
+    }
+
+    // the distinct sections, in order of their first appearance
+    pub(crate) fn section_names(&self) -> Vec<&str> {
+        self.sections.keys().map(String::as_str).collect()
+    }
+
+    // the values of the distinct keys of all instances of `section`, in order of their first appearance
+    pub(crate) fn grouped_entries(&self, section: &str) -> Vec<(&str, Vec<&EntryValue>)> {
+        let mut grouped: Vec<(&str, Vec<&EntryValue>)> = Vec::new();
+        for entries in self.sections.get_all(section) {
+            for (key, value) in entries.data.iter() {
+                match grouped.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, values)) => values.push(value),
+                    None => grouped.push((key, vec![value])),
+                }
+            }
+        }
+        grouped
+    }
+
     pub fn has_key<S, K>(&self, section: S, key: K) -> bool
     where
         S: Into<String>,
@@ -97,7 +143,7 @@ impl SystemdUnit {
     {
         self.sections
             .get(&section.into())
-            .map_or(false, |e| e.data.contains_key(&key.into()))
+            .is_some_and(|e| e.data.contains_key(&key.into()))
     }
 
     /// Retrun `true` if there's an (non-empty) instance of section `name`
@@ -105,6 +151,11 @@ impl SystemdUnit {
         self.sections.contains_key(&name.into())
     }
 
+    /// Return `true` if there are no sections
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
     /// Number of unique sections (i.e. with different names)
     pub fn len(&self) -> usize {
         self.sections.keys_len()
@@ -118,6 +169,18 @@ impl SystemdUnit {
         Ok(unit)
     }
 
+    /// Load from a file, remembering its path
+    ///
+    /// Errors will carry `path` as context.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).map_err(|e| Error::from(e).in_file(path))?;
+        let mut unit = Self::load_from_str(&data).map_err(|e| e.in_file(path))?;
+        unit.path = Some(path.to_path_buf());
+
+        Ok(unit)
+    }
+
     /// Get an interator of values for all `key`s in all instances of `section`
     pub fn lookup_all<S, K>(&self, section: S, key: K) -> impl DoubleEndedIterator<Item = String>
     where
@@ -127,6 +190,21 @@ impl SystemdUnit {
         self.lookup_all_values(section, key).map(|v| v.unquote())
     }
 
+    /// Get an interator of values for all `key`s in all instances of `section`, unquoted according to `mode`
+    pub fn lookup_all_with<S, K>(
+        &self,
+        section: S,
+        key: K,
+        mode: UnquoteMode,
+    ) -> impl DoubleEndedIterator<Item = Result<&str, Error>>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        self.lookup_all_values(section, key)
+            .map(move |v| v.unquote_with(mode))
+    }
+
     /// Get an interator of values for all `key`s in all instances of `section`
     pub fn lookup_all_values<S, K>(
         &self,
@@ -142,7 +220,6 @@ impl SystemdUnit {
             .unwrap_or_default()
             .data
             .get_all(&key.into())
-            .map(|v| v)
     }
 
     /// Get a Vec of values for all `key`s in all instances of `section`
@@ -158,7 +235,7 @@ impl SystemdUnit {
             .unwrap_or_default()
             .data
             .get_all(&key.into())
-            .map(|v| v.unquoted().as_str());
+            .map(|v| v.unquote_relaxed());
 
         // size_hint.0 is not optimal, but may prevent forseeable growing
         let est_cap = values.size_hint().0;
@@ -181,6 +258,21 @@ impl SystemdUnit {
         self.lookup_last_value(section, key).map(|v| v.unquote())
     }
 
+    // Get the last value for `key` in all instances of `section`, unquoted according to `mode`
+    pub fn lookup_last_with<S, K>(
+        &self,
+        section: S,
+        key: K,
+        mode: UnquoteMode,
+    ) -> Option<Result<&str, Error>>
+    where
+        S: Into<String>,
+        K: Into<String>,
+    {
+        self.lookup_last_value(section, key)
+            .map(|v| v.unquote_with(mode))
+    }
+
     // Get the last value for `key` in all instances of `section`
     pub fn lookup_last_value<S, K>(&self, section: S, key: K) -> Option<&EntryValue>
     where
@@ -192,7 +284,7 @@ impl SystemdUnit {
             .unwrap_or_default()
             .data
             .get_all(&key.into())
-            .last()
+            .next_back()
     }
 
     pub fn new() -> Self {
@@ -210,6 +302,38 @@ impl SystemdUnit {
         }
     }
 
+    /// Merge `other` (e.g. a drop-in) like systemd does and keep only the effective values (see
+    /// [`SystemdUnit::flatten()`])
+    pub fn merge_effective(&mut self, other: &SystemdUnit) {
+        self.merge_from(other);
+        *self = self.flatten();
+    }
+
+    /// A copy with only the effective values of each key (see [`SystemdUnit::effective_values()`])
+    ///
+    /// Overridden scalars, cleared lists and empty assignments are dropped, so the result means the same to systemd
+    /// and merging another drop-in into it gives the same result as into the original. Only a cleared key of
+    /// [`EMPTY_SET_KEYS`] keeps its empty assignment.
+    pub fn flatten(&self) -> SystemdUnit {
+        let mut flat = SystemdUnit::new();
+        flat.path = self.path.clone();
+
+        for section in self.section_names() {
+            for (key, _) in self.grouped_entries(section) {
+                let values = self.effective_values(section, key);
+                // dropping the reset would give these all items instead of none
+                if values.is_empty() && EMPTY_SET_KEYS.contains(&key) {
+                    flat.append_entry_value(section, key, EntryValue::from_raw(""));
+                }
+                for value in values {
+                    flat.append_entry_value(section, key, value.clone());
+                }
+            }
+        }
+
+        flat
+    }
+
     pub fn path(&self) -> &Option<PathBuf> {
         &self.path
     }
@@ -266,13 +390,15 @@ impl SystemdUnit {
         self.set_entry_value(section, key, EntryValue::from_unquoted(value));
     }
 
+    /// Set the last value of `key` to the (already quoted) `value` as is, see [`SystemdUnit::try_set_entry_raw()`]
+    /// to validate it
     pub fn set_entry_raw<S, K, V>(&mut self, section: S, key: K, value: V)
     where
         S: Into<String>,
         K: Into<String>,
         V: Into<String>,
     {
-        self.set_entry_value(section, key, EntryValue::try_from_raw(value).unwrap());
+        self.set_entry_value(section, key, EntryValue::from_raw(value));
     }
 
     pub fn set_entry_value<S, K>(&mut self, section: S, key: K, value: EntryValue)
@@ -297,36 +423,100 @@ impl SystemdUnit {
             entries.data.append(key.clone(), v);
         }
         // ... and append a "new" last value
-        entries.data.append(key.into(), value);
+        entries.data.append(key, value);
     }
 
     /// Write to a writer
     pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
-        write!(writer, "# Automatically generated by systemd-unit-rs\n")?;
-
-        for (section, entries) in &self.sections {
-            write!(writer, "[{}]\n", section)?;
-            for (k, v) in &entries.data {
-                write!(writer, "{}={}\n", k, v.raw())?;
-            }
-            write!(writer, "\n")?;
-        }
-
-        Ok(())
+        self.write_with(writer, &WriteOptions::default())
     }
 
+    /// Write `service_name` in `output_path` atomically (see [`SystemdUnit::write_file()`])
     pub fn generate_service_file(
         &self,
         output_path: &Path,
         service_name: &PathBuf,
     ) -> io::Result<()> {
-        let out_filename = output_path.join(service_name);
+        self.write_file(output_path.join(service_name), &FileOptions::default())
+            .map(|_| ())
+            .map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(data: &str) -> SystemdUnit {
+        SystemdUnit::load_from_str(data).unwrap()
+    }
+
+    fn values(unit: &SystemdUnit, section: &str, key: &str) -> Vec<String> {
+        unit.lookup_all(section, key).collect()
+    }
+
+    #[test]
+    fn flatten_scalar() {
+        let flat = unit("[Unit]\nDescription=a\nDescription=b\n[Service]\nUser=a\nUser=\n").flatten();
+        assert_eq!(values(&flat, "Unit", "Description"), ["b"]);
+        // an empty assignment resets a scalar, so nothing is left
+        assert!(!flat.has_key("Service", "User"));
+    }
+
+    #[test]
+    fn flatten_list() {
+        let flat = unit("[Service]\nExecStart=/bin/a\nExecStart=\nExecStart=/bin/b\nExecStart=/bin/c\n").flatten();
+        assert_eq!(values(&flat, "Service", "ExecStart"), ["/bin/b", "/bin/c"]);
+
+        let cleared = unit("[Service]\nEnvironment=A=1\nEnvironment=\n").flatten();
+        assert!(!cleared.has_key("Service", "Environment"));
+
+        // no capabilities isn't the same as not restricting them
+        let flat = unit("[Service]\nCapabilityBoundingSet=CAP_KILL\nCapabilityBoundingSet=\n").flatten();
+        assert_eq!(values(&flat, "Service", "CapabilityBoundingSet"), [""]);
+        let flat = unit("[Service]\nCapabilityBoundingSet=\nCapabilityBoundingSet=CAP_KILL\n").flatten();
+        assert_eq!(values(&flat, "Service", "CapabilityBoundingSet"), ["CAP_KILL"]);
+    }
+
+    #[test]
+    fn flatten_dependency() {
+        let flat = unit("[Unit]\nAfter=a.service\nAfter=\nAfter=b.service\n").flatten();
+        assert_eq!(values(&flat, "Unit", "After"), ["a.service", "b.service"]);
+    }
+
+    #[test]
+    fn merge_effective() {
+        let vendor = unit("[Unit]\nDescription=Vendor\nWants=a.service\n[Service]\nExecStart=/bin/a\nUser=a\n");
+        let drop_in = unit("[Unit]\nWants=\nWants=b.service\n[Service]\nExecStart=\nExecStart=/bin/b\nUser=\n");
+
+        let mut merged = vendor.flatten();
+        merged.merge_effective(&drop_in);
+        assert_eq!(values(&merged, "Unit", "Description"), ["Vendor"]);
+        assert_eq!(values(&merged, "Unit", "Wants"), ["a.service", "b.service"]);
+        assert_eq!(values(&merged, "Service", "ExecStart"), ["/bin/b"]);
+        assert!(!merged.has_key("Service", "User"));
+
+        // merging into the original gives the same result
+        let mut original = vendor;
+        original.merge_from(&drop_in);
+        assert_eq!(original.flatten(), merged);
+    }
+
+    #[test]
+    fn generate_service_file() {
+        let dir = std::env::temp_dir().join(format!("systemd-unit-generate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let unit = unit("[Service]\nExecStart=/bin/foo\n");
 
-        let out_file = File::create(&out_filename)?;
-        let mut writer = BufWriter::new(out_file);
+        // the underlying I/O error is kept
+        let e = unit.generate_service_file(&dir, &PathBuf::from("foo.service")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
 
-        self.write_to(&mut writer)?;
+        fs::create_dir_all(&dir).unwrap();
+        unit.generate_service_file(&dir, &PathBuf::from("foo.service")).unwrap();
+        let written = SystemdUnit::load_from_file(dir.join("foo.service")).unwrap();
+        assert_eq!(written.lookup_last("Service", "ExecStart").as_deref(), Some("/bin/foo"));
 
-        Ok(())
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,677 @@
+use std::collections::HashSet;
+use std::fmt;
+use super::*;
+
+/// How bad a lint finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    /// systemd refuses to load or start the unit
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.write_str(s)
+    }
+}
+
+/// What a rule looks at
+pub struct LintTarget<'a> {
+    /// The unit name (e.g. `foo.service`), if known
+    pub name: Option<&'a str>,
+    pub unit: &'a SystemdUnit,
+    pub unit_type: Option<UnitType>,
+    /// The names of all units linted together (`None` when linting a single unit)
+    pub unit_names: Option<&'a HashSet<&'a str>>,
+}
+
+// (section, key, message)
+type Finding = (Option<String>, Option<String>, String);
+
+/// A lint rule with a stable ID
+#[derive(Clone, Copy, Debug)]
+pub struct Rule {
+    /// Stable ID (e.g. `SU001`), use it to enable or disable the rule
+    pub id: &'static str,
+    pub name: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+    check: fn(&LintTarget<'_>) -> Vec<Finding>,
+}
+
+/// All rules known to the [`Linter`]
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "SU001",
+        name: "oneshot-restart",
+        severity: Severity::Error,
+        description: "Type=oneshot services don't support Restart=always and Restart=on-success",
+        check: check_oneshot_restart,
+    },
+    Rule {
+        id: "SU002",
+        name: "missing-exec-start",
+        severity: Severity::Error,
+        description: "services need ExecStart= unless they are Type=oneshot",
+        check: check_missing_exec_start,
+    },
+    Rule {
+        id: "SU003",
+        name: "multiple-exec-start",
+        severity: Severity::Error,
+        description: "only Type=oneshot services may have more than one ExecStart=",
+        check: check_multiple_exec_start,
+    },
+    Rule {
+        id: "SU004",
+        name: "relative-exec-path",
+        severity: Severity::Warning,
+        description: "executables in Exec*= should be absolute paths",
+        check: check_relative_exec_path,
+    },
+    Rule {
+        id: "SU005",
+        name: "install-key-outside-install",
+        severity: Severity::Warning,
+        description: "WantedBy=, RequiredBy=, UpheldBy=, Alias= and Also= are only read from the [Install] section",
+        check: check_install_key_outside_install,
+    },
+    Rule {
+        id: "SU006",
+        name: "user-with-dynamic-user",
+        severity: Severity::Warning,
+        description: "with DynamicUser=yes, User= only names the dynamically allocated user",
+        check: check_user_with_dynamic_user,
+    },
+    Rule {
+        id: "SU007",
+        name: "timer-without-unit",
+        severity: Severity::Error,
+        description: "the unit activated by a timer must exist",
+        check: check_timer_without_unit,
+    },
+    Rule {
+        id: "SU008",
+        name: "socket-without-service",
+        severity: Severity::Error,
+        description: "the service activated by a socket must exist",
+        check: check_socket_without_service,
+    },
+    Rule {
+        id: "SU009",
+        name: "unknown-key",
+        severity: Severity::Warning,
+        description: "systemd ignores unknown sections and keys",
+        check: check_unknown_key,
+    },
+];
+
+const EXEC_KEYS: &[&str] = &[
+    "ExecCondition", "ExecStartPre", "ExecStart", "ExecStartPost", "ExecReload", "ExecStop", "ExecStopPre",
+    "ExecStopPost",
+];
+
+const INSTALL_ONLY_KEYS: &[&str] = &["WantedBy", "RequiredBy", "UpheldBy", "Alias", "Also"];
+
+fn is_service(t: &LintTarget<'_>) -> bool {
+    t.unit_type == Some(UnitType::Service)
+}
+
+fn service_type(t: &LintTarget<'_>) -> String {
+    match t.unit.lookup_last(SERVICE_SECTION, "Type") {
+        Some(service_type) => service_type,
+        // see systemd.service(5)
+        None if t.unit.lookup_all_with_reset(SERVICE_SECTION, "ExecStart").is_empty() => "oneshot".into(),
+        None => "simple".into(),
+    }
+}
+
+fn check_oneshot_restart(t: &LintTarget<'_>) -> Vec<Finding> {
+    if !is_service(t) || service_type(t) != "oneshot" {
+        return Vec::new();
+    }
+
+    match t.unit.lookup_last(SERVICE_SECTION, "Restart") {
+        // see systemd's service_verify()
+        Some(restart) if ["always", "on-success"].contains(&restart.as_str()) => vec![(
+            Some(SERVICE_SECTION.into()),
+            Some("Restart".into()),
+            format!("Restart={restart} isn't allowed for Type=oneshot services"),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+fn check_missing_exec_start(t: &LintTarget<'_>) -> Vec<Finding> {
+    if !is_service(t) || !t.unit.lookup_all_with_reset(SERVICE_SECTION, "ExecStart").is_empty() {
+        return Vec::new();
+    }
+
+    let service_type = service_type(t);
+    if service_type != "oneshot" {
+        vec![(
+            Some(SERVICE_SECTION.into()),
+            None,
+            format!("service has no ExecStart=, which is only allowed for Type=oneshot (not Type={service_type})"),
+        )]
+    } else if t.unit.lookup_all_with_reset(SERVICE_SECTION, "ExecStop").is_empty()
+        && t.unit.lookup_last(UNIT_SECTION, "SuccessAction").is_none()
+    {
+        vec![(Some(SERVICE_SECTION.into()), None, "service has no ExecStart=, ExecStop= or SuccessAction=".into())]
+    } else {
+        Vec::new()
+    }
+}
+
+fn check_multiple_exec_start(t: &LintTarget<'_>) -> Vec<Finding> {
+    if !is_service(t) {
+        return Vec::new();
+    }
+
+    let count = t.unit.lookup_all_with_reset(SERVICE_SECTION, "ExecStart").len();
+    let service_type = service_type(t);
+    if count > 1 && service_type != "oneshot" {
+        vec![(
+            Some(SERVICE_SECTION.into()),
+            Some("ExecStart".into()),
+            format!("service has {count} ExecStart= settings, which is only allowed for Type=oneshot"),
+        )]
+    } else {
+        Vec::new()
+    }
+}
+
+fn check_relative_exec_path(t: &LintTarget<'_>) -> Vec<Finding> {
+    let Some(section) = t.unit_type.and_then(|ut| ut.section()) else {
+        return Vec::new();
+    };
+
+    let mut findings = Vec::new();
+    for &key in EXEC_KEYS {
+        for value in t.unit.lookup_all_values(section, key) {
+            let command = value.raw().trim_start_matches(['@', '-', ':', '+', '!']);
+            let Some(executable) = SplitWord::new(command).next() else {
+                continue;
+            };
+            // specifiers (e.g. `%h`) may expand to an absolute path
+            if !executable.starts_with(['/', '%']) {
+                findings.push((
+                    Some(section.into()),
+                    Some(key.into()),
+                    format!("{key}= uses the relative executable {executable:?}, which is looked up in $PATH"),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+fn check_install_key_outside_install(t: &LintTarget<'_>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (section, entries) in t.unit.sections.iter() {
+        if section == INSTALL_SECTION {
+            continue;
+        }
+        for &key in INSTALL_ONLY_KEYS {
+            if entries.data.contains_key(key) {
+                findings.push((
+                    Some(section.clone()),
+                    Some(key.into()),
+                    format!("{key}= in section [{section}] is ignored, it belongs in the [Install] section"),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+fn check_user_with_dynamic_user(t: &LintTarget<'_>) -> Vec<Finding> {
+    let Some(section) = t.unit_type.and_then(|ut| ut.section()) else {
+        return Vec::new();
+    };
+
+    let dynamic_user = t
+        .unit
+        .lookup_last_value(section, "DynamicUser")
+        .is_some_and(|v| v.to_bool().unwrap_or(false));
+    match t.unit.lookup_last(section, "User") {
+        Some(user) if dynamic_user && !user.is_empty() => vec![(
+            Some(section.into()),
+            Some("User".into()),
+            format!("User={user} is combined with DynamicUser=yes, the user will be allocated dynamically"),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// The unit `name` activates by default (e.g. `foo.service` for `foo.timer`)
+fn default_activated_unit(name: &str, suffix: &str) -> Option<String> {
+    let (stem, _) = name.rsplit_once('.')?;
+    Some(format!("{stem}.{suffix}"))
+}
+
+fn check_timer_without_unit(t: &LintTarget<'_>) -> Vec<Finding> {
+    if t.unit_type != Some(UnitType::Timer) {
+        return Vec::new();
+    }
+    let (Some(name), Some(unit_names)) = (t.name, t.unit_names) else {
+        return Vec::new();
+    };
+
+    let unit = t
+        .unit
+        .lookup_last(TIMER_SECTION, "Unit")
+        .or_else(|| default_activated_unit(name, "service"));
+    match unit {
+        Some(unit) if !unit_names.contains(unit.as_str()) => vec![(
+            Some(TIMER_SECTION.into()),
+            Some("Unit".into()),
+            format!("timer activates {unit}, which doesn't exist"),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+fn check_socket_without_service(t: &LintTarget<'_>) -> Vec<Finding> {
+    if t.unit_type != Some(UnitType::Socket) {
+        return Vec::new();
+    }
+    let (Some(name), Some(unit_names)) = (t.name, t.unit_names) else {
+        return Vec::new();
+    };
+
+    let accept = t
+        .unit
+        .lookup_last_value(SOCKET_SECTION, "Accept")
+        .is_some_and(|v| v.to_bool().unwrap_or(false));
+    let service = match t.unit.lookup_last(SOCKET_SECTION, "Service") {
+        Some(service) => Some(service),
+        // Accept=yes spawns instances of the template `foo@.service`
+        None if accept => name.rsplit_once('.').map(|(stem, _)| format!("{stem}@.service")),
+        None => default_activated_unit(name, "service"),
+    };
+    match service {
+        Some(service) if !unit_names.contains(service.as_str()) => vec![(
+            Some(SOCKET_SECTION.into()),
+            Some("Service".into()),
+            format!("socket activates {service}, which doesn't exist"),
+        )],
+        _ => Vec::new(),
+    }
+}
+
+fn check_unknown_key(t: &LintTarget<'_>) -> Vec<Finding> {
+    let Some(unit_type) = t.unit_type else {
+        return Vec::new();
+    };
+
+    t.unit
+        .validate_as(unit_type)
+        .into_iter()
+        .map(|w| (Some(w.section.clone()), w.key.clone(), w.to_string()))
+        .collect()
+}
+
+/// A rule violation found by the [`Linter`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule_id: &'static str,
+    pub rule_name: &'static str,
+    pub severity: Severity,
+    /// The unit name, if known
+    pub unit: Option<String>,
+    pub section: Option<String>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(unit) = &self.unit {
+            write!(f, "{unit}: ")?;
+        }
+        write!(f, "{} [{} {}] {}", self.severity, self.rule_id, self.rule_name, self.message)
+    }
+}
+
+pub(crate) fn json_string(s: Option<&str>) -> String {
+    let Some(s) = s else {
+        return "null".into();
+    };
+
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl Diagnostic {
+    /// Render as a JSON object
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"rule_id\":{},\"rule_name\":{},\"severity\":{},\"unit\":{},\"section\":{},\"key\":{},\"message\":{}}}",
+            json_string(Some(self.rule_id)),
+            json_string(Some(self.rule_name)),
+            json_string(Some(&self.severity.to_string())),
+            json_string(self.unit.as_deref()),
+            json_string(self.section.as_deref()),
+            json_string(self.key.as_deref()),
+            json_string(Some(&self.message)),
+        )
+    }
+}
+
+/// The diagnostics of a [`Linter`] run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LintReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LintReport {
+    /// Return `true` if any diagnostic has [`Severity::Error`]
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    /// Render as a JSON array of diagnostics
+    pub fn to_json(&self) -> String {
+        let diagnostics: Vec<String> = self.diagnostics.iter().map(Diagnostic::to_json).collect();
+        format!("[{}]", diagnostics.join(","))
+    }
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in &self.diagnostics {
+            writeln!(f, "{d}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Semantic checks in the spirit of `systemd-analyze verify`
+#[derive(Clone, Debug, Default)]
+pub struct Linter {
+    disabled: HashSet<&'static str>,
+}
+
+impl Linter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disable the rule with ID (or name) `rule`
+    pub fn disable(&mut self, rule: &str) -> &mut Self {
+        if let Some(r) = RULES.iter().find(|r| r.id == rule || r.name == rule) {
+            self.disabled.insert(r.id);
+        }
+        self
+    }
+
+    /// Enable the rule with ID (or name) `rule`
+    pub fn enable(&mut self, rule: &str) -> &mut Self {
+        if let Some(r) = RULES.iter().find(|r| r.id == rule || r.name == rule) {
+            self.disabled.remove(r.id);
+        }
+        self
+    }
+
+    pub fn is_enabled(&self, rule: &str) -> bool {
+        RULES
+            .iter()
+            .find(|r| r.id == rule || r.name == rule)
+            .is_some_and(|r| !self.disabled.contains(r.id))
+    }
+
+    fn run(&self, target: &LintTarget<'_>, report: &mut LintReport) {
+        for rule in RULES.iter().filter(|r| !self.disabled.contains(r.id)) {
+            for (section, key, message) in (rule.check)(target) {
+                report.diagnostics.push(Diagnostic {
+                    rule_id: rule.id,
+                    rule_name: rule.name,
+                    severity: rule.severity,
+                    unit: target.name.map(str::to_string),
+                    section,
+                    key,
+                    message,
+                });
+            }
+        }
+    }
+
+    /// Lint a single unit (rules that need other units are skipped)
+    pub fn lint(&self, unit: &SystemdUnit) -> LintReport {
+        let name = unit
+            .path()
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str());
+        let target = LintTarget {
+            name,
+            unit,
+            unit_type: unit.unit_type(),
+            unit_names: None,
+        };
+
+        let mut report = LintReport::default();
+        self.run(&target, &mut report);
+        report
+    }
+
+    // lint unit `name` (rules that need other units are skipped)
+    pub(crate) fn lint_named(&self, name: &str, unit: &SystemdUnit) -> LintReport {
+        let target = LintTarget {
+            name: Some(name),
+            unit,
+            unit_type: UnitType::from_name(name).or_else(|| unit.unit_type()),
+            unit_names: None,
+        };
+
+        let mut report = LintReport::default();
+        self.run(&target, &mut report);
+        report
+    }
+
+    /// Lint a set of units given by name, including rules checking references between them
+    pub fn lint_all<'a, I>(&self, units: I) -> LintReport
+    where
+        I: IntoIterator<Item = (&'a str, &'a SystemdUnit)>,
+    {
+        let units: Vec<(&str, &SystemdUnit)> = units.into_iter().collect();
+        let unit_names: HashSet<&str> = units.iter().map(|(name, _)| *name).collect();
+
+        let mut report = LintReport::default();
+        for (name, unit) in units {
+            let target = LintTarget {
+                name: Some(name),
+                unit,
+                unit_type: UnitType::from_name(name).or_else(|| unit.unit_type()),
+                unit_names: Some(&unit_names),
+            };
+            self.run(&target, &mut report);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(data: &str) -> LintReport {
+        Linter::new().lint(&SystemdUnit::load_from_str(data).unwrap())
+    }
+
+    fn rule_ids(report: &LintReport) -> Vec<&'static str> {
+        report.diagnostics.iter().map(|d| d.rule_id).collect()
+    }
+
+    #[test]
+    fn oneshot_restart() {
+        for restart in ["always", "on-success"] {
+            let report = lint(&format!("[Service]\nType=oneshot\nExecStart=/bin/true\nRestart={restart}\n"));
+            assert_eq!(rule_ids(&report), ["SU001"], "Restart={restart}");
+            assert_eq!(report.diagnostics[0].key.as_deref(), Some("Restart"));
+        }
+
+        for restart in ["no", "on-failure", "on-abnormal", "on-abort", "on-watchdog"] {
+            let report = lint(&format!("[Service]\nType=oneshot\nExecStart=/bin/true\nRestart={restart}\n"));
+            assert!(report.diagnostics.is_empty(), "Restart={restart}: {report}");
+        }
+    }
+
+    #[test]
+    fn unknown_key() {
+        let report = lint("[Service]\nExecStart=/bin/true\nExecStrat=/bin/false\n");
+        assert_eq!(rule_ids(&report), ["SU009"]);
+        assert_eq!(report.diagnostics[0].section.as_deref(), Some("Service"));
+        assert_eq!(report.diagnostics[0].key.as_deref(), Some("ExecStrat"));
+    }
+
+    fn lint_all(units: &[(&str, &str)]) -> LintReport {
+        let units: Vec<(&str, SystemdUnit)> =
+            units.iter().map(|(name, data)| (*name, SystemdUnit::load_from_str(data).unwrap())).collect();
+        Linter::new().lint_all(units.iter().map(|(name, unit)| (*name, unit)))
+    }
+
+    #[test]
+    fn missing_exec_start() {
+        let report = lint("[Service]\nType=simple\n");
+        assert_eq!(rule_ids(&report), ["SU002"]);
+        assert!(report.has_errors());
+        assert_eq!(rule_ids(&lint("[Service]\nType=oneshot\n")), ["SU002"]);
+
+        // a oneshot service may do without if it has ExecStop= or SuccessAction=
+        assert!(lint("[Service]\nType=oneshot\nExecStop=/bin/true\n").diagnostics.is_empty());
+        assert!(lint("[Unit]\nSuccessAction=exit\n[Service]\n").diagnostics.is_empty());
+    }
+
+    #[test]
+    fn multiple_exec_start() {
+        let report = lint("[Service]\nExecStart=/bin/a\nExecStart=/bin/b\n");
+        assert_eq!(rule_ids(&report), ["SU003"]);
+        assert_eq!(report.diagnostics[0].key.as_deref(), Some("ExecStart"));
+
+        assert!(lint("[Service]\nType=oneshot\nExecStart=/bin/a\nExecStart=/bin/b\n").diagnostics.is_empty());
+        // an empty assignment resets the list
+        assert!(lint("[Service]\nExecStart=/bin/a\nExecStart=\nExecStart=/bin/b\n").diagnostics.is_empty());
+    }
+
+    #[test]
+    fn relative_exec_path() {
+        let report = lint("[Service]\nExecStart=-true\nExecStartPre=/bin/true\nExecStop=%h/stop\n");
+        assert_eq!(rule_ids(&report), ["SU004"]);
+        assert_eq!(report.diagnostics[0].key.as_deref(), Some("ExecStart"));
+        assert_eq!(report.diagnostics[0].severity, Severity::Warning);
+
+        let report = lint("[Socket]\nListenStream=80\nExecStartPre=mkdir /x\n");
+        assert_eq!(rule_ids(&report), ["SU004"]);
+        assert_eq!(report.diagnostics[0].section.as_deref(), Some("Socket"));
+    }
+
+    #[test]
+    fn install_key_outside_install() {
+        let report = lint("[Unit]\nWantedBy=multi-user.target\n[Service]\nExecStart=/bin/true\n");
+        // it's an unknown key, too
+        assert_eq!(rule_ids(&report), ["SU005", "SU009"]);
+        assert_eq!(report.diagnostics[0].section.as_deref(), Some("Unit"));
+        assert_eq!(report.diagnostics[0].key.as_deref(), Some("WantedBy"));
+
+        let report = lint("[Service]\nExecStart=/bin/true\n[Install]\nWantedBy=multi-user.target\n");
+        assert!(report.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn user_with_dynamic_user() {
+        let report = lint("[Service]\nExecStart=/bin/true\nDynamicUser=yes\nUser=foo\n");
+        assert_eq!(rule_ids(&report), ["SU006"]);
+        assert_eq!(report.diagnostics[0].key.as_deref(), Some("User"));
+
+        assert!(lint("[Service]\nExecStart=/bin/true\nDynamicUser=no\nUser=foo\n").diagnostics.is_empty());
+        assert!(lint("[Service]\nExecStart=/bin/true\nDynamicUser=yes\n").diagnostics.is_empty());
+    }
+
+    #[test]
+    fn timer_without_unit() {
+        let timer = "[Timer]\nOnCalendar=daily\n";
+        let service = "[Service]\nExecStart=/bin/true\n";
+        assert_eq!(rule_ids(&lint_all(&[("foo.timer", timer)])), ["SU007"]);
+        assert!(lint_all(&[("foo.timer", timer), ("foo.service", service)]).diagnostics.is_empty());
+
+        let timer = "[Timer]\nOnCalendar=daily\nUnit=bar.service\n";
+        let report = lint_all(&[("foo.timer", timer), ("foo.service", service)]);
+        assert_eq!(rule_ids(&report), ["SU007"]);
+        assert_eq!(report.diagnostics[0].unit.as_deref(), Some("foo.timer"));
+        assert!(report.diagnostics[0].message.contains("bar.service"));
+
+        // a single unit can't be checked
+        assert!(lint(timer).diagnostics.is_empty());
+    }
+
+    #[test]
+    fn socket_without_service() {
+        let service = "[Service]\nExecStart=/bin/true\n";
+        let socket = "[Socket]\nListenStream=80\n";
+        assert_eq!(rule_ids(&lint_all(&[("foo.socket", socket)])), ["SU008"]);
+        assert!(lint_all(&[("foo.socket", socket), ("foo.service", service)]).diagnostics.is_empty());
+
+        // Accept=yes activates instances of a template
+        let socket = "[Socket]\nListenStream=80\nAccept=yes\n";
+        assert_eq!(rule_ids(&lint_all(&[("foo.socket", socket), ("foo.service", service)])), ["SU008"]);
+        assert!(lint_all(&[("foo.socket", socket), ("foo@.service", service)]).diagnostics.is_empty());
+
+        let socket = "[Socket]\nListenStream=80\nService=bar.service\n";
+        assert!(lint_all(&[("foo.socket", socket), ("bar.service", service)]).diagnostics.is_empty());
+    }
+
+    #[test]
+    fn enable_and_disable() {
+        let data = "[Service]\nExecStart=true\nExecStrat=/bin/false\n";
+        let unit = SystemdUnit::load_from_str(data).unwrap();
+
+        let mut linter = Linter::new();
+        assert!(linter.is_enabled("SU004") && linter.is_enabled("unknown-key"));
+        assert_eq!(rule_ids(&linter.lint(&unit)), ["SU004", "SU009"]);
+
+        // by ID or by name
+        linter.disable("SU004").disable("unknown-key");
+        assert!(!linter.is_enabled("relative-exec-path") && !linter.is_enabled("SU009"));
+        assert!(linter.lint(&unit).diagnostics.is_empty());
+
+        linter.enable("relative-exec-path");
+        assert_eq!(rule_ids(&linter.lint(&unit)), ["SU004"]);
+
+        // unknown rules are ignored
+        linter.disable("SU999");
+        assert!(!linter.is_enabled("SU999"));
+    }
+
+    #[test]
+    fn json() {
+        let report = lint("[Service]\nExecStart=\"a b\"\n");
+        assert_eq!(
+            report.to_json(),
+            "[{\"rule_id\":\"SU004\",\"rule_name\":\"relative-exec-path\",\"severity\":\"warning\",\"unit\":null,\
+             \"section\":\"Service\",\"key\":\"ExecStart\",\
+             \"message\":\"ExecStart= uses the relative executable \\\"a b\\\", which is looked up in $PATH\"}]"
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use super::{EntryValue, SystemdUnit, MOUNT_SECTION, SERVICE_SECTION, SOCKET_SECTION, SWAP_SECTION, UNIT_SECTION};
+
+// sections with an exec context (see `systemd.exec(5)`)
+const EXEC_SECTIONS: &[&str] = &[SERVICE_SECTION, SOCKET_SECTION, MOUNT_SECTION, SWAP_SECTION];
+
+/// A deprecated key name systemd still accepts in place of `canonical`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyAlias {
+    /// Sections the alias may appear in
+    pub sections: &'static [&'static str],
+    pub alias: &'static str,
+    /// Section of the canonical key (`None` if it's the same as the alias' section)
+    pub canonical_section: Option<&'static str>,
+    pub canonical: &'static str,
+}
+
+/// Key aliases as understood by systemd's `load-fragment-gperf.gperf`
+pub const KEY_ALIASES: &[KeyAlias] = &[
+    KeyAlias { sections: &[UNIT_SECTION], alias: "BindTo", canonical_section: None, canonical: "BindsTo" },
+    KeyAlias { sections: &[UNIT_SECTION], alias: "RequiresOverridable", canonical_section: None, canonical: "Requires" },
+    KeyAlias { sections: &[UNIT_SECTION], alias: "RequisiteOverridable", canonical_section: None, canonical: "Requisite" },
+    KeyAlias { sections: &[UNIT_SECTION], alias: "PropagateReloadTo", canonical_section: None, canonical: "PropagatesReloadTo" },
+    KeyAlias { sections: &[UNIT_SECTION], alias: "PropagateReloadFrom", canonical_section: None, canonical: "ReloadPropagatedFrom" },
+    KeyAlias { sections: &[UNIT_SECTION], alias: "StartLimitInterval", canonical_section: None, canonical: "StartLimitIntervalSec" },
+    KeyAlias { sections: &[SERVICE_SECTION], alias: "StartLimitInterval", canonical_section: Some(UNIT_SECTION), canonical: "StartLimitIntervalSec" },
+    KeyAlias { sections: &[SERVICE_SECTION], alias: "StartLimitBurst", canonical_section: Some(UNIT_SECTION), canonical: "StartLimitBurst" },
+    KeyAlias { sections: &[SERVICE_SECTION], alias: "StartLimitAction", canonical_section: Some(UNIT_SECTION), canonical: "StartLimitAction" },
+    KeyAlias { sections: &[SERVICE_SECTION], alias: "FailureAction", canonical_section: Some(UNIT_SECTION), canonical: "FailureAction" },
+    KeyAlias { sections: &[SERVICE_SECTION], alias: "RebootArgument", canonical_section: Some(UNIT_SECTION), canonical: "RebootArgument" },
+    KeyAlias { sections: EXEC_SECTIONS, alias: "ReadWriteDirectories", canonical_section: None, canonical: "ReadWritePaths" },
+    KeyAlias { sections: EXEC_SECTIONS, alias: "ReadOnlyDirectories", canonical_section: None, canonical: "ReadOnlyPaths" },
+    KeyAlias { sections: EXEC_SECTIONS, alias: "InaccessibleDirectories", canonical_section: None, canonical: "InaccessiblePaths" },
+];
+
+/// Resolve `key` in `section` to its canonical `(section, key)` if it's a known alias
+pub fn canonical_key<'a>(section: &'a str, key: &'a str) -> (&'a str, &'a str) {
+    KEY_ALIASES
+        .iter()
+        .find(|a| a.alias == key && a.sections.contains(&section))
+        .map_or((section, key), |a| (a.canonical_section.unwrap_or(section), a.canonical))
+}
+
+/// How keys are matched by [`SystemdUnit::lookup_all_matching()`] and [`SystemdUnit::lookup_last_matching()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LookupOptions {
+    /// Also find known aliases of the key (see [`KEY_ALIASES`])
+    pub aliases: bool,
+    /// Ignore the (ASCII) case of keys
+    pub case_insensitive: bool,
+}
+
+/// How a key was matched
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Exactly the key that was looked up
+    Exact,
+    /// A known alias of the key that was looked up
+    Alias,
+    /// The key (or one of its aliases) spelled with a different case
+    CaseInsensitive,
+}
+
+/// A value found by a lookup, together with the spelling it was found under
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyMatch<'a> {
+    pub section: &'a str,
+    pub key: &'a str,
+    pub value: &'a EntryValue,
+    pub kind: MatchKind,
+}
+
+impl SystemdUnit {
+    /// Get all values for `key` in `section`, including aliases and differently cased keys according to `options`
+    ///
+    /// Values of a section are in file order, e.g. interleaved `BindTo=` and `BindsTo=` lines. Values of an alias in
+    /// another section (e.g. `StartLimitBurst=` in `[Service]`) are grouped by section, in order of the sections'
+    /// first appearance, because all instances of a section are merged.
+    pub fn lookup_all_matching(&self, section: &str, key: &str, options: LookupOptions) -> Vec<KeyMatch<'_>> {
+        let mut candidates = vec![(section, key)];
+        if options.aliases {
+            let (canonical_section, canonical) = canonical_key(section, key);
+            candidates.push((canonical_section, canonical));
+            for alias in KEY_ALIASES {
+                if alias.canonical == canonical {
+                    for &s in alias.sections {
+                        if alias.canonical_section.unwrap_or(s) == canonical_section {
+                            candidates.push((s, alias.alias));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        for (s, entries) in self.sections.iter() {
+            if !candidates.iter().any(|(cs, _)| cs == s) {
+                continue;
+            }
+
+            for (k, value) in entries.data.iter() {
+                let kind = candidates
+                    .iter()
+                    .filter(|(cs, _)| cs == s)
+                    .filter_map(|&(cs, ck)| {
+                        if ck == k {
+                            if (cs, ck) == (section, key) {
+                                Some(MatchKind::Exact)
+                            } else {
+                                Some(MatchKind::Alias)
+                            }
+                        } else if options.case_insensitive && ck.eq_ignore_ascii_case(k) {
+                            Some(MatchKind::CaseInsensitive)
+                        } else {
+                            None
+                        }
+                    })
+                    .min_by_key(|kind| *kind as u8);
+
+                if let Some(kind) = kind {
+                    found.push(KeyMatch { section: s, key: k, value, kind });
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Get the last value for `key` in `section`, including aliases and differently cased keys according to
+    /// `options`
+    pub fn lookup_last_matching(&self, section: &str, key: &str, options: LookupOptions) -> Option<KeyMatch<'_>> {
+        self.lookup_all_matching(section, key, options).pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALIASES: LookupOptions = LookupOptions { aliases: true, case_insensitive: false };
+
+    fn found<'a>(matches: &[KeyMatch<'a>]) -> Vec<(&'a str, &'a str, &'a str, MatchKind)> {
+        matches.iter().map(|m| (m.section, m.key, m.value.raw().as_str(), m.kind)).collect()
+    }
+
+    #[test]
+    fn canonical() {
+        assert_eq!(canonical_key("Unit", "BindTo"), ("Unit", "BindsTo"));
+        assert_eq!(canonical_key("Service", "StartLimitBurst"), ("Unit", "StartLimitBurst"));
+        assert_eq!(canonical_key("Socket", "ReadOnlyDirectories"), ("Socket", "ReadOnlyPaths"));
+        // only in the sections the alias is known in
+        assert_eq!(canonical_key("Install", "BindTo"), ("Install", "BindTo"));
+        assert_eq!(canonical_key("Unit", "After"), ("Unit", "After"));
+    }
+
+    #[test]
+    fn exact() {
+        let unit = SystemdUnit::load_from_str("[Unit]\nBindTo=a\nBindsTo=b\n").unwrap();
+        let matches = unit.lookup_all_matching("Unit", "BindsTo", LookupOptions::default());
+        assert_eq!(found(&matches), [("Unit", "BindsTo", "b", MatchKind::Exact)]);
+    }
+
+    #[test]
+    fn aliases_in_file_order() {
+        let unit = SystemdUnit::load_from_str("[Unit]\nBindTo=a\nBindsTo=b\nBindTo=c\n").unwrap();
+        let expected = [
+            ("Unit", "BindTo", "a", MatchKind::Alias),
+            ("Unit", "BindsTo", "b", MatchKind::Exact),
+            ("Unit", "BindTo", "c", MatchKind::Alias),
+        ];
+        assert_eq!(found(&unit.lookup_all_matching("Unit", "BindsTo", ALIASES)), expected);
+
+        // looking up the alias finds the canonical key, too
+        let matches = unit.lookup_all_matching("Unit", "BindTo", ALIASES);
+        assert_eq!(found(&matches)[1], ("Unit", "BindsTo", "b", MatchKind::Alias));
+        let last = unit.lookup_last_matching("Unit", "BindsTo", ALIASES).unwrap();
+        assert_eq!(last.value.raw(), "c");
+    }
+
+    #[test]
+    fn aliases_in_other_sections() {
+        let data = "[Service]\nStartLimitBurst=1\n[Unit]\nStartLimitBurst=2\n[Service]\nStartLimitBurst=3\n";
+        let unit = SystemdUnit::load_from_str(data).unwrap();
+        assert_eq!(
+            found(&unit.lookup_all_matching("Unit", "StartLimitBurst", ALIASES)),
+            [
+                ("Service", "StartLimitBurst", "1", MatchKind::Alias),
+                ("Service", "StartLimitBurst", "3", MatchKind::Alias),
+                ("Unit", "StartLimitBurst", "2", MatchKind::Exact),
+            ]
+        );
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let unit = SystemdUnit::load_from_str("[Unit]\nbindto=a\nBINDSTO=b\nBindsTo=c\n").unwrap();
+        let options = LookupOptions { aliases: true, case_insensitive: true };
+        assert_eq!(
+            found(&unit.lookup_all_matching("Unit", "BindsTo", options)),
+            [
+                ("Unit", "bindto", "a", MatchKind::CaseInsensitive),
+                ("Unit", "BINDSTO", "b", MatchKind::CaseInsensitive),
+                ("Unit", "BindsTo", "c", MatchKind::Exact),
+            ]
+        );
+
+        let options = LookupOptions { aliases: false, case_insensitive: true };
+        assert_eq!(unit.lookup_all_matching("Unit", "BindsTo", options).len(), 2);
+    }
+}
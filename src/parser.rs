@@ -1,280 +1,375 @@
 use super::*;
 
-use std::fmt::Display;
-use std::str::Chars;
+use std::borrow::Cow;
+use std::ops::Range;
 
 const LINE_CONTINUATION_REPLACEMENT: &str = " ";
+// same as systemd's LONG_LINE_MAX
+const LONG_LINE_MAX: usize = 1024 * 1024;
+const UTF8_BYTE_ORDER_MARK: &str = "\u{feff}";
+// same as systemd's WHITESPACE
+pub(crate) const WHITESPACE: [char; 4] = [' ', '\t', '\n', '\r'];
 
-type ParseResult<T> = Result<T, ParseError>;
-#[derive(Debug, PartialEq, Eq)]
-pub struct ParseError {
-    pub line: usize,
-    pub col: usize,
-    pub msg: String,
+type ParseResult<T> = Result<T, Error>;
+
+/// Line based parser mimicking systemd's `config_parse()`
+#[derive(Debug)]
+pub struct Parser<'a> {
+    buf: &'a str,
+    // byte offset of the next line to read
+    pos: usize,
+    // number of the last line read (1-based)
+    line: usize,
 }
 
-impl Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{} {}", self.line, self.col, self.msg)
+// same categories as systemd's `EndOfLineMarker`
+const EOL_NONE: u8 = 0;
+const EOL_ZERO: u8 = 1 << 0;
+const EOL_TEN: u8 = 1 << 1;
+const EOL_THIRTEEN: u8 = 1 << 2;
+
+fn categorize_eol(c: u8) -> u8 {
+    match c {
+        b'\n' => EOL_TEN,
+        b'\r' => EOL_THIRTEEN,
+        b'\0' => EOL_ZERO,
+        _ => EOL_NONE,
     }
 }
 
-#[derive(Debug)]
-pub struct Parser<'a> {
-    cur: Option<char>,
-    buf: Chars<'a>,
-    line: usize,
-    column: usize,
+/// Returns `true` if `s` ends in an (unescaped) backslash
+fn ends_with_escape(s: &str) -> bool {
+    let mut escaped = false;
+    for c in s.bytes() {
+        if escaped {
+            escaped = false;
+        } else if c == b'\\' {
+            escaped = true;
+        }
+    }
+    escaped
+}
+
+/// Same as systemd's `string_is_safe()`: no control characters, quotes or backslashes
+fn string_is_safe(s: &str) -> bool {
+    !s.chars()
+        .any(|c| (c > '\0' && c < ' ') || ['"', '\'', '\\', '\x7f'].contains(&c))
+}
+
+/// Check that `name` can be written as a section header which the parser reads back unchanged
+pub(crate) fn check_section_name(name: &str) -> Result<(), Error> {
+    let problem = if name.is_empty() {
+        "is empty"
+    } else if !string_is_safe(name) {
+        "contains control characters, quotes or backslashes"
+    } else {
+        return Ok(());
+    };
+    Err(Error::new(ErrorKind::Validation, format!("section name {name:?} {problem}")))
+}
+
+/// Check that `key` can be written as the key of an entry which the parser reads back unchanged
+pub(crate) fn check_key(key: &str) -> Result<(), Error> {
+    let problem = if key.is_empty() {
+        "is empty"
+    } else if key.contains('=') {
+        "contains '='"
+    } else if key.chars().any(|c| c.is_control()) {
+        "contains control characters"
+    } else if key.starts_with(WHITESPACE) || key.ends_with(WHITESPACE) {
+        "starts or ends with whitespace"
+    } else if key.starts_with(['#', ';']) {
+        "would start a comment"
+    } else if key.starts_with('[') {
+        "would start a section header"
+    } else {
+        return Ok(());
+    };
+    Err(Error::new(ErrorKind::Validation, format!("key {key:?} {problem}")))
+}
+
+/// Check that the raw `value` of `key` is valid and the parser reads it back unchanged
+pub(crate) fn check_raw_value(key: &str, value: &EntryValue) -> Result<(), Error> {
+    let raw = value.raw();
+    let problem = if raw.bytes().any(|c| categorize_eol(c) != EOL_NONE) {
+        "contains a line break"
+    } else if raw.starts_with(WHITESPACE) || raw.ends_with(WHITESPACE) {
+        "starts or ends with whitespace"
+    } else if ends_with_escape(raw) {
+        "ends with a line continuation"
+    } else {
+        return value
+            .unquote_with(UnquoteMode::Strict)
+            .map(|_| ())
+            .map_err(|e| Error::new(e.kind(), format!("invalid value for key {key:?}")).with_source(e));
+    };
+    Err(Error::new(ErrorKind::Validation, format!("value {raw:?} of key {key:?} {problem}")))
 }
 
 impl<'a> Parser<'a> {
     pub fn new(buf: &'a str) -> Self {
-        let mut p = Self {
-            cur: None,
-            buf: buf.chars(),
+        Self {
+            buf,
+            pos: 0,
             line: 0,
-            column: 0,
-        };
-        p.bump();
-        p
+        }
     }
 
-    fn bump(&mut self) {
-        self.cur = self.buf.next();
-        match self.cur {
-            Some('\n') => {
-                self.line += 1;
-                self.column = 0;
-            }
-            Some(..) => {
-                self.column += 1;
+    fn error(&self, line: usize, column: usize, msg: String) -> Error {
+        Error::new(ErrorKind::Syntax, msg).at(line, column)
+    }
+
+    /// Read the next physical line (without end-of-line markers).
+    ///
+    /// Like systemd's `read_line()` this accepts `\n`, `\r`, `\0` and any combination of *different* markers
+    /// (e.g. `\r\n`) as end of line.
+    fn next_line(&mut self) -> Option<&'a str> {
+        if self.pos >= self.buf.len() {
+            return None;
+        }
+
+        let bytes = self.buf.as_bytes();
+        let start = self.pos;
+        let mut end = start;
+        while end < bytes.len() && categorize_eol(bytes[end]) == EOL_NONE {
+            end += 1;
+        }
+
+        let mut next = end;
+        let mut previous_eol = EOL_NONE;
+        while next < bytes.len() {
+            let eol = categorize_eol(bytes[next]);
+            if eol == EOL_NONE || previous_eol & (eol | EOL_ZERO) != 0 {
+                break;
             }
-            None => {}
+            previous_eol |= eol;
+            next += 1;
         }
-    }
 
-    fn error(&self, msg: String) -> ParseError {
-        ParseError {
-            line: self.line,
-            col: self.column,
-            msg,
-         }
+        self.pos = next;
+        self.line += 1;
+        Some(&self.buf[start..end])
     }
 
     pub fn parse(&mut self) -> ParseResult<SystemdUnit> {
-        self.parse_unit()
+        let mut unit = SystemdUnit::new();
+        self.parse_unit(&mut unit)?;
+        Ok(unit)
     }
 
-    // COMMENT        = ('#' | ';') ANY* NL
-    fn parse_comment(&mut self) -> ParseResult<String> {
-        match self.cur {
-            Some('#' | ';') => (),
-            Some(c) => return Err(self.error(format!("expected comment, but found {c:?}"))),
-            None => return Err(self.error("expected comment, but found EOF".to_string())),
-        }
-
-        let comment = self.parse_until_any_of(&['\n']);
-        Ok(comment)
+    pub fn parse_borrowed(&mut self) -> ParseResult<SystemdUnitRef<'a>> {
+        let mut unit = SystemdUnitRef::default();
+        self.parse_unit(&mut unit)?;
+        Ok(unit)
     }
 
-    // ENTRY          = KEY WS* '=' WS* VALUE NL
-    fn parse_entry(&mut self) -> ParseResult<(EntryKey, EntryRawValue)> {
-        let key = self.parse_key()?;
+    // UNIT           = [COMMENT | SECTION]*
+    fn parse_unit<U: ParseSink<'a>>(&mut self, unit: &mut U) -> ParseResult<()> {
+        let mut section: Option<Cow<'a, str>> = None;
+        let mut section_ignored = false;
+        // first line and accumulated value of a line continuation
+        let mut continuation: Option<(usize, String)> = None;
+        let mut bom_seen = false;
+
+        while let Some(buf) = self.next_line() {
+            if buf.len() > LONG_LINE_MAX {
+                return Err(self.error(self.line, 1, "line too long".into()));
+            }
 
-        // skip whitespace before '='
-        let _ = self.parse_until_none_of(&[' ', '\t']);
-        match self.cur {
-            Some('=') => self.bump(),
-            Some(c) => return Err(self.error(format!("expected '=' after key, but found {c:?}"))),
-            None => return Err(self.error("expected '=' after key, but found EOF".to_string())),
-        }
-        // skip whitespace after '='
-        let _ = self.parse_until_none_of(&[' ', '\t']);
+            // COMMENT        = WS* ('#' | ';') ANY* NL
+            // comments are ignored everywhere, even between continued lines
+            // NOTE: like systemd this is checked before the BOM is stripped, so a comment after a BOM isn't one
+            if buf.trim_start_matches(WHITESPACE).starts_with(['#', ';']) {
+                continue;
+            }
 
-        let value = self.parse_value()?;
+            // like systemd, strip the BOM of the first line which has one
+            let l = match buf.strip_prefix(UTF8_BYTE_ORDER_MARK) {
+                Some(l) if !bom_seen => {
+                    bom_seen = true;
+                    l
+                }
+                _ => buf,
+            };
 
-        Ok((key, value))
-    }
+            let (line, text) = match continuation.take() {
+                Some((line, mut c)) => {
+                    if c.len() + l.len() > LONG_LINE_MAX {
+                        return Err(self.error(line, 1, "continuation line too long".into()));
+                    }
+                    c.push_str(l);
+                    (line, Cow::Owned(c))
+                }
+                None => (self.line, Cow::Borrowed(l)),
+            };
 
-    // KEY            = [A-Za-z0-9-]
-    fn parse_key(&mut self) -> ParseResult<EntryKey> {
-        let key: String = self.parse_until_any_of(&['=', /*+ WHITESPACE*/' ', '\t', '\n', '\r'] );
+            // CONTINUE_NL    = '\' NL
+            if ends_with_escape(&text) {
+                let mut c = text.into_owned();
+                c.pop();
+                c.push_str(LINE_CONTINUATION_REPLACEMENT);
+                continuation = Some((line, c));
+                continue;
+            }
 
-        if !key.chars().all(|c| c.is_alphanumeric() || c == '-') {
-            return Err(self.error(format!("Invalid key {:?}. Allowed characters are A-Za-z0-9-", key)))
+            self.parse_line(unit, &mut section, &mut section_ignored, line, text)?;
         }
 
-        Ok(key)
-    }
-
-    // SECTION        = SECTION_HEADER [COMMENT | ENTRY]*
-    fn parse_section(&mut self) -> ParseResult<(SectionKey, Vec<(EntryKey, EntryRawValue)>)> {
-        let name = self.parse_section_header()?;
-        let mut entries: Vec<(EntryKey, EntryRawValue)> = Vec::new();
-
-
-        while let Some(c) = self.cur {
-            match c {
-                '#' | ';' => {
-                    // ignore comment
-                    let _ = self.parse_comment();
-                },
-                '[' => break,
-                _ if c.is_ascii_whitespace() => self.bump(),
-                _ => {
-                    entries.push(self.parse_entry()?);
-                },
-            }
+        // a line continuation at EOF still terminates the value
+        if let Some((line, c)) = continuation {
+            self.parse_line(unit, &mut section, &mut section_ignored, line, Cow::Owned(c))?;
         }
 
-        Ok((name, entries))
+        Ok(())
     }
 
-    // SECTION_HEADER = '[' ANY+ ']' NL
-    fn parse_section_header(&mut self) -> ParseResult<String> {
-        match self.cur {
-            Some('[') => self.bump(),
-            Some(c) => return Err(self.error(format!("expected '[' as start of section header, but found {c:?}"))),
-            None => return Err(self.error("expected '[' as start of section header, but found EOF".to_string())),
+    // LINE           = WS* (SECTION_HEADER | ENTRY)? WS*
+    fn parse_line<U: ParseSink<'a>>(
+        &self,
+        unit: &mut U,
+        section: &mut Option<Cow<'a, str>>,
+        section_ignored: &mut bool,
+        line: usize,
+        text: Cow<'a, str>,
+    ) -> ParseResult<()> {
+        let start = text.len() - text.trim_start_matches(WHITESPACE).len();
+        let end = text.trim_end_matches(WHITESPACE).len().max(start);
+        let l = &text[start..end];
+        let column = text[..start].chars().count() + 1;
+
+        if l.is_empty() {
+            return Ok(());
         }
 
-        let section_name = self.parse_until_any_of(&[']', '\n']);
-
-        match self.cur {
-            Some(']') => self.bump(),
-            Some(c) => return Err(self.error(format!("expected ']' as end of section header, but found {c:?}"))),
-            None => return Err(self.error("expected ']' as end of section header, but found EOF".to_string())),
+        if l.starts_with('[') {
+            self.parse_section_header(line, column, l)?;
+            if l.len() == 2 {
+                log::warn!("line {line}: Unknown section ''. Ignoring.");
+                *section = None;
+                *section_ignored = true;
+            } else {
+                let name = slice(&text, start + 1..end - 1);
+                // make sure there's a section entry (even if it has no entries)
+                unit.add_section(name.clone());
+                *section = Some(name);
+                *section_ignored = false;
+            }
+            return Ok(());
         }
 
-        if section_name.is_empty() {
-            return Err(self.error("section header cannot be empty".into()));
-        } else {
-            // TODO: validate section name
+        let Some(section) = section else {
+            if !*section_ignored {
+                log::warn!("line {line}: Assignment outside of section. Ignoring.");
+            }
+            return Ok(());
+        };
+
+        // ENTRY          = KEY WS* '=' WS* VALUE
+        let Some(eq) = l.find('=') else {
+            log::warn!("line {line}: Missing '=', ignoring line.");
+            return Ok(());
+        };
+
+        // KEY            = [^=]+
+        // NOTE: like systemd we don't restrict the characters of a key, unknown keys are only rejected on lookup
+        let key_end = start + l[..eq].trim_end_matches(WHITESPACE).len();
+        if key_end == start {
+            log::warn!("line {line}: Missing key name before '=', ignoring line.");
+            return Ok(());
         }
 
-        // TODO: silently accept whitespace until EOL
+        // VALUE          = ANY* [CONTINUE_NL [COMMENT]* VALUE]
+        let value_start = end - l[eq + 1..].trim_start_matches(WHITESPACE).len();
 
-        Ok(section_name)
+        unit.add_entry(
+            section,
+            slice(&text, start..key_end),
+            slice(&text, value_start..end),
+            line,
+            column,
+        )
     }
 
-    // UNIT           = [COMMENT | SECTION]*
-    fn parse_unit(&mut self) -> ParseResult<SystemdUnit> {
-        let mut unit = SystemdUnit::new();
+    // SECTION_HEADER = '[' ANY* ']'
+    fn parse_section_header(&self, line: usize, column: usize, l: &str) -> ParseResult<()> {
+        let Some(name) = l.strip_prefix('[').and_then(|l| l.strip_suffix(']')) else {
+            return Err(self.error(line, column, format!("invalid section header {l:?}")));
+        };
 
-        while let Some(c) = self.cur {
-            match c {
-                '#' | ';' => {
-                    // ignore comment
-                    let _ = self.parse_comment();
-                },
-                '[' => {
-                    let (section, entries) = self.parse_section()?;
-                    // make sure there's a section entry (even if `entries` is empty)
-                    unit.sections.entry(section.clone()).or_insert(Entries::default());
-                    for (key, value) in entries {
-                        unit.append_entry_value(
-                            section.as_str(),
-                            key,
-                            match EntryValue::try_from_raw(value) {
-                                Ok(v) => v,
-                                Err(e) => return Err(self.error(e.to_string())),
-                            },
-                        );
-                    }
-                },
-                _ if c.is_ascii_whitespace() => self.bump(),
-                _ => return Err(self.error("Expected comment or section".into())),
-            };
+        if !string_is_safe(name) {
+            return Err(self.error(line, column, format!("bad characters in section header {l:?}")));
         }
 
-        Ok(unit)
+        Ok(())
     }
+}
 
-    fn parse_until_any_of(&mut self, end: &[char]) -> String {
-        let mut s = String::new();
+/// Borrow `range` from `text` if possible
+fn slice<'a>(text: &Cow<'a, str>, range: Range<usize>) -> Cow<'a, str> {
+    match text {
+        Cow::Borrowed(t) => Cow::Borrowed(&t[range]),
+        Cow::Owned(t) => Cow::Owned(t[range].to_string()),
+    }
+}
 
-        while let Some(c) = self.cur {
-            if end.contains(&c) {
-                break;
-            }
-            s.push(c);
-            self.bump();
-        }
+/// Receives the sections and entries found by the [`Parser`]
+pub(crate) trait ParseSink<'a> {
+    fn add_section(&mut self, name: Cow<'a, str>);
+
+    fn add_entry(
+        &mut self,
+        section: &str,
+        key: Cow<'a, str>,
+        value: Cow<'a, str>,
+        line: usize,
+        column: usize,
+    ) -> ParseResult<()>;
+}
 
-        s
+impl<'a> ParseSink<'a> for SystemdUnit {
+    fn add_section(&mut self, name: Cow<'a, str>) {
+        self.sections.entry(name.into_owned()).or_insert(Entries::default());
     }
 
-    fn parse_until_none_of(&mut self, end: &[char]) -> String {
-        let mut s = String::new();
-
-        while let Some(c) = self.cur {
-            if !end.contains(&c) {
-                break;
+    fn add_entry(
+        &mut self,
+        section: &str,
+        key: Cow<'a, str>,
+        value: Cow<'a, str>,
+        line: usize,
+        column: usize,
+    ) -> ParseResult<()> {
+        let value = match EntryValue::try_from_raw(value) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(Error::new(e.kind(), format!("invalid value for key {key:?}"))
+                    .at(line, column)
+                    .with_source(e))
             }
-            s.push(c);
-            self.bump();
-        }
+        };
 
-        s
+        self.append_entry_value(section, key, value);
+        Ok(())
     }
+}
 
-    // VALUE          = ANY* CONTINUE_NL [COMMENT]* VALUE
-    fn parse_value(&mut self) -> ParseResult<EntryRawValue> {
-        let mut value: String = String::new();
-        let mut backslash = false;
-        let mut line_continuation = false;
-
-        while let Some(c) = self.cur {
-            if backslash {
-                backslash = false;
-                match c {
-                    // line continuation -> add replacement to value and continue normally
-                    '\n' => {
-                        value.push_str(LINE_CONTINUATION_REPLACEMENT);
-                        line_continuation = true;
-                    },
-                    // just an escape sequence -> add to value and continue normally
-                    _ => {
-                        value.push('\\');
-                        value.push(c);
-                    },
-                }
-            } else if line_continuation {
-                line_continuation = false;
-                match c {
-                    '#' | ';' => {
-                        // ignore interspersed comments
-                        let _ = self.parse_comment();
-                        line_continuation = true;
-                    },
-                    // end of value
-                    '\n' => break,
-                    // start of section header (although an unexpected one), i.e. end of value
-                    // NOTE: we're trying to be clever here and assume the line continuation was a mistake
-                    '[' => break,
-                    // value continues after line continuation, add the actual line
-                    // continuation characters back to value and continue normally
-                    _ => {
-                        if c == '\\' {
-                            // we may have a line continuation following another line continuation
-                            backslash = true;
-                        } else {
-                            value.push(c);
-                        }
-                    },
-                }
-            } else {
-                match c {
-                    // may be start of a line continuation
-                    '\\' => backslash = true,
-                    // end of value
-                    '\n' => break,
-                    _ => value.push(c),
-                }
-            }
-            self.bump();
-        }
+impl<'a> ParseSink<'a> for SystemdUnitRef<'a> {
+    fn add_section(&mut self, name: Cow<'a, str>) {
+        self.sections.entry(name).or_insert(EntriesRef::default());
+    }
 
-        Ok(value)
+    fn add_entry(
+        &mut self,
+        section: &str,
+        key: Cow<'a, str>,
+        value: Cow<'a, str>,
+        _line: usize,
+        _column: usize,
+    ) -> ParseResult<()> {
+        // unquoting is deferred until the value is looked up
+        if let Some(entries) = self.sections.get_mut(section) {
+            entries.data.append(key, EntryValueRef::from_raw(value));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use super::*;
+
+/// The directories system preset files are loaded from, in order of precedence
+pub const SYSTEM_PRESET_PATH: &[&str] = &[
+    "/etc/systemd/system-preset",
+    "/run/systemd/system-preset",
+    "/usr/local/lib/systemd/system-preset",
+    "/usr/lib/systemd/system-preset",
+    "/lib/systemd/system-preset",
+];
+
+/// What a preset rule does with the units it matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PresetAction {
+    Enable,
+    Disable,
+    /// Leave the unit as it is
+    Ignore,
+}
+
+impl fmt::Display for PresetAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PresetAction::Enable => "enable",
+            PresetAction::Disable => "disable",
+            PresetAction::Ignore => "ignore",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A line of a preset file, e.g. `enable getty@.service tty1 tty2`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresetRule {
+    pub action: PresetAction,
+    /// A unit name or glob
+    pub pattern: String,
+    /// The instances to enable, if `pattern` is a template
+    pub instances: Vec<String>,
+}
+
+impl PresetRule {
+    /// Whether this rule applies to unit `name`
+    pub fn matches(&self, name: &str) -> bool {
+        if !self.instances.is_empty() && is_template(&self.pattern) {
+            if is_template(name) {
+                return self.pattern == name;
+            }
+            if let Some((_, instance, _)) = split_instance(name) {
+                return template_name(name).as_deref() == Some(self.pattern.as_str())
+                    && self.instances.iter().any(|i| i == instance);
+            }
+        }
+
+        glob_match(&self.pattern, name)
+    }
+}
+
+impl fmt::Display for PresetRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.action, self.pattern)?;
+        for instance in &self.instances {
+            write!(f, " {instance}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The preset of a unit, see [`Presets::query()`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Preset {
+    pub action: PresetAction,
+    /// The instances to enable, if the unit is a template
+    pub instances: Vec<String>,
+}
+
+/// The result of [`InstallRoot::preset_all()`]
+#[derive(Debug, Default)]
+pub struct PresetAll {
+    pub changes: Vec<InstallChange>,
+    /// The units which couldn't be preset, they don't keep the other units from being preset
+    pub errors: Vec<(String, Error)>,
+}
+
+impl PresetAll {
+    /// Whether all units were preset
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// The rules of a set of preset files, in order of evaluation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Presets {
+    pub rules: Vec<PresetRule>,
+}
+
+impl Presets {
+    /// Parse a preset file, invalid lines are ignored with a warning like systemd does
+    pub fn parse(data: &str) -> Self {
+        let mut rules = Vec::new();
+
+        for (i, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            let mut words = SplitWord::new(line);
+            let action = match words.next().as_deref() {
+                Some("enable") => PresetAction::Enable,
+                Some("disable") => PresetAction::Disable,
+                Some("ignore") => PresetAction::Ignore,
+                _ => {
+                    log::warn!("line {}: Couldn't parse line '{line}'. Ignoring.", i + 1);
+                    continue;
+                }
+            };
+            let Some(pattern) = words.next() else {
+                log::warn!("line {}: Couldn't parse line '{line}'. Ignoring.", i + 1);
+                continue;
+            };
+            let instances: Vec<String> = words.collect();
+            if !instances.is_empty() && (action != PresetAction::Enable || !is_template(&pattern)) {
+                log::warn!("line {}: Instances are only allowed for enabling templates. Ignoring.", i + 1);
+                continue;
+            }
+
+            rules.push(PresetRule {
+                action,
+                pattern,
+                instances,
+            });
+        }
+
+        Self { rules }
+    }
+
+    /// Load the `*.preset` files of `dirs` below `root`
+    ///
+    /// A file overrides files with the same name in later directories, and the files are evaluated in the order of
+    /// their names, regardless of their directory.
+    pub fn load<P: AsRef<Path>>(root: P, dirs: &[&str]) -> Result<Self, Error> {
+        let root = root.as_ref();
+        let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+
+        for dir in dirs {
+            let dir = root.join(dir.trim_start_matches('/'));
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries {
+                let entry = entry.map_err(|e| Error::from(e).in_file(&dir))?;
+                let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                if name.ends_with(".preset") {
+                    files.entry(name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+
+        let mut presets = Self::default();
+        for path in files.values() {
+            let data = fs::read_to_string(path).map_err(|e| Error::from(e).in_file(path))?;
+            presets.rules.extend(Self::parse(&data).rules);
+        }
+
+        Ok(presets)
+    }
+
+    /// Load the system preset files below `root` (see [`SYSTEM_PRESET_PATH`])
+    pub fn load_system<P: AsRef<Path>>(root: P) -> Result<Self, Error> {
+        Self::load(root, SYSTEM_PRESET_PATH)
+    }
+
+    /// The preset of unit `name`, from the first matching rule
+    ///
+    /// Units without a matching rule are enabled, like systemd does.
+    pub fn query(&self, name: &str) -> Preset {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(name))
+            .map_or(
+                Preset {
+                    action: PresetAction::Enable,
+                    instances: Vec::new(),
+                },
+                |rule| Preset {
+                    action: rule.action,
+                    instances: if is_template(name) { rule.instances.clone() } else { Vec::new() },
+                },
+            )
+    }
+}
+
+impl InstallRoot {
+    /// Enable or disable unit `name` according to `presets`, like `systemctl preset`
+    ///
+    /// A template with instances in its preset gets these instances enabled instead of its `DefaultInstance=`.
+    pub fn preset(&self, name: &str, presets: &Presets) -> Result<Vec<InstallChange>, Error> {
+        let preset = presets.query(name);
+        match preset.action {
+            PresetAction::Ignore => Ok(Vec::new()),
+            PresetAction::Disable => self.disable(name),
+            PresetAction::Enable if preset.instances.is_empty() => self.enable(name),
+            PresetAction::Enable => {
+                let unit = self.load_unit(name)?;
+                let (prefix, _, suffix) = split_instance(name).unwrap_or_default();
+                let mut changes = Vec::new();
+                for instance in &preset.instances {
+                    changes.extend(self.enable_unit(&format!("{prefix}@{instance}.{suffix}"), &unit)?);
+                }
+                Ok(changes)
+            }
+        }
+    }
+
+    /// Apply `presets` to all unit files, like `systemctl preset-all`
+    ///
+    /// Like `systemctl`, a unit which can't be preset (e.g. because a unit in its `Also=` is masked) doesn't stop the
+    /// others, its error is collected instead.
+    pub fn preset_all(&self, presets: &Presets) -> PresetAll {
+        let mut result = PresetAll::default();
+        for name in self.unit_files() {
+            match self.preset(&name, presets) {
+                Ok(changes) => result.changes.extend(changes),
+                Err(e) => result.errors.push((name, e)),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory below the system's temporary directory with `files` (path, contents)
+    fn root(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("systemd-unit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, data) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+        root
+    }
+
+    fn actions(presets: &Presets, names: &[&str]) -> Vec<PresetAction> {
+        names.iter().map(|n| presets.query(n).action).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let presets = Presets::parse(
+            "# comment\n; comment\nenable foo.service\n  disable  bar.*  \nenable getty@.service tty1 tty2\n\
+             frobnicate x.service\nenable\ndisable foo@.service a\nenable foo.service a\n",
+        );
+        let rules: Vec<String> = presets.rules.iter().map(PresetRule::to_string).collect();
+        assert_eq!(rules, ["enable foo.service", "disable bar.*", "enable getty@.service tty1 tty2"]);
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let presets = Presets::parse("disable foo.service\nenable foo.*\nenable *.socket\ndisable *\n");
+        assert_eq!(
+            actions(&presets, &["foo.service", "foo.socket", "bar.socket", "bar.service"]),
+            [PresetAction::Disable, PresetAction::Enable, PresetAction::Enable, PresetAction::Disable]
+        );
+
+        // without a matching rule a unit is enabled
+        let presets = Presets::parse("disable foo.service\nignore bar.service\n");
+        assert_eq!(
+            actions(&presets, &["foo.service", "bar.service", "baz.service"]),
+            [PresetAction::Disable, PresetAction::Ignore, PresetAction::Enable]
+        );
+    }
+
+    #[test]
+    fn instances() {
+        let presets = Presets::parse("enable getty@.service tty1 tty2\ndisable getty@*\n");
+        assert_eq!(
+            presets.query("getty@.service"),
+            Preset {
+                action: PresetAction::Enable,
+                instances: vec!["tty1".into(), "tty2".into()],
+            }
+        );
+        assert_eq!(presets.query("getty@tty2.service").action, PresetAction::Enable);
+        assert!(presets.query("getty@tty2.service").instances.is_empty());
+        assert_eq!(presets.query("getty@tty3.service").action, PresetAction::Disable);
+    }
+
+    #[test]
+    fn precedence() {
+        let root = root(
+            "preset-precedence",
+            &[
+                ("usr/lib/systemd/system-preset/90-default.preset", "disable *\n"),
+                ("usr/lib/systemd/system-preset/50-vendor.preset", "enable vendor.service\nenable admin.service\n"),
+                // overrides the vendor file of the same name
+                ("etc/systemd/system-preset/50-vendor.preset", "enable vendor.service\n"),
+                // sorts before the vendor file, regardless of the directory
+                ("usr/lib/systemd/system-preset/10-first.preset", "disable vendor.service\n"),
+                ("usr/lib/systemd/system-preset/README", "enable *\n"),
+            ],
+        );
+        let presets = Presets::load_system(&root).unwrap();
+        let rules: Vec<String> = presets.rules.iter().map(PresetRule::to_string).collect();
+        assert_eq!(rules, ["disable vendor.service", "enable vendor.service", "disable *"]);
+        assert_eq!(
+            actions(&presets, &["vendor.service", "admin.service"]),
+            [PresetAction::Disable, PresetAction::Disable]
+        );
+
+        assert!(Presets::load(root.join("missing"), SYSTEM_PRESET_PATH).unwrap().rules.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn preset_all() {
+        let root = InstallRoot::new(root(
+            "preset-all",
+            &[
+                ("usr/lib/systemd/system/a.service", "[Install]\nWantedBy=multi-user.target\n"),
+                ("usr/lib/systemd/system/b.service", "[Install]\nWantedBy=multi-user.target\nAlso=c.service\n"),
+                ("usr/lib/systemd/system/c.service", "[Install]\nWantedBy=multi-user.target\n"),
+                ("usr/lib/systemd/system/d.service", "[Install]\nWantedBy=multi-user.target\n"),
+                ("usr/lib/systemd/system/getty@.service", "[Install]\nWantedBy=getty.target\n"),
+            ],
+        ));
+        root.mask("c.service").unwrap();
+        root.enable("d.service").unwrap();
+
+        let presets = Presets::parse("enable a.service\nenable b.service\nenable getty@.service tty1\ndisable *\n");
+        let result = root.preset_all(&presets);
+        // the masked unit in `Also=` fails b.service, the others are still preset
+        assert!(!result.is_ok());
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].0, "b.service");
+        assert_eq!(result.errors[0].1.kind(), ErrorKind::Masked);
+
+        let mut links: Vec<String> = result
+            .changes
+            .iter()
+            .map(|c| match c {
+                InstallChange::Symlink { link, .. } => format!("+{}", link.display()),
+                InstallChange::Unlink { link } => format!("-{}", link.display()),
+            })
+            .collect();
+        links.sort();
+        assert_eq!(
+            links,
+            [
+                "+/etc/systemd/system/getty.target.wants/getty@tty1.service",
+                "+/etc/systemd/system/multi-user.target.wants/a.service",
+                "-/etc/systemd/system/multi-user.target.wants/d.service",
+            ]
+        );
+        // still masked
+        assert!(root.is_masked("c.service"));
+    }
+}
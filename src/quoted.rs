@@ -1,6 +1,6 @@
 use std::str::Chars;
 
-use super::Error;
+use super::{Error, ErrorKind};
 
 fn char_needs_escaping(c: char) -> bool {
     if c as usize > 128 {
@@ -33,9 +33,9 @@ pub fn quote_value(value: &str) -> String {
             '\x0b' => escaped.push_str("\\v"),
             '\x0c' => escaped.push_str("\\f"),
             '\\'   => escaped.push_str("\\\\"),
-            ' '    => escaped.push_str(" "),
+            ' '    => escaped.push(' '),
             '"'    => escaped.push_str("\\\""),
-            '\''    => escaped.push_str("'"),
+            '\''    => escaped.push('\''),
             _ => escaped.push_str(&format!("\\x{:02x}", c as isize)[..])
         }
     }
@@ -61,12 +61,31 @@ pub fn unquote_value(raw: &str) -> Result<String, Error> {
     let mut parser = Quoted {
         chars: raw.chars(),
         cur: None,
+        relaxed: false,
     };
     parser.bump();
 
     parser.parse_and_unquote()
 }
 
+/// Same as [`unquote_value()`], but never fails.
+///
+/// Like systemd's `EXTRACT_RELAX|EXTRACT_CUNESCAPE_RELAX`, invalid (i.e. unknown or malformed) escape sequences are
+/// kept as they are, including the backslash, and a trailing backslash is dropped.
+pub fn unquote_value_relaxed(raw: &str) -> String {
+    let mut parser = Quoted {
+        chars: raw.chars(),
+        cur: None,
+        relaxed: true,
+    };
+    parser.bump();
+
+    // errors are only returned in strict mode
+    parser
+        .parse_and_unquote()
+        .unwrap_or_else(|_| raw.to_string())
+}
+
 fn word_needs_escaping(word: &str) -> bool {
     word.chars().any(char_needs_escaping)
 }
@@ -74,6 +93,7 @@ fn word_needs_escaping(word: &str) -> bool {
 struct Quoted<'a> {
     chars: Chars<'a>,
     cur: Option<char>,
+    relaxed: bool,
 }
 
 impl<'a> Quoted<'a> {
@@ -87,14 +107,28 @@ impl<'a> Quoted<'a> {
 
         while self.cur.is_some() {
             match self.cur {
-                None => return Err(Error::Unquoting("found early EOF".into())),
+                None => return Err(Error::new(ErrorKind::Quoting, "found early EOF")),
                 Some('\'' | '"') if result.ends_with([' ', '\t', '\n']) || result.is_empty() => {
                     quote = self.cur;
                 }
                 Some('\\') => {
                     self.bump();
                     match self.cur {
-                        None => return Err(Error::Unquoting("expecting escape sequence, but found EOF.".into())),
+                        // eat up trailing backslash
+                        None if self.relaxed => break,
+                        None => return Err(Error::new(ErrorKind::Escape, "expecting escape sequence, but found EOF.")),
+                        Some(c) if self.relaxed => {
+                            let (chars, cur) = (self.chars.clone(), self.cur);
+                            match self.parse_escape_sequence() {
+                                Ok(r) => result.push(r),
+                                Err(_) => {
+                                    // keep the backslash and the escaped character and continue right after it
+                                    (self.chars, self.cur) = (chars, cur);
+                                    result.push('\\');
+                                    result.push(c);
+                                }
+                            }
+                        }
                         // line continuation (i.e. value continues on the next line)
                         Some(_) => result.push(self.parse_escape_sequence()?),
                     }
@@ -143,12 +177,12 @@ impl<'a> Quoted<'a> {
                 '0'..='7' => {  // 3 character octal encoding
                     self.parse_unicode_escape(None, 3, 8)?
                 }
-                c => return Err(Error::Unquoting(format!("expecting escape sequence, but found {c:?}.")))
+                c => return Err(Error::new(ErrorKind::Escape, format!("expecting escape sequence, but found {c:?}.")))
             };
 
             Ok(r)
         } else {
-            Err(Error::Unquoting("expecting escape sequence, but found EOF.".into()))
+            Err(Error::new(ErrorKind::Escape, "expecting escape sequence, but found EOF."))
         }
     }
 
@@ -161,12 +195,12 @@ impl<'a> Quoted<'a> {
             if let Some(c) = self.cur {
                 code.push(c);
                 if radix == 16 && !c.is_ascii_hexdigit() {
-                    return Err(Error::Unquoting(format!("expected {max_chars} hex values after \"\\{c}\", but got \"\\{c}{code}\"" )))
+                    return Err(Error::new(ErrorKind::Escape, format!("expected {max_chars} hex values after \"\\{c}\", but got \"\\{c}{code}\"" )))
                 } else if radix == 8 && (!c.is_ascii_digit() || c == '8' || c == '9') {
-                    return Err(Error::Unquoting(format!("expected {max_chars} octal values after \"\\\", but got \"\\{code}\"" )))
+                    return Err(Error::new(ErrorKind::Escape, format!("expected {max_chars} octal values after \"\\\", but got \"\\{code}\"" )))
                 }
             } else {
-                return Err(Error::Unquoting("expecting unicode escape sequence, but found EOF.".into()))
+                return Err(Error::new(ErrorKind::Escape, "expecting unicode escape sequence, but found EOF."))
             }
 
             if code.len() != max_chars {
@@ -176,12 +210,53 @@ impl<'a> Quoted<'a> {
 
         let ucp = u32::from_str_radix(code.as_str(), radix).unwrap();
         if ucp == 0 {
-            return Err(Error::Unquoting("\\0 character not allowed in escape sequence".into()))
+            return Err(Error::new(ErrorKind::Escape, "\\0 character not allowed in escape sequence"))
         }
 
         match char::try_from(ucp) {
             Ok(u) => Ok(u),
-            Err(e) => Err(Error::Unquoting(format!("invalid unicode character in escape sequence: {e}"))),
+            Err(e) => Err(Error::new(ErrorKind::Escape, format!("invalid unicode character in escape sequence: {e}")).with_source(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict() {
+        assert_eq!(unquote_value("plain value").unwrap(), "plain value");
+        assert_eq!(unquote_value("'a  b' \"c\\\"d\"").unwrap(), "a  b c\"d");
+        assert_eq!(unquote_value("\\a\\t\\s\\101\\x41\\u00e4\\U0001F600").unwrap(), "\u{7}\t AA\u{e4}\u{1F600}");
+        // quotes only start at the beginning of a word
+        assert_eq!(unquote_value("a\"b c\"").unwrap(), "a\"b c\"");
+    }
+
+    #[test]
+    fn strict_errors() {
+        for raw in ["\\x4g", "\\q", "abc\\", "\\x00", "\\u12"] {
+            assert_eq!(unquote_value(raw).unwrap_err().kind(), ErrorKind::Escape, "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn relaxed() {
+        assert_eq!(unquote_value_relaxed("'a  b' \\x41"), "a  b A");
+        // invalid escape sequences are kept, including the backslash
+        assert_eq!(unquote_value_relaxed("\\x4g"), "\\x4g");
+        assert_eq!(unquote_value_relaxed("a\\qb"), "a\\qb");
+        assert_eq!(unquote_value_relaxed("\\x00 \\x41"), "\\x00 A");
+        // a trailing backslash is dropped
+        assert_eq!(unquote_value_relaxed("abc\\"), "abc");
+    }
+
+    #[test]
+    fn quote() {
+        for value in ["plain", "with space", "tab\tnewline\n", "don't \"quote\"", "back\\slash", "\u{1}", "\u{e4}"] {
+            let quoted = quote_value(value);
+            assert_eq!(unquote_value(&quoted).unwrap(), value, "{quoted:?}");
         }
+        assert_eq!(quote_words(["a", "b c", "d\"e"].into_iter()), "a \"b c\" \"d\\\"e\"");
     }
 }
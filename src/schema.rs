@@ -0,0 +1,422 @@
+use std::fmt;
+use std::path::Path;
+use super::*;
+
+/// The type of a unit, as given by the suffix of its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnitType {
+    Service,
+    Socket,
+    Target,
+    Device,
+    Mount,
+    Automount,
+    Swap,
+    Timer,
+    Path,
+    Slice,
+    Scope,
+}
+
+impl UnitType {
+    pub const ALL: [UnitType; 11] = [
+        UnitType::Service,
+        UnitType::Socket,
+        UnitType::Target,
+        UnitType::Device,
+        UnitType::Mount,
+        UnitType::Automount,
+        UnitType::Swap,
+        UnitType::Timer,
+        UnitType::Path,
+        UnitType::Slice,
+        UnitType::Scope,
+    ];
+
+    /// Get the unit type from a unit name (e.g. `foo.service`) or path
+    pub fn from_name<P: AsRef<Path>>(name: P) -> Option<Self> {
+        let ext = name.as_ref().extension()?.to_str()?;
+        Self::ALL.into_iter().find(|t| t.suffix() == ext)
+    }
+
+    /// The suffix of unit names of this type (without the `.`)
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            UnitType::Service => "service",
+            UnitType::Socket => "socket",
+            UnitType::Target => "target",
+            UnitType::Device => "device",
+            UnitType::Mount => "mount",
+            UnitType::Automount => "automount",
+            UnitType::Swap => "swap",
+            UnitType::Timer => "timer",
+            UnitType::Path => "path",
+            UnitType::Slice => "slice",
+            UnitType::Scope => "scope",
+        }
+    }
+
+    /// The type specific section (e.g. `[Service]`), if any
+    pub fn section(&self) -> Option<&'static str> {
+        match self {
+            UnitType::Service => Some(SERVICE_SECTION),
+            UnitType::Socket => Some(SOCKET_SECTION),
+            UnitType::Mount => Some(MOUNT_SECTION),
+            UnitType::Automount => Some(AUTOMOUNT_SECTION),
+            UnitType::Swap => Some(SWAP_SECTION),
+            UnitType::Timer => Some(TIMER_SECTION),
+            UnitType::Path => Some(PATH_SECTION),
+            UnitType::Slice => Some(SLICE_SECTION),
+            UnitType::Scope => Some(SCOPE_SECTION),
+            UnitType::Target | UnitType::Device => None,
+        }
+    }
+
+    /// All sections valid in units of this type
+    pub fn sections(&self) -> Vec<&'static str> {
+        let mut sections = vec![UNIT_SECTION];
+        sections.extend(self.section());
+        sections.push(INSTALL_SECTION);
+        sections
+    }
+
+    /// Groups of keys valid in `section` for units of this type (`None` if the section isn't valid)
+    pub fn section_keys(&self, section: &str) -> Option<Vec<&'static [&'static str]>> {
+        if section == UNIT_SECTION {
+            return Some(vec![UNIT_KEYS]);
+        } else if section == INSTALL_SECTION {
+            return Some(vec![INSTALL_KEYS]);
+        } else if Some(section) != self.section() {
+            return None;
+        }
+
+        let keys = match self {
+            UnitType::Service => vec![SERVICE_KEYS, EXEC_KEYS, KILL_KEYS, RESOURCE_CONTROL_KEYS],
+            UnitType::Socket => vec![SOCKET_KEYS, EXEC_KEYS, KILL_KEYS, RESOURCE_CONTROL_KEYS],
+            UnitType::Mount => vec![MOUNT_KEYS, EXEC_KEYS, KILL_KEYS, RESOURCE_CONTROL_KEYS],
+            UnitType::Swap => vec![SWAP_KEYS, EXEC_KEYS, KILL_KEYS, RESOURCE_CONTROL_KEYS],
+            UnitType::Automount => vec![AUTOMOUNT_KEYS],
+            UnitType::Timer => vec![TIMER_KEYS],
+            UnitType::Path => vec![PATH_KEYS],
+            UnitType::Slice => vec![RESOURCE_CONTROL_KEYS],
+            UnitType::Scope => vec![SCOPE_KEYS, KILL_KEYS, RESOURCE_CONTROL_KEYS],
+            UnitType::Target | UnitType::Device => return None,
+        };
+        Some(keys)
+    }
+
+    /// Return `true` if `key` is valid in `section` for units of this type
+    ///
+    /// `X-` extension sections and keys, `Condition…=`/`Assert…=` in `[Unit]` and known aliases are accepted.
+    pub fn is_known_key(&self, section: &str, key: &str) -> bool {
+        if section.starts_with("X-") || key.starts_with("X-") {
+            return true;
+        }
+
+        if section == UNIT_SECTION && is_condition_key(key) {
+            return true;
+        }
+
+        if KEY_ALIASES
+            .iter()
+            .any(|a| a.alias == key && a.sections.contains(&section))
+        {
+            return true;
+        }
+
+        self.section_keys(section)
+            .is_some_and(|groups| groups.iter().any(|keys| keys.contains(&key)))
+    }
+
+    /// Return `true` if `section` is valid for units of this type (including `X-` extension sections)
+    pub fn is_known_section(&self, section: &str) -> bool {
+        section.starts_with("X-") || self.sections().contains(&section)
+    }
+}
+
+impl fmt::Display for UnitType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.suffix())
+    }
+}
+
+/// Return `true` if `key` is a `Condition…=` or `Assert…=` key
+pub fn is_condition_key(key: &str) -> bool {
+    key.strip_prefix("Condition")
+        .or_else(|| key.strip_prefix("Assert"))
+        .is_some_and(|check| CONDITION_CHECKS.contains(&check))
+}
+
+/// Checks available as `Condition…=` and `Assert…=` (see `systemd.unit(5)`)
+pub const CONDITION_CHECKS: &[&str] = &[
+    "Architecture", "Firmware", "Virtualization", "Host", "KernelCommandLine", "KernelVersion",
+    "Credential", "Environment", "Security", "Capability", "ACPower", "NeedsUpdate", "FirstBoot",
+    "PathExists", "PathExistsGlob", "PathIsDirectory", "PathIsSymbolicLink", "PathIsMountPoint",
+    "PathIsReadWrite", "PathIsEncrypted", "DirectoryNotEmpty", "FileNotEmpty", "FileIsExecutable",
+    "User", "Group", "ControlGroupController", "Memory", "CPUs", "CPUFeature", "OSRelease",
+    "MemoryPressure", "CPUPressure", "IOPressure",
+];
+
+/// Keys of the `[Unit]` section (see `systemd.unit(5)`), without conditions and asserts
+pub const UNIT_KEYS: &[&str] = &[
+    "Description", "Documentation", "Wants", "Requires", "Requisite", "BindsTo", "PartOf", "Upholds",
+    "Conflicts", "Before", "After", "OnFailure", "OnSuccess", "PropagatesReloadTo", "ReloadPropagatedFrom",
+    "PropagatesStopTo", "StopPropagatedFrom", "JoinsNamespaceOf", "RequiresMountsFor", "WantsMountsFor",
+    "OnFailureJobMode", "OnSuccessJobMode", "IgnoreOnIsolate", "StopWhenUnneeded", "RefuseManualStart",
+    "RefuseManualStop", "AllowIsolate", "DefaultDependencies", "SurviveFinalKillSignal", "CollectMode",
+    "FailureAction", "SuccessAction", "FailureActionExitStatus", "SuccessActionExitStatus", "JobTimeoutSec",
+    "JobRunningTimeoutSec", "JobTimeoutAction", "JobTimeoutRebootArgument", "StartLimitIntervalSec",
+    "StartLimitBurst", "StartLimitAction", "RebootArgument", "SourcePath",
+];
+
+/// Keys of the `[Install]` section (see `systemd.unit(5)`)
+pub const INSTALL_KEYS: &[&str] = &["Alias", "WantedBy", "RequiredBy", "UpheldBy", "Also", "DefaultInstance"];
+
+/// Keys of the `[Service]` section (see `systemd.service(5)`)
+pub const SERVICE_KEYS: &[&str] = &[
+    "Type", "ExitType", "RemainAfterExit", "GuessMainPID", "PIDFile", "BusName", "ExecStart", "ExecStartPre",
+    "ExecStartPost", "ExecCondition", "ExecReload", "ExecStop", "ExecStopPost", "RestartSec", "RestartSteps",
+    "RestartMaxDelaySec", "TimeoutStartSec", "TimeoutStopSec", "TimeoutAbortSec", "TimeoutSec",
+    "TimeoutStartFailureMode", "TimeoutStopFailureMode", "RuntimeMaxSec", "RuntimeRandomizedExtraSec",
+    "WatchdogSec", "Restart", "RestartMode", "SuccessExitStatus", "RestartPreventExitStatus",
+    "RestartForceExitStatus", "RootDirectoryStartOnly", "NonBlocking", "NotifyAccess", "Sockets",
+    "FileDescriptorStoreMax", "FileDescriptorStorePreserve", "USBFunctionDescriptors", "USBFunctionStrings",
+    "OOMPolicy", "OpenFile", "ReloadSignal", "PermissionsStartOnly",
+];
+
+/// Keys of the `[Socket]` section (see `systemd.socket(5)`)
+pub const SOCKET_KEYS: &[&str] = &[
+    "ListenStream", "ListenDatagram", "ListenSequentialPacket", "ListenFIFO", "ListenSpecial", "ListenNetlink",
+    "ListenMessageQueue", "ListenUSBFunction", "SocketProtocol", "BindIPv6Only", "Backlog", "BindToDevice",
+    "SocketUser", "SocketGroup", "SocketMode", "DirectoryMode", "Accept", "Writable", "FlushPending",
+    "MaxConnections", "MaxConnectionsPerSource", "KeepAlive", "KeepAliveTimeSec", "KeepAliveIntervalSec",
+    "KeepAliveProbes", "NoDelay", "Priority", "DeferAcceptSec", "ReceiveBuffer", "SendBuffer", "IPTOS", "IPTTL",
+    "Mark", "ReusePort", "SmackLabel", "SmackLabelIPIn", "SmackLabelIPOut", "SELinuxContextFromNet", "PipeSize",
+    "MessageQueueMaxMessages", "MessageQueueMessageSize", "FreeBind", "Transparent", "Broadcast",
+    "PassCredentials", "PassSecurity", "PassPacketInfo", "Timestamping", "TCPCongestion", "ExecStartPre",
+    "ExecStartPost", "ExecStopPre", "ExecStopPost", "TimeoutSec", "Service", "RemoveOnStop", "Symlinks",
+    "FileDescriptorName", "TriggerLimitIntervalSec", "TriggerLimitBurst", "PollLimitIntervalSec",
+    "PollLimitBurst", "PassFileDescriptorsToExec",
+];
+
+/// Keys of the `[Mount]` section (see `systemd.mount(5)`)
+pub const MOUNT_KEYS: &[&str] = &[
+    "What", "Where", "Type", "Options", "SloppyOptions", "LazyUnmount", "ReadWriteOnly", "ForceUnmount",
+    "DirectoryMode", "TimeoutSec",
+];
+
+/// Keys of the `[Automount]` section (see `systemd.automount(5)`)
+pub const AUTOMOUNT_KEYS: &[&str] = &["Where", "ExtraOptions", "DirectoryMode", "TimeoutIdleSec"];
+
+/// Keys of the `[Swap]` section (see `systemd.swap(5)`)
+pub const SWAP_KEYS: &[&str] = &["What", "Priority", "Options", "TimeoutSec"];
+
+/// Keys of the `[Timer]` section (see `systemd.timer(5)`)
+pub const TIMER_KEYS: &[&str] = &[
+    "OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "OnCalendar",
+    "AccuracySec", "RandomizedDelaySec", "FixedRandomDelay", "OnClockChange", "OnTimezoneChange", "Unit",
+    "Persistent", "WakeSystem", "RemainAfterElapse",
+];
+
+/// Keys of the `[Path]` section (see `systemd.path(5)`)
+pub const PATH_KEYS: &[&str] = &[
+    "PathExists", "PathExistsGlob", "PathChanged", "PathModified", "DirectoryNotEmpty", "Unit", "MakeDirectory",
+    "DirectoryMode", "TriggerLimitIntervalSec", "TriggerLimitBurst",
+];
+
+/// Keys of the `[Scope]` section (see `systemd.scope(5)`), without kill and resource control keys
+pub const SCOPE_KEYS: &[&str] = &["RuntimeMaxSec", "RuntimeRandomizedExtraSec", "TimeoutStopSec", "OOMPolicy"];
+
+/// Keys configuring the execution environment (see `systemd.exec(5)`)
+pub const EXEC_KEYS: &[&str] = &[
+    "WorkingDirectory", "RootDirectory", "RootImage", "RootImageOptions", "RootEphemeral", "RootHash",
+    "RootHashSignature", "RootVerity", "RootImagePolicy", "MountImagePolicy", "ExtensionImagePolicy",
+    "MountAPIVFS", "ProtectProc", "ProcSubset", "BindPaths", "BindReadOnlyPaths", "MountImages",
+    "ExtensionImages", "ExtensionDirectories", "User", "Group", "DynamicUser", "SupplementaryGroups",
+    "SetLoginEnvironment", "PAMName", "CapabilityBoundingSet", "AmbientCapabilities", "NoNewPrivileges",
+    "SecureBits", "SELinuxContext", "AppArmorProfile", "SmackProcessLabel", "LimitCPU", "LimitFSIZE", "LimitDATA",
+    "LimitSTACK", "LimitCORE", "LimitRSS", "LimitNOFILE", "LimitAS", "LimitNPROC", "LimitMEMLOCK", "LimitLOCKS",
+    "LimitSIGPENDING", "LimitMSGQUEUE", "LimitNICE", "LimitRTPRIO", "LimitRTTIME", "UMask", "CoredumpFilter",
+    "KeyringMode", "OOMScoreAdjust", "TimerSlackNSec", "Personality", "IgnoreSIGPIPE", "Nice",
+    "CPUSchedulingPolicy", "CPUSchedulingPriority", "CPUSchedulingResetOnFork", "CPUAffinity", "NUMAPolicy",
+    "NUMAMask", "IOSchedulingClass", "IOSchedulingPriority", "ProtectSystem", "ProtectHome", "RuntimeDirectory",
+    "StateDirectory", "CacheDirectory", "LogsDirectory", "ConfigurationDirectory", "RuntimeDirectoryMode",
+    "StateDirectoryMode", "CacheDirectoryMode", "LogsDirectoryMode", "ConfigurationDirectoryMode",
+    "RuntimeDirectoryPreserve", "TimeoutCleanSec", "ReadWritePaths", "ReadOnlyPaths", "InaccessiblePaths",
+    "ExecPaths", "NoExecPaths", "TemporaryFileSystem", "PrivateTmp", "PrivateDevices", "PrivateNetwork",
+    "NetworkNamespacePath", "PrivateIPC", "IPCNamespacePath", "MemoryKSM", "PrivateUsers", "ProtectHostname",
+    "ProtectClock", "ProtectKernelTunables", "ProtectKernelModules", "ProtectKernelLogs", "ProtectControlGroups",
+    "RestrictAddressFamilies", "RestrictFileSystems", "RestrictNamespaces", "LockPersonality",
+    "MemoryDenyWriteExecute", "RestrictRealtime", "RestrictSUIDSGID", "RemoveIPC", "PrivateMounts", "MountFlags",
+    "SystemCallFilter", "SystemCallErrorNumber", "SystemCallArchitectures", "SystemCallLog", "Environment",
+    "EnvironmentFile", "PassEnvironment", "UnsetEnvironment", "StandardInput", "StandardOutput", "StandardError",
+    "StandardInputText", "StandardInputData", "LogLevelMax", "LogExtraFields", "LogRateLimitIntervalSec",
+    "LogRateLimitBurst", "LogFilterPatterns", "LogNamespace", "SyslogIdentifier", "SyslogFacility", "SyslogLevel",
+    "SyslogLevelPrefix", "TTYPath", "TTYReset", "TTYVHangup", "TTYRows", "TTYColumns", "TTYVTDisallocate",
+    "LoadCredential", "LoadCredentialEncrypted", "ImportCredential", "SetCredential", "SetCredentialEncrypted",
+    "UtmpIdentifier", "UtmpMode",
+];
+
+/// Keys configuring how processes are killed (see `systemd.kill(5)`)
+pub const KILL_KEYS: &[&str] = &[
+    "KillMode", "KillSignal", "RestartKillSignal", "SendSIGHUP", "SendSIGKILL", "FinalKillSignal",
+    "WatchdogSignal",
+];
+
+/// Keys configuring resource control (see `systemd.resource-control(5)`)
+pub const RESOURCE_CONTROL_KEYS: &[&str] = &[
+    "CPUAccounting", "CPUWeight", "StartupCPUWeight", "CPUQuota", "CPUQuotaPeriodSec", "AllowedCPUs",
+    "StartupAllowedCPUs", "AllowedMemoryNodes", "StartupAllowedMemoryNodes", "MemoryAccounting", "MemoryMin",
+    "MemoryLow", "StartupMemoryLow", "DefaultStartupMemoryLow", "MemoryHigh", "StartupMemoryHigh", "MemoryMax",
+    "StartupMemoryMax", "MemorySwapMax", "StartupMemorySwapMax", "MemoryZSwapMax", "StartupMemoryZSwapMax",
+    "MemoryZSwapWriteback", "TasksAccounting", "TasksMax", "IOAccounting", "IOWeight", "StartupIOWeight",
+    "IODeviceWeight", "IOReadBandwidthMax", "IOWriteBandwidthMax", "IOReadIOPSMax", "IOWriteIOPSMax",
+    "IODeviceLatencyTargetSec", "IPAccounting", "IPAddressAllow", "IPAddressDeny", "SocketBindAllow",
+    "SocketBindDeny", "RestrictNetworkInterfaces", "NFTSet", "IPIngressFilterPath", "IPEgressFilterPath",
+    "BPFProgram", "DeviceAllow", "DevicePolicy", "Slice", "Delegate", "DelegateSubgroup", "DisableControllers",
+    "ManagedOOMSwap", "ManagedOOMMemoryPressure", "ManagedOOMMemoryPressureLimit", "ManagedOOMPreference",
+    "MemoryPressureWatch", "MemoryPressureThresholdSec", "CoredumpReceive", "DefaultMemoryMin",
+    "DefaultMemoryLow", "CPUShares", "StartupCPUShares", "MemoryLimit", "BlockIOAccounting", "BlockIOWeight",
+    "StartupBlockIOWeight", "BlockIODeviceWeight", "BlockIOReadBandwidth", "BlockIOWriteBandwidth",
+];
+
+/// How repeated assignments of a key combine (see `systemd.syntax(7)`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KeyKind {
+    /// The last assignment wins, an empty one resets the key to its default
+    Scalar,
+    /// Assignments accumulate, an empty one clears the list
+    List,
+    /// Assignments accumulate and can't be cleared, empty ones are ignored
+    Dependency,
+}
+
+impl KeyKind {
+    // the effective ones of the assignments `values` (in order) of a key of this kind
+    pub(crate) fn effective<'a, I>(self, values: I) -> Vec<&'a EntryValue>
+    where
+        I: IntoIterator<Item = &'a EntryValue>,
+    {
+        let mut effective = Vec::new();
+        for value in values {
+            let empty = value.unquote_relaxed().is_empty();
+            match self {
+                KeyKind::Scalar => effective.clear(),
+                KeyKind::List if empty => effective.clear(),
+                _ => {}
+            }
+            if !empty {
+                effective.push(value);
+            }
+        }
+        effective
+    }
+}
+
+/// Dependency keys of the `[Unit]` section, which can't be reset by an empty assignment
+pub const DEPENDENCY_KEYS: &[&str] = &[
+    "Wants", "Requires", "Requisite", "BindsTo", "PartOf", "Upholds", "Conflicts", "Before", "After", "OnFailure",
+    "OnSuccess", "PropagatesReloadTo", "ReloadPropagatedFrom", "PropagatesStopTo", "StopPropagatedFrom",
+    "JoinsNamespaceOf",
+];
+
+/// Keys (in any section) whose assignments accumulate, until an empty assignment clears them
+pub const LIST_KEYS: &[&str] = &[
+    // [Unit] and [Install]
+    "Documentation", "RequiresMountsFor", "WantsMountsFor", "Alias", "WantedBy", "RequiredBy", "UpheldBy", "Also",
+    // commands
+    "ExecCondition", "ExecStartPre", "ExecStart", "ExecStartPost", "ExecReload", "ExecStop", "ExecStopPre",
+    "ExecStopPost",
+    // [Service]
+    "SuccessExitStatus", "RestartPreventExitStatus", "RestartForceExitStatus", "Sockets", "OpenFile",
+    // [Socket]
+    "ListenStream", "ListenDatagram", "ListenSequentialPacket", "ListenFIFO", "ListenSpecial", "ListenNetlink",
+    "ListenMessageQueue", "ListenUSBFunction", "Symlinks",
+    // [Timer] and [Path]
+    "OnActiveSec", "OnBootSec", "OnStartupSec", "OnUnitActiveSec", "OnUnitInactiveSec", "OnCalendar", "PathExists",
+    "PathExistsGlob", "PathChanged", "PathModified", "DirectoryNotEmpty",
+    // execution environment
+    "Environment", "EnvironmentFile", "PassEnvironment", "UnsetEnvironment", "SupplementaryGroups",
+    "ReadWritePaths", "ReadOnlyPaths", "InaccessiblePaths", "ExecPaths", "NoExecPaths", "BindPaths",
+    "BindReadOnlyPaths", "TemporaryFileSystem", "MountImages", "ExtensionImages", "ExtensionDirectories",
+    "RuntimeDirectory", "StateDirectory", "CacheDirectory", "LogsDirectory", "ConfigurationDirectory",
+    "CapabilityBoundingSet", "AmbientCapabilities", "SystemCallFilter", "SystemCallArchitectures", "SystemCallLog",
+    "RestrictAddressFamilies", "RestrictFileSystems", "RestrictNamespaces", "LoadCredential",
+    "LoadCredentialEncrypted", "ImportCredential", "SetCredential", "SetCredentialEncrypted", "LogExtraFields",
+    "LogFilterPatterns",
+    // resource control
+    "DeviceAllow", "IPAddressAllow", "IPAddressDeny", "SocketBindAllow", "SocketBindDeny",
+    "RestrictNetworkInterfaces", "IODeviceWeight", "IOReadBandwidthMax", "IOWriteBandwidthMax", "IOReadIOPSMax",
+    "IOWriteIOPSMax", "IODeviceLatencyTargetSec", "IPIngressFilterPath", "IPEgressFilterPath", "BPFProgram",
+    "NFTSet", "DisableControllers",
+];
+
+/// List keys whose empty assignment means the empty set rather than the default (e.g. `CapabilityBoundingSet=`
+/// allows all capabilities unless it's assigned)
+pub const EMPTY_SET_KEYS: &[&str] = &["CapabilityBoundingSet"];
+
+/// How repeated assignments of `key` in `section` combine
+///
+/// Conditions and asserts are lists, unknown keys are assumed to be scalars.
+pub fn key_kind(section: &str, key: &str) -> KeyKind {
+    if section == UNIT_SECTION && DEPENDENCY_KEYS.contains(&key) {
+        KeyKind::Dependency
+    } else if LIST_KEYS.contains(&key) || (section == UNIT_SECTION && is_condition_key(key)) {
+        KeyKind::List
+    } else {
+        KeyKind::Scalar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        assert_eq!(key_kind(UNIT_SECTION, "After"), KeyKind::Dependency);
+        assert_eq!(key_kind(UNIT_SECTION, "Documentation"), KeyKind::List);
+        assert_eq!(key_kind(UNIT_SECTION, "ConditionPathExists"), KeyKind::List);
+        assert_eq!(key_kind(UNIT_SECTION, "Description"), KeyKind::Scalar);
+        assert_eq!(key_kind(SERVICE_SECTION, "ExecStart"), KeyKind::List);
+        assert_eq!(key_kind(SERVICE_SECTION, "Type"), KeyKind::Scalar);
+        assert_eq!(key_kind(INSTALL_SECTION, "WantedBy"), KeyKind::List);
+        // dependencies and conditions only exist in [Unit]
+        assert_eq!(key_kind(SERVICE_SECTION, "After"), KeyKind::Scalar);
+        assert_eq!(key_kind(SERVICE_SECTION, "ConditionPathExists"), KeyKind::Scalar);
+        // unknown keys are scalars
+        assert_eq!(key_kind(SERVICE_SECTION, "X-Foo"), KeyKind::Scalar);
+    }
+
+    #[test]
+    fn unit_types() {
+        assert_eq!(UnitType::from_name("foo.service"), Some(UnitType::Service));
+        assert_eq!(UnitType::from_name("/etc/systemd/system/foo@bar.socket"), Some(UnitType::Socket));
+        assert_eq!(UnitType::from_name("foo.conf"), None);
+        assert_eq!(UnitType::from_name("foo"), None);
+        assert!(UnitType::ALL.iter().all(|t| UnitType::from_name(format!("x.{t}")) == Some(*t)));
+
+        assert_eq!(UnitType::Service.sections(), [UNIT_SECTION, SERVICE_SECTION, INSTALL_SECTION]);
+        assert_eq!(UnitType::Target.sections(), [UNIT_SECTION, INSTALL_SECTION]);
+    }
+
+    #[test]
+    fn known_keys() {
+        let service = UnitType::Service;
+        assert!(service.is_known_key(SERVICE_SECTION, "ExecStart"));
+        assert!(service.is_known_key(SERVICE_SECTION, "KillMode"));
+        assert!(service.is_known_key(SERVICE_SECTION, "MemoryMax"));
+        assert!(service.is_known_key(SERVICE_SECTION, "ReadWriteDirectories"));
+        assert!(service.is_known_key(UNIT_SECTION, "AssertPathExists"));
+        assert!(service.is_known_key(UNIT_SECTION, "BindTo"));
+        assert!(service.is_known_key(SERVICE_SECTION, "X-Custom"));
+        assert!(service.is_known_key("X-Section", "Anything"));
+        assert!(!service.is_known_key(SERVICE_SECTION, "ListenStream"));
+        assert!(!service.is_known_key(UNIT_SECTION, "ConditionFoo"));
+        assert!(!UnitType::Timer.is_known_key(TIMER_SECTION, "ExecStart"));
+
+        assert!(service.is_known_section("X-Section"));
+        assert!(!service.is_known_section(SOCKET_SECTION));
+        assert_eq!(UnitType::Target.section_keys(SERVICE_SECTION), None);
+    }
+}
@@ -0,0 +1,743 @@
+use std::collections::HashSet;
+use std::fmt;
+use super::*;
+
+/// The effective value of a list setting with `~` deny list syntax, like `CapabilityBoundingSet=`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListFilter {
+    /// The setting isn't used, everything is allowed
+    All,
+    /// Only the listed items are allowed
+    Allow(HashSet<String>),
+    /// Everything but the listed items is allowed
+    Deny(HashSet<String>),
+}
+
+impl ListFilter {
+    /// Merge all assignments of a setting like systemd does: an empty assignment resets the filter, a `~`
+    /// prefix denies the listed items, otherwise the listed items are allowed
+    pub fn from_assignments<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut filter = ListFilter::All;
+
+        for value in values {
+            let value = value.trim();
+            filter = if value.is_empty() {
+                ListFilter::All
+            } else {
+                filter.merge(value, |item| vec![item])
+            };
+        }
+
+        filter
+    }
+
+    /// Merge all assignments of a capability set like `CapabilityBoundingSet=`, which differs from
+    /// [`ListFilter::from_assignments()`] in resetting: an empty assignment means no capabilities at all and a
+    /// lone `~` means all of them
+    pub fn from_capability_assignments<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut filter = ListFilter::All;
+
+        for value in values {
+            filter = match value.trim() {
+                "" => ListFilter::Allow(HashSet::new()),
+                "~" => ListFilter::All,
+                value => match filter.merge(value, |item| vec![item]) {
+                    // systemd stores a bit mask, so denying nothing is the same as not using the setting
+                    ListFilter::Deny(s) if s.is_empty() => ListFilter::All,
+                    filter => filter,
+                },
+            };
+        }
+
+        filter
+    }
+
+    // apply a non-empty assignment, `expand` returns the items an item stands for (including itself)
+    fn merge(self, value: &str, expand: impl Fn(String) -> Vec<String>) -> Self {
+        let (deny, list) = match value.strip_prefix('~') {
+            Some(list) => (true, list),
+            None => (false, value),
+        };
+        let items: HashSet<String> = SplitWord::new(list).flat_map(expand).collect();
+
+        match (self, deny) {
+            (ListFilter::All, true) => ListFilter::Deny(items),
+            (ListFilter::All, false) => ListFilter::Allow(items),
+            (ListFilter::Allow(s), false) => ListFilter::Allow(&s | &items),
+            (ListFilter::Allow(s), true) => ListFilter::Allow(&s - &items),
+            (ListFilter::Deny(s), true) => ListFilter::Deny(&s | &items),
+            (ListFilter::Deny(s), false) => ListFilter::Deny(&s - &items),
+        }
+    }
+
+    pub fn allows(&self, item: &str) -> bool {
+        match self {
+            ListFilter::All => true,
+            ListFilter::Allow(s) => s.contains(item),
+            ListFilter::Deny(s) => !s.contains(item),
+        }
+    }
+}
+
+/// The result of a single check of a [`SecurityAssessment`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityCheck {
+    /// Name of the check as used by `systemd-analyze security` (e.g. `PrivateNetwork=`)
+    pub id: &'static str,
+    pub description: String,
+    pub weight: u64,
+    pub range: u64,
+    /// `0` is perfect, `range` is worst, `None` if the check doesn't apply
+    pub badness: Option<u64>,
+}
+
+impl SecurityCheck {
+    /// Return `true` if the check passed perfectly
+    pub fn passed(&self) -> bool {
+        self.badness == Some(0)
+    }
+}
+
+/// The overall rating of a [`SecurityAssessment`], same as `systemd-analyze security`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExposureLevel {
+    Perfect,
+    Safe,
+    Ok,
+    Medium,
+    Exposed,
+    Unsafe,
+    Dangerous,
+}
+
+impl fmt::Display for ExposureLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ExposureLevel::Perfect => "PERFECT",
+            ExposureLevel::Safe => "SAFE",
+            ExposureLevel::Ok => "OK",
+            ExposureLevel::Medium => "MEDIUM",
+            ExposureLevel::Exposed => "EXPOSED",
+            ExposureLevel::Unsafe => "UNSAFE",
+            ExposureLevel::Dangerous => "DANGEROUS",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Offline equivalent of `systemd-analyze security` for a `[Service]` section
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityAssessment {
+    pub checks: Vec<SecurityCheck>,
+}
+
+impl SecurityAssessment {
+    // exposure in 0..=100, i.e. tenths of the displayed value
+    fn exposure_percent(&self) -> u64 {
+        let mut badness_sum = 0;
+        let mut weight_sum = 0;
+        for c in &self.checks {
+            if let Some(badness) = c.badness {
+                badness_sum += (badness * c.weight).div_ceil(c.range);
+                weight_sum += c.weight;
+            }
+        }
+
+        if weight_sum == 0 {
+            return 0;
+        }
+        (badness_sum * 100).div_ceil(weight_sum)
+    }
+
+    /// The overall exposure from `0.0` (perfect) to `10.0` (dangerous)
+    pub fn exposure(&self) -> f64 {
+        self.exposure_percent() as f64 / 10.0
+    }
+
+    pub fn level(&self) -> ExposureLevel {
+        match self.exposure_percent() {
+            100.. => ExposureLevel::Dangerous,
+            90.. => ExposureLevel::Unsafe,
+            75.. => ExposureLevel::Exposed,
+            50.. => ExposureLevel::Medium,
+            10.. => ExposureLevel::Ok,
+            1.. => ExposureLevel::Safe,
+            0 => ExposureLevel::Perfect,
+        }
+    }
+
+    /// Return `true` if the exposure doesn't exceed `threshold` (like `systemd-analyze security --threshold=`)
+    pub fn passes(&self, threshold: f64) -> bool {
+        self.exposure() <= threshold
+    }
+}
+
+impl fmt::Display for SecurityAssessment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weight_sum: u64 = self
+            .checks
+            .iter()
+            .filter(|c| c.badness.is_some())
+            .map(|c| c.weight)
+            .sum();
+
+        writeln!(f, "  {:<45} {:<70} EXPOSURE", "NAME", "DESCRIPTION")?;
+        for c in &self.checks {
+            match c.badness {
+                Some(badness) => {
+                    let mark = if badness == 0 { '✓' } else { '✗' };
+                    let exposure = (badness * c.weight * 100) as f64 / (c.range * weight_sum.max(1)) as f64;
+                    if badness == 0 {
+                        writeln!(f, "{mark} {:<45} {}", c.id, c.description)?;
+                    } else {
+                        writeln!(f, "{mark} {:<45} {:<70} {:>8.1}", c.id, c.description, exposure / 10.0)?;
+                    }
+                }
+                None => writeln!(f, "  {:<45} {:<70} {:>8}", c.id, c.description, "n/a")?,
+            }
+        }
+        writeln!(f)?;
+        write!(f, "→ Overall exposure level: {:.1} {}", self.exposure(), self.level())
+    }
+}
+
+// (id, weight, capabilities)
+const CAPABILITY_CHECKS: &[(&str, u64, &[&str])] = &[
+    ("CapabilityBoundingSet=~CAP_SYS_ADMIN", 1500, &["CAP_SYS_ADMIN"]),
+    ("CapabilityBoundingSet=~CAP_SET(UID|GID|PCAP)", 1500, &["CAP_SETUID", "CAP_SETGID", "CAP_SETPCAP"]),
+    ("CapabilityBoundingSet=~CAP_SYS_PTRACE", 1500, &["CAP_SYS_PTRACE"]),
+    ("CapabilityBoundingSet=~CAP_SYS_TIME", 1000, &["CAP_SYS_TIME"]),
+    ("CapabilityBoundingSet=~CAP_NET_ADMIN", 1500, &["CAP_NET_ADMIN"]),
+    ("CapabilityBoundingSet=~CAP_SYS_RAWIO", 1000, &["CAP_SYS_RAWIO"]),
+    ("CapabilityBoundingSet=~CAP_SYS_MODULE", 1500, &["CAP_SYS_MODULE"]),
+    ("CapabilityBoundingSet=~CAP_AUDIT_*", 500, &["CAP_AUDIT_CONTROL", "CAP_AUDIT_READ", "CAP_AUDIT_WRITE"]),
+    ("CapabilityBoundingSet=~CAP_SYSLOG", 1500, &["CAP_SYSLOG"]),
+    ("CapabilityBoundingSet=~CAP_SYS_(NICE|RESOURCE)", 500, &["CAP_SYS_NICE", "CAP_SYS_RESOURCE"]),
+    ("CapabilityBoundingSet=~CAP_MKNOD", 500, &["CAP_MKNOD"]),
+    ("CapabilityBoundingSet=~CAP_(CHOWN|FSETID|SETFCAP)", 1000, &["CAP_CHOWN", "CAP_FSETID", "CAP_SETFCAP"]),
+    ("CapabilityBoundingSet=~CAP_(DAC_*|FOWNER|IPC_OWNER)", 1000, &["CAP_DAC_OVERRIDE", "CAP_DAC_READ_SEARCH", "CAP_FOWNER", "CAP_IPC_OWNER"]),
+    ("CapabilityBoundingSet=~CAP_KILL", 500, &["CAP_KILL"]),
+    ("CapabilityBoundingSet=~CAP_NET_(BIND_SERVICE|BROADCAST|RAW)", 500, &["CAP_NET_BIND_SERVICE", "CAP_NET_BROADCAST", "CAP_NET_RAW"]),
+    ("CapabilityBoundingSet=~CAP_SYS_BOOT", 100, &["CAP_SYS_BOOT"]),
+    ("CapabilityBoundingSet=~CAP_MAC_*", 1500, &["CAP_MAC_ADMIN", "CAP_MAC_OVERRIDE"]),
+    ("CapabilityBoundingSet=~CAP_LINUX_IMMUTABLE", 1000, &["CAP_LINUX_IMMUTABLE"]),
+    ("CapabilityBoundingSet=~CAP_IPC_LOCK", 500, &["CAP_IPC_LOCK"]),
+    ("CapabilityBoundingSet=~CAP_SYS_CHROOT", 500, &["CAP_SYS_CHROOT"]),
+    ("CapabilityBoundingSet=~CAP_BLOCK_SUSPEND", 25, &["CAP_BLOCK_SUSPEND"]),
+    ("CapabilityBoundingSet=~CAP_WAKE_ALARM", 25, &["CAP_WAKE_ALARM"]),
+    ("CapabilityBoundingSet=~CAP_LEASE", 25, &["CAP_LEASE"]),
+    ("CapabilityBoundingSet=~CAP_SYS_TTY_CONFIG", 1000, &["CAP_SYS_TTY_CONFIG"]),
+    ("CapabilityBoundingSet=~CAP_BPF", 1500, &["CAP_BPF"]),
+];
+
+// (id, weight, setting, description if enabled)
+const BOOL_CHECKS: &[(&str, u64, &str, &str)] = &[
+    ("PrivateDevices=", 1000, "PrivateDevices", "Service has no access to hardware devices"),
+    ("PrivateMounts=", 1000, "PrivateMounts", "Service cannot install system mounts"),
+    ("PrivateNetwork=", 2500, "PrivateNetwork", "Service has no access to the host's network"),
+    ("PrivateTmp=", 1000, "PrivateTmp", "Service has no access to other software's temporary files"),
+    ("PrivateUsers=", 1000, "PrivateUsers", "Service does not have access to other users"),
+    ("ProtectControlGroups=", 1000, "ProtectControlGroups", "Service cannot modify the control group file system"),
+    ("ProtectKernelModules=", 1000, "ProtectKernelModules", "Service cannot load or read kernel modules"),
+    ("ProtectKernelTunables=", 1000, "ProtectKernelTunables", "Service cannot alter kernel tunables (/proc/sys, …)"),
+    ("ProtectKernelLogs=", 1000, "ProtectKernelLogs", "Service cannot read from or write to the kernel log ring buffer"),
+    ("ProtectClock=", 1000, "ProtectClock", "Service cannot write to the hardware clock or system clock"),
+    ("ProtectHostname=", 50, "ProtectHostname", "Service cannot change system host/domainname"),
+    ("LockPersonality=", 100, "LockPersonality", "Service cannot change ABI personality"),
+    ("MemoryDenyWriteExecute=", 100, "MemoryDenyWriteExecute", "Service cannot create writable executable memory mappings"),
+    ("NoNewPrivileges=", 1000, "NoNewPrivileges", "Service processes cannot acquire new privileges"),
+    ("RestrictRealtime=", 500, "RestrictRealtime", "Service realtime scheduling access is restricted"),
+    ("RestrictSUIDSGID=", 1000, "RestrictSUIDSGID", "SUID/SGID file creation by service is restricted"),
+];
+
+// (id, weight, namespace)
+const NAMESPACE_CHECKS: &[(&str, u64, &str)] = &[
+    ("RestrictNamespaces=~user", 1500, "user"),
+    ("RestrictNamespaces=~mnt", 500, "mnt"),
+    ("RestrictNamespaces=~ipc", 500, "ipc"),
+    ("RestrictNamespaces=~pid", 500, "pid"),
+    ("RestrictNamespaces=~cgroup", 500, "cgroup"),
+    ("RestrictNamespaces=~uts", 500, "uts"),
+    ("RestrictNamespaces=~net", 500, "net"),
+];
+
+// (id, weight, address families)
+const ADDRESS_FAMILY_CHECKS: &[(&str, u64, &[&str])] = &[
+    ("RestrictAddressFamilies=~AF_(INET|INET6)", 1500, &["AF_INET", "AF_INET6"]),
+    ("RestrictAddressFamilies=~AF_UNIX", 25, &["AF_UNIX"]),
+    ("RestrictAddressFamilies=~AF_PACKET", 1000, &["AF_PACKET"]),
+    ("RestrictAddressFamilies=~AF_NETLINK", 200, &["AF_NETLINK"]),
+];
+
+// (id, weight, system call group)
+const SYSCALL_CHECKS: &[(&str, u64, &str)] = &[
+    ("SystemCallFilter=~@swap", 1000, "@swap"),
+    ("SystemCallFilter=~@obsolete", 250, "@obsolete"),
+    ("SystemCallFilter=~@clock", 1000, "@clock"),
+    ("SystemCallFilter=~@cpu-emulation", 250, "@cpu-emulation"),
+    ("SystemCallFilter=~@debug", 1000, "@debug"),
+    ("SystemCallFilter=~@module", 1000, "@module"),
+    ("SystemCallFilter=~@mount", 1000, "@mount"),
+    ("SystemCallFilter=~@raw-io", 1000, "@raw-io"),
+    ("SystemCallFilter=~@reboot", 1000, "@reboot"),
+    ("SystemCallFilter=~@privileged", 700, "@privileged"),
+    ("SystemCallFilter=~@resources", 700, "@resources"),
+];
+
+// system call groups and the groups they share system calls with, `@privileged` e.g. includes `chown()` and
+// `setuid()`, so allowing `@chown` or `@setuid` means `@privileged` isn't filtered completely
+const SYSCALL_GROUP_MEMBERS: &[(&str, &[&str])] = &[
+    (
+        "@system-service",
+        &[
+            "@aio", "@basic-io", "@chown", "@default", "@file-system", "@io-event", "@ipc", "@keyring",
+            "@memlock", "@network-io", "@process", "@resources", "@setuid", "@signal", "@sync", "@timer",
+        ],
+    ),
+    ("@privileged", &["@chown", "@clock", "@module", "@raw-io", "@reboot", "@swap", "@setuid"]),
+];
+
+fn syscall_group_members(group: &str) -> &'static [&'static str] {
+    SYSCALL_GROUP_MEMBERS
+        .iter()
+        .find(|(g, _)| *g == group)
+        .map_or(&[], |(_, members)| members)
+}
+
+// a group stands for itself and its members, so that e.g. `~@resources` removes `@resources` from
+// `@system-service`
+fn expand_syscall_group(item: String) -> Vec<String> {
+    let members = syscall_group_members(&item).iter().map(|m| m.to_string());
+    std::iter::once(item.clone()).chain(members).collect()
+}
+
+/// Reads the settings of a `[Service]` section
+struct ServiceSettings<'a> {
+    unit: &'a SystemdUnit,
+}
+
+impl ServiceSettings<'_> {
+    fn last(&self, key: &str) -> Option<String> {
+        self.unit
+            .lookup_last(SERVICE_SECTION, key)
+            .filter(|v| !v.is_empty())
+    }
+
+    fn bool(&self, key: &str) -> bool {
+        self.unit
+            .lookup_last_value(SERVICE_SECTION, key)
+            .is_some_and(|v| v.to_bool().unwrap_or(false))
+    }
+
+    fn filter(&self, key: &str) -> ListFilter {
+        ListFilter::from_assignments(
+            self.unit
+                .lookup_all_values(SERVICE_SECTION, key)
+                .map(|v| v.raw().as_str()),
+        )
+    }
+
+    fn capabilities(&self, key: &str) -> ListFilter {
+        ListFilter::from_capability_assignments(
+            self.unit
+                .lookup_all_values(SERVICE_SECTION, key)
+                .map(|v| v.raw().as_str()),
+        )
+    }
+
+    fn syscall_filter(&self) -> ListFilter {
+        let mut filter = ListFilter::All;
+        for value in self.unit.lookup_all_values(SERVICE_SECTION, "SystemCallFilter") {
+            let value = value.raw().trim();
+            filter = if value.is_empty() {
+                ListFilter::All
+            } else {
+                filter.merge(value, expand_syscall_group)
+            };
+        }
+        filter
+    }
+
+    fn is_root(&self) -> bool {
+        match self.last("User") {
+            Some(user) => user == "root" || user == "0",
+            None => !self.bool("DynamicUser"),
+        }
+    }
+}
+
+fn check(id: &'static str, weight: u64, range: u64, badness: Option<u64>, description: &str) -> SecurityCheck {
+    SecurityCheck {
+        id,
+        description: description.to_string(),
+        weight,
+        range,
+        badness,
+    }
+}
+
+impl SystemdUnit {
+    /// Assess the sandboxing of the `[Service]` section like `systemd-analyze security` does
+    pub fn security_assessment(&self) -> SecurityAssessment {
+        let s = ServiceSettings { unit: self };
+        let mut checks = Vec::new();
+
+        let user = s.last("User");
+        let (badness, description) = match user.as_deref() {
+            Some("nobody" | "65534") => (9, "Service runs under as 'nobody' user, which should not be used for services"),
+            Some(u) if s.bool("DynamicUser") && u != "root" && u != "0" => (0, "Service runs under a transient non-root user identity"),
+            None if s.bool("DynamicUser") => (0, "Service runs under a transient non-root user identity"),
+            Some(u) if u != "root" && u != "0" => (0, "Service runs under a static non-root user identity"),
+            _ => (10, "Service runs as root user"),
+        };
+        checks.push(check("User=/DynamicUser=", 2000, 10, Some(badness), description));
+
+        let groups = !s.unit.lookup_all_with_reset(SERVICE_SECTION, "SupplementaryGroups").is_empty();
+        checks.push(if s.is_root() {
+            check("SupplementaryGroups=", 200, 1, None, "Service runs as root, option does not matter")
+        } else if groups {
+            check("SupplementaryGroups=", 200, 1, Some(1), "Service has supplementary groups")
+        } else {
+            check("SupplementaryGroups=", 200, 1, Some(0), "Service has no supplementary groups")
+        });
+
+        for &(id, weight, key, description) in BOOL_CHECKS {
+            if s.bool(key) {
+                checks.push(check(id, weight, 1, Some(0), description));
+            } else {
+                checks.push(check(id, weight, 1, Some(1), &format!("{key}= is not enabled")));
+            }
+        }
+
+        let (badness, description) = match s.last("ProtectSystem").as_deref() {
+            Some("strict") => (0, "Service has strict read-only access to the OS file hierarchy"),
+            Some("full") => (3, "Service has very limited write access to the OS file hierarchy"),
+            Some(v) if parse_bool(v) == Ok(true) => (5, "Service has limited write access to the OS file hierarchy"),
+            _ => (10, "Service has full access to the OS file hierarchy"),
+        };
+        checks.push(check("ProtectSystem=", 1000, 10, Some(badness), description));
+
+        let (badness, description) = match s.last("ProtectHome").as_deref() {
+            Some("tmpfs") => (1, "Service has access to fake empty home directories"),
+            Some("read-only") => (5, "Service has read-only access to home directories"),
+            Some(v) if parse_bool(v) == Ok(true) => (0, "Service has no access to home directories"),
+            _ => (10, "Service has full access to home directories"),
+        };
+        checks.push(check("ProtectHome=", 1000, 10, Some(badness), description));
+
+        let (badness, description) = match s.last("ProtectProc").as_deref() {
+            Some("invisible") => (0, "Service has no access to other processes' /proc/ entries"),
+            Some("ptraceable") => (0, "Service has restricted access to other processes' /proc/ entries"),
+            Some("noaccess") => (1, "Service has no access to other processes' /proc/ data"),
+            _ => (10, "Service has full access to process tree (/proc/)"),
+        };
+        checks.push(check("ProtectProc=", 1000, 10, Some(badness), description));
+
+        let pid_only = s.last("ProcSubset").as_deref() == Some("pid");
+        checks.push(if pid_only {
+            check("ProcSubset=", 10, 1, Some(0), "Service has no access to non-process /proc/ files")
+        } else {
+            check("ProcSubset=", 10, 1, Some(1), "Service has full access to non-process /proc/ files")
+        });
+
+        let root = s.last("RootDirectory").is_some() || s.last("RootImage").is_some();
+        checks.push(if root {
+            check("RootDirectory=/RootImage=", 200, 1, Some(0), "Service has its own root directory/image")
+        } else {
+            check("RootDirectory=/RootImage=", 200, 1, Some(1), "Service runs within the host's root directory")
+        });
+
+        let bounding_set = s.capabilities("CapabilityBoundingSet");
+        let ambient = !s.unit.lookup_all_with_reset(SERVICE_SECTION, "AmbientCapabilities").is_empty();
+        for &(id, weight, caps) in CAPABILITY_CHECKS {
+            let allowed: Vec<&str> = caps.iter().copied().filter(|c| bounding_set.allows(c)).collect();
+            checks.push(if allowed.is_empty() {
+                check(id, weight, 1, Some(0), "Service has no such capabilities")
+            } else {
+                check(id, weight, 1, Some(1), &format!("Service may acquire {}", allowed.join(", ")))
+            });
+        }
+        checks.push(if ambient {
+            check("AmbientCapabilities=", 500, 1, Some(1), "Service process receives ambient capabilities")
+        } else {
+            check("AmbientCapabilities=", 500, 1, Some(0), "Service process does not receive ambient capabilities")
+        });
+
+        let families = match s.last("RestrictAddressFamilies").as_deref() {
+            Some("none") => ListFilter::Allow(HashSet::new()),
+            _ => s.filter("RestrictAddressFamilies"),
+        };
+        for &(id, weight, afs) in ADDRESS_FAMILY_CHECKS {
+            checks.push(if afs.iter().any(|af| families.allows(af)) {
+                check(id, weight, 1, Some(1), &format!("Service may allocate {} sockets", afs.join("/")))
+            } else {
+                check(id, weight, 1, Some(0), &format!("Service cannot allocate {} sockets", afs.join("/")))
+            });
+        }
+        let other_allowed = match &families {
+            ListFilter::Allow(s) => s
+                .iter()
+                .any(|af| !ADDRESS_FAMILY_CHECKS.iter().any(|(_, _, afs)| afs.contains(&af.as_str()))),
+            _ => true,
+        };
+        checks.push(if other_allowed {
+            check("RestrictAddressFamilies=~…", 1250, 1, Some(1), "Service may allocate exotic sockets")
+        } else {
+            check("RestrictAddressFamilies=~…", 1250, 1, Some(0), "Service cannot allocate exotic sockets")
+        });
+
+        let namespaces = match s.last("RestrictNamespaces") {
+            Some(v) => match parse_bool(&v) {
+                Ok(true) => ListFilter::Allow(HashSet::new()),
+                Ok(false) => ListFilter::All,
+                Err(_) => s.filter("RestrictNamespaces"),
+            },
+            None => ListFilter::All,
+        };
+        for &(id, weight, ns) in NAMESPACE_CHECKS {
+            checks.push(if namespaces.allows(ns) {
+                check(id, weight, 1, Some(1), &format!("Service may create {ns} namespaces"))
+            } else {
+                check(id, weight, 1, Some(0), &format!("Service cannot create {ns} namespaces"))
+            });
+        }
+
+        let (badness, description) = match s.last("SystemCallArchitectures") {
+            Some(archs) if archs == "native" => (0, "Service may execute system calls only with native ABI"),
+            Some(_) => (8, "Service may execute system calls with multiple ABIs"),
+            None => (10, "Service may execute system calls with all ABIs"),
+        };
+        checks.push(check("SystemCallArchitectures=", 1000, 10, Some(badness), description));
+
+        let syscalls = s.syscall_filter();
+        for &(id, weight, group) in SYSCALL_CHECKS {
+            // an allow list lets some of the group through if it allows any group sharing system calls with it
+            let allowed = match &syscalls {
+                ListFilter::Allow(list) => {
+                    list.contains(group) || syscall_group_members(group).iter().any(|m| list.contains(*m))
+                }
+                filter => filter.allows(group),
+            };
+            checks.push(if allowed {
+                check(id, weight, 10, Some(10), &format!("Service does not filter system calls of {group}"))
+            } else {
+                check(id, weight, 10, Some(0), &format!("System call {group} is blocked"))
+            });
+        }
+
+        let deny = s.unit.lookup_all_with_reset(SERVICE_SECTION, "IPAddressDeny");
+        let allow = s.unit.lookup_all_with_reset(SERVICE_SECTION, "IPAddressAllow");
+        let deny_all = deny
+            .iter()
+            .flat_map(|v| SplitWord::new(v))
+            .any(|a| a == "any" || a == "0.0.0.0/0" || a == "::/0");
+        let localhost_only = allow
+            .iter()
+            .flat_map(|v| SplitWord::new(v))
+            .all(|a| ["localhost", "link-local", "127.0.0.0/8", "::1/128"].contains(&a.as_str()));
+        let (badness, description) = if !deny_all {
+            (10, "Service does not define an IP address allow list")
+        } else if allow.is_empty() {
+            (0, "Service blocks all IP address ranges")
+        } else if localhost_only {
+            (2, "Service defines IP address allow list with only localhost entries")
+        } else {
+            (5, "Service defines IP address allow list with non-localhost entries")
+        };
+        checks.push(check("IPAddressDeny=", 1000, 10, Some(badness), description));
+
+        let device_allow = s.unit.lookup_all_with_reset(SERVICE_SECTION, "DeviceAllow");
+        let (badness, description) = match s.last("DevicePolicy").as_deref() {
+            Some("strict" | "closed") if device_allow.is_empty() => (0, "Service has no device ACL entries"),
+            Some("strict" | "closed") => (5, "Service has a device ACL with some entries"),
+            _ => (10, "Service has no device ACL"),
+        };
+        checks.push(check("DeviceAllow=", 1000, 10, Some(badness), description));
+
+        let delegate = s
+            .last("Delegate")
+            .is_some_and(|v| parse_bool(&v) != Ok(false));
+        checks.push(if delegate {
+            check("Delegate=", 100, 1, Some(1), "Service maintains its own delegated control group subtree")
+        } else {
+            check("Delegate=", 100, 1, Some(0), "Service does not maintain its own delegated control group subtree")
+        });
+
+        let shared_keyring = s.last("KeyringMode").as_deref() == Some("shared");
+        checks.push(if shared_keyring {
+            check("KeyringMode=", 1000, 1, Some(1), "Service shares key chain with other services")
+        } else {
+            check("KeyringMode=", 1000, 1, Some(0), "Service doesn't share key material with other services")
+        });
+
+        let notify_all = s.last("NotifyAccess").as_deref() == Some("all");
+        checks.push(if notify_all {
+            check("NotifyAccess=", 1000, 1, Some(1), "Service child processes may alter service state")
+        } else {
+            check("NotifyAccess=", 1000, 1, Some(0), "Service child processes cannot alter service state")
+        });
+
+        checks.push(if s.is_root() {
+            check("RemoveIPC=", 100, 1, None, "Service runs as root, option does not apply")
+        } else if s.bool("RemoveIPC") {
+            check("RemoveIPC=", 100, 1, Some(0), "Service user cannot leave SysV IPC objects around")
+        } else {
+            check("RemoveIPC=", 100, 1, Some(1), "Service user may leave SysV IPC objects around")
+        });
+
+        let umask = s
+            .last("UMask")
+            .and_then(|m| u32::from_str_radix(&m, 8).ok())
+            .unwrap_or(0o022);
+        let (badness, description) = if umask & 0o002 == 0 {
+            (10, "Files created by service are world-writable by default")
+        } else if umask & 0o004 == 0 {
+            (5, "Files created by service are world-readable by default")
+        } else if umask & 0o020 == 0 {
+            (2, "Files created by service are group-writable by default")
+        } else if umask & 0o040 == 0 {
+            (1, "Files created by service are group-readable by default")
+        } else {
+            (0, "Files created by service are accessible only by service's own user by default")
+        };
+        checks.push(check("UMask=", 100, 10, Some(badness), description));
+
+        SecurityAssessment { checks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assess(service: &str) -> SecurityAssessment {
+        SystemdUnit::load_from_str(&format!("[Service]\n{service}")).unwrap().security_assessment()
+    }
+
+    fn badness(assessment: &SecurityAssessment, id: &str) -> Option<u64> {
+        assessment.checks.iter().find(|c| c.id == id).unwrap().badness
+    }
+
+    fn set(items: &[&str]) -> HashSet<String> {
+        items.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn list_filter() {
+        assert_eq!(ListFilter::from_assignments([]), ListFilter::All);
+        assert_eq!(ListFilter::from_assignments(["a b", "c"]), ListFilter::Allow(set(&["a", "b", "c"])));
+        assert_eq!(ListFilter::from_assignments(["a b", "~b"]), ListFilter::Allow(set(&["a"])));
+        assert_eq!(ListFilter::from_assignments(["~a b", "~c", "b"]), ListFilter::Deny(set(&["a", "c"])));
+        assert_eq!(ListFilter::from_assignments(["a", "", "~b"]), ListFilter::Deny(set(&["b"])));
+
+        let filter = ListFilter::from_assignments(["~a"]);
+        assert!(!filter.allows("a"));
+        assert!(filter.allows("b"));
+    }
+
+    #[test]
+    fn capability_filter() {
+        // an empty assignment is the empty set, not "unset"
+        assert_eq!(ListFilter::from_capability_assignments([""]), ListFilter::Allow(HashSet::new()));
+        assert_eq!(ListFilter::from_capability_assignments(["CAP_A", ""]), ListFilter::Allow(HashSet::new()));
+        assert_eq!(ListFilter::from_capability_assignments(["", "CAP_A"]), ListFilter::Allow(set(&["CAP_A"])));
+        assert_eq!(ListFilter::from_capability_assignments(["", "~CAP_A"]), ListFilter::Allow(HashSet::new()));
+        // a lone `~` is every capability
+        assert_eq!(ListFilter::from_capability_assignments(["", "~"]), ListFilter::All);
+        assert_eq!(ListFilter::from_capability_assignments(["~CAP_A", "CAP_A"]), ListFilter::All);
+        let filter = ListFilter::from_capability_assignments(["~CAP_A CAP_B"]);
+        assert_eq!(filter, ListFilter::Deny(set(&["CAP_A", "CAP_B"])));
+    }
+
+    #[test]
+    fn capability_checks() {
+        let assessment = assess("CapabilityBoundingSet=\n");
+        let caps: Vec<_> = assessment.checks.iter().filter(|c| c.id.starts_with("CapabilityBoundingSet=")).collect();
+        assert_eq!(caps.len(), CAPABILITY_CHECKS.len());
+        assert!(caps.iter().all(|c| c.passed()));
+
+        let assessment = assess("CapabilityBoundingSet=CAP_SYS_ADMIN\nCapabilityBoundingSet=CAP_KILL\n");
+        assert_eq!(badness(&assessment, "CapabilityBoundingSet=~CAP_SYS_ADMIN"), Some(1));
+        assert_eq!(badness(&assessment, "CapabilityBoundingSet=~CAP_KILL"), Some(1));
+        assert_eq!(badness(&assessment, "CapabilityBoundingSet=~CAP_SYS_PTRACE"), Some(0));
+
+        let assessment = assess("CapabilityBoundingSet=~CAP_SETUID\n");
+        let check = assessment
+            .checks
+            .iter()
+            .find(|c| c.id == "CapabilityBoundingSet=~CAP_SET(UID|GID|PCAP)")
+            .unwrap();
+        assert_eq!(check.description, "Service may acquire CAP_SETGID, CAP_SETPCAP");
+    }
+
+    #[test]
+    fn system_call_filter() {
+        let assessment = assess("SystemCallFilter=@system-service\n");
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@resources"), Some(10));
+        // `@system-service` includes `@chown` and `@setuid`, which are part of `@privileged`
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@privileged"), Some(10));
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@mount"), Some(0));
+
+        let assessment = assess("SystemCallFilter=@system-service\nSystemCallFilter=~@resources\n");
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@resources"), Some(0));
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@privileged"), Some(10));
+
+        let assessment = assess("SystemCallFilter=@system-service\nSystemCallFilter=~@privileged @resources\n");
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@resources"), Some(0));
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@privileged"), Some(0));
+
+        let assessment = assess("SystemCallFilter=~@privileged\n");
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@privileged"), Some(0));
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@clock"), Some(0));
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@resources"), Some(10));
+
+        let assessment = assess("SystemCallFilter=~@privileged\nSystemCallFilter=\n");
+        assert_eq!(badness(&assessment, "SystemCallFilter=~@privileged"), Some(10));
+    }
+
+    #[test]
+    fn scoring() {
+        let assessment = assess("");
+        assert_eq!(assessment.exposure_percent(), 96);
+        assert_eq!(assessment.exposure(), 9.6);
+        assert_eq!(assessment.level(), ExposureLevel::Unsafe);
+        assert!(!assessment.passes(9.5));
+        assert!(assessment.passes(9.6));
+        // root makes some checks not apply
+        assert_eq!(badness(&assessment, "RemoveIPC="), None);
+
+        let checks = vec![
+            check("A=", 100, 10, Some(5), ""),
+            check("B=", 300, 1, Some(0), ""),
+            check("C=", 1000, 1, None, ""),
+        ];
+        let assessment = SecurityAssessment { checks };
+        // (5 * 100 / 10 + 0) * 100 / (100 + 300), rounded up
+        assert_eq!(assessment.exposure_percent(), 13);
+        assert_eq!(assessment.level(), ExposureLevel::Ok);
+
+        assert_eq!(SecurityAssessment { checks: vec![] }.level(), ExposureLevel::Perfect);
+    }
+
+    #[test]
+    fn user() {
+        let assessment = assess("DynamicUser=yes\n");
+        assert_eq!(badness(&assessment, "User=/DynamicUser="), Some(0));
+        assert_eq!(badness(&assessment, "RemoveIPC="), Some(1));
+        assert_eq!(badness(&assess("User=nobody\n"), "User=/DynamicUser="), Some(9));
+        assert_eq!(badness(&assess("User=root\nDynamicUser=yes\n"), "User=/DynamicUser="), Some(10));
+    }
+
+    #[test]
+    fn hardened() {
+        let assessment = assess(
+            "DynamicUser=yes\nCapabilityBoundingSet=\nRestrictNamespaces=yes\nRestrictAddressFamilies=none\n\
+             PrivateNetwork=yes\nProtectSystem=strict\nProtectHome=yes\nSystemCallFilter=@system-service\n\
+             SystemCallFilter=~@privileged @resources\nSystemCallArchitectures=native\n",
+        );
+        assert!(assessment.exposure() < 5.0, "{assessment}");
+        assert_eq!(badness(&assessment, "RestrictNamespaces=~user"), Some(0));
+        assert_eq!(badness(&assessment, "RestrictAddressFamilies=~…"), Some(0));
+        assert!(assessment.exposure() < assess("DynamicUser=yes\n").exposure());
+    }
+}
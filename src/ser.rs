@@ -0,0 +1,796 @@
+use serde::ser::{self, Impossible, Serialize, SerializeMap};
+use super::*;
+
+/// Serialize `value` (a struct or map of sections, each a struct or map of keys) into a unit
+///
+/// A `Vec` becomes a repeated key, `None` leaves the key (or section) out and bools are written as `yes`/`no`.
+pub fn to_unit<T: Serialize + ?Sized>(value: &T) -> Result<SystemdUnit, Error> {
+    let mut unit = SystemdUnit::new();
+    value.serialize(UnitSerializer { unit: &mut unit })?;
+    Ok(unit)
+}
+
+/// Serialize `value` into the text of a unit file (see [`to_unit()`])
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let mut buf = Vec::new();
+    to_unit(value)?.write_to(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::new(ErrorKind::Conversion, format!("{what} can't be serialized into a unit"))
+}
+
+// a section or key name
+fn name<T: Serialize + ?Sized>(key: &T) -> Result<String, Error> {
+    match key.serialize(ValueSerializer { raw: true })?.as_slice() {
+        [name] => Ok(name.raw().to_string()),
+        _ => Err(unsupported("a name which isn't a string")),
+    }
+}
+
+impl Serialize for EntryValue {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(ENTRY_VALUE, self.raw())
+    }
+}
+
+// the values of a key, a single value isn't wrapped in a sequence
+struct Values<'a>(Vec<&'a EntryValue>);
+
+impl Serialize for Values<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [value] => value.serialize(serializer),
+            values => serializer.collect_seq(values),
+        }
+    }
+}
+
+struct SectionRef<'a> {
+    unit: &'a SystemdUnit,
+    name: &'a str,
+}
+
+impl Serialize for SectionRef<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self.unit.grouped_entries(self.name);
+        let mut map = serializer.serialize_map(Some(entries.len()))?;
+        for (key, values) in entries {
+            map.serialize_entry(key, &Values(values))?;
+        }
+        map.end()
+    }
+}
+
+/// Sections are maps of keys to a value or a sequence of values, instances of a section are merged
+impl Serialize for SystemdUnit {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = self.section_names();
+        let mut map = serializer.serialize_map(Some(names.len()))?;
+        for name in names {
+            map.serialize_entry(name, &SectionRef { unit: self, name })?;
+        }
+        map.end()
+    }
+}
+
+struct UnitSerializer<'a> {
+    unit: &'a mut SystemdUnit,
+}
+
+struct UnitMap<'a> {
+    unit: &'a mut SystemdUnit,
+    section: Option<String>,
+}
+
+impl<'a> ser::Serializer for UnitSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = UnitMap<'a>;
+    type SerializeStruct = UnitMap<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(UnitMap {
+            unit: self.unit,
+            section: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Err(unsupported("a bool"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(unsupported("a number"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), Error> {
+        Err(unsupported("a string"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), Error> {
+        Err(unsupported("a string"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<(), Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("a sequence of sections"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("an enum"))
+    }
+}
+
+impl UnitMap<'_> {
+    fn serialize_section<T: Serialize + ?Sized>(&mut self, section: String, value: &T) -> Result<(), Error> {
+        value.serialize(SectionSerializer {
+            unit: self.unit,
+            section,
+            key: None,
+        })
+    }
+}
+
+impl ser::SerializeMap for UnitMap<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.section = Some(name(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let section = self.section.take().unwrap_or_default();
+        self.serialize_section(section, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for UnitMap<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.serialize_section(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// a section, which is also its own map and struct serializer
+struct SectionSerializer<'a> {
+    unit: &'a mut SystemdUnit,
+    section: String,
+    key: Option<String>,
+}
+
+impl SectionSerializer<'_> {
+    fn serialize_entry<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        for value in value.serialize(ValueSerializer { raw: false })? {
+            self.unit.append_entry_value(self.section.as_str(), key, value);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for SectionSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = SectionSerializer<'a>;
+    type SerializeStruct = SectionSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Ok(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<(), Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unsupported("a section which isn't a struct or map"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("an enum"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("an enum"))
+    }
+}
+
+impl ser::SerializeMap for SectionSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(name(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().unwrap_or_default();
+        self.serialize_entry(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for SectionSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+// the values of a key, `raw` values are used verbatim instead of being quoted
+struct ValueSerializer {
+    raw: bool,
+}
+
+struct ValueSeq {
+    values: Vec<EntryValue>,
+}
+
+impl ValueSerializer {
+    fn string(&self, s: &str) -> Result<Vec<EntryValue>, Error> {
+        let value = if self.raw {
+            EntryValue::try_from_raw(s)?
+        } else {
+            EntryValue::from_unquoted(s)
+        };
+        Ok(vec![value])
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Vec<EntryValue>;
+    type Error = Error;
+    type SerializeSeq = ValueSeq;
+    type SerializeTuple = ValueSeq;
+    type SerializeTupleStruct = ValueSeq;
+    type SerializeTupleVariant = Impossible<Vec<EntryValue>, Error>;
+    type SerializeMap = Impossible<Vec<EntryValue>, Error>;
+    type SerializeStruct = Impossible<Vec<EntryValue>, Error>;
+    type SerializeStructVariant = Impossible<Vec<EntryValue>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        self.string(if v { "yes" } else { "no" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        self.string(&v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        self.string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        self.string(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Error> {
+        Err(unsupported("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(Vec::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        self.string(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<Self::Ok, Error> {
+        if name == ENTRY_VALUE {
+            value.serialize(ValueSerializer { raw: true })
+        } else {
+            value.serialize(self)
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Error> {
+        Err(unsupported("an enum with data"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(ValueSeq {
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unsupported("an enum with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unsupported("a map as a value"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(unsupported("a struct as a value"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unsupported("an enum with data"))
+    }
+}
+
+impl ser::SerializeSeq for ValueSeq {
+    type Ok = Vec<EntryValue>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.values.extend(value.serialize(ValueSerializer { raw: false })?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.values)
+    }
+}
+
+impl ser::SerializeTuple for ValueSeq {
+    type Ok = Vec<EntryValue>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.values)
+    }
+}
+
+impl ser::SerializeTupleStruct for ValueSeq {
+    type Ok = Vec<EntryValue>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct Unit {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        after: Vec<String>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct Service {
+        exec_start: Vec<String>,
+        remain_after_exit: bool,
+        restart_sec: Option<u32>,
+        nice: Option<i8>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "PascalCase", default)]
+    struct UnitFile {
+        unit: Unit,
+        service: Option<Service>,
+    }
+
+    fn unit_file() -> UnitFile {
+        UnitFile {
+            unit: Unit {
+                description: Some("Foo \"Bar\"".into()),
+                after: vec!["a.service".into(), "b.service".into()],
+            },
+            service: Some(Service {
+                exec_start: vec!["/bin/foo --bar".into()],
+                remain_after_exit: true,
+                restart_sec: Some(30),
+                nice: Some(-5),
+            }),
+        }
+    }
+
+    #[test]
+    fn structs() {
+        let unit = to_unit(&unit_file()).unwrap();
+        let after: Vec<_> = unit.lookup_all_values("Unit", "After").map(EntryValue::raw).collect();
+        assert_eq!(after, ["a.service", "b.service"]);
+        assert_eq!(unit.lookup_last("Service", "RemainAfterExit").unwrap(), "yes");
+        assert_eq!(unit.lookup_last("Service", "RestartSec").unwrap(), "30");
+        assert_eq!(unit.lookup_last("Service", "Nice").unwrap(), "-5");
+        assert_eq!(unit.lookup_last("Unit", "Description").unwrap(), "Foo \"Bar\"");
+    }
+
+    #[test]
+    fn options() {
+        let file = UnitFile {
+            service: Some(Service::default()),
+            ..Default::default()
+        };
+        let unit = to_unit(&file).unwrap();
+        assert_eq!(unit.section_names(), ["Service"]);
+        assert_eq!(unit.grouped_entries("Service").len(), 1);
+        assert_eq!(unit.lookup_last("Service", "RemainAfterExit").unwrap(), "no");
+
+        assert_eq!(to_unit(&UnitFile::default()).unwrap(), SystemdUnit::new());
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = unit_file();
+        let s = to_string(&file).unwrap();
+        assert_eq!(from_str::<UnitFile>(&s).unwrap(), file);
+
+        let mut map = BTreeMap::new();
+        map.insert("X".to_string(), BTreeMap::from([("Foo".to_string(), vec!["a b".to_string(), "c".to_string()])]));
+        let s = to_string(&map).unwrap();
+        assert_eq!(from_str::<BTreeMap<String, BTreeMap<String, Vec<String>>>>(&s).unwrap(), map);
+    }
+
+    #[test]
+    fn unit_round_trip() {
+        let data = "[Unit]\nAfter=a.service\n[Service]\nExecStart=a\nExecStart=\nExecStart=\"b c\"\nNice=1\n";
+        let unit = SystemdUnit::load_from_str(data).unwrap();
+        let unit2: SystemdUnit = from_unit(&to_unit(&unit).unwrap()).unwrap();
+        assert_eq!(unit2, unit);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json() {
+        let unit = SystemdUnit::load_from_str("[Service]\nExecStart=a\nExecStart=\"b c\"\nNice=1\n").unwrap();
+        let json = serde_json::to_string(&unit).unwrap();
+        assert_eq!(json, r#"{"Service":{"ExecStart":["a","\"b c\""],"Nice":"1"}}"#);
+        assert_eq!(serde_json::from_str::<SystemdUnit>(&json).unwrap(), unit);
+    }
+
+    #[test]
+    fn errors() {
+        #[derive(Serialize)]
+        struct Nested {
+            foo: BTreeMap<String, String>,
+        }
+        let e = to_unit(&BTreeMap::from([("X", Nested { foo: BTreeMap::new() })])).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Conversion);
+        assert_eq!(e.message(), "a map as a value can't be serialized into a unit");
+
+        #[derive(Serialize)]
+        enum Mode {
+            Fixed(u32),
+        }
+        let e = to_unit(&BTreeMap::from([("X", BTreeMap::from([("foo", Mode::Fixed(1))]))])).unwrap_err();
+        assert_eq!(e.message(), "an enum with data can't be serialized into a unit");
+
+        let e = to_unit(&BTreeMap::from([("X", BTreeMap::from([(("a", "b"), "x")]))])).unwrap_err();
+        assert_eq!(e.message(), "a name which isn't a string can't be serialized into a unit");
+    }
+}
@@ -28,7 +28,25 @@ impl<'a> SplitStrv<'a> {
         s
     }
 
-    pub fn next<'b>(&mut self) -> Option<String> {
+    fn parse_until_none_of(&mut self, end: &[char]) -> String {
+        let mut s = String::new();
+
+        while let Some(c) = self.c {
+            if !end.contains(&c) {
+                break;
+            }
+            s.push(c);
+            self.bump();
+        }
+
+        s
+    }
+}
+
+impl<'a> Iterator for SplitStrv<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
         let separators = &WHITESPACE;
         let mut word = String::new();
 
@@ -67,28 +85,6 @@ impl<'a> SplitStrv<'a> {
             Some(word)
         }
     }
-
-    fn parse_until_none_of(&mut self, end: &[char]) -> String {
-        let mut s = String::new();
-
-        while let Some(c) = self.c {
-            if !end.contains(&c) {
-                break;
-            }
-            s.push(c);
-            self.bump();
-        }
-
-        s
-    }
-}
-
-impl<'a> Iterator for SplitStrv<'a> {
-    type Item = String;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next()
-    }
 }
 
 /// Splits a string at whitespace and removes quotes while preserving whitespace *inside* quotes.
@@ -117,65 +113,6 @@ impl<'a> SplitWord<'a> {
         s
     }
 
-    pub fn next<'b>(&mut self) -> Option<String> {
-        let separators = &WHITESPACE;
-        let mut word = String::new();
-
-        // skip initial whitespace
-        self.parse_until_none_of(separators);
-
-        let mut quote: Option<char> = None;  // None or Some('\'') or Some('"')
-        let mut backslash = false;  // whether we've just seen a backslash
-        while let Some(c) = self.c {
-            if backslash {
-                match self.parse_escape_sequence() {
-                    Ok(r) => word.push(r),
-                    Err(_) => return None,
-                };
-
-                backslash = false;
-            } else if let Some(q) = quote {
-                // inside either single or double quotes
-                word.push_str(self.parse_until_any_of(&[q, '\\']).as_str());
-
-                match self.c {
-                    Some(c) if c == q => {
-                        quote = None;
-                    },
-                    Some('\\') => backslash = true,
-                    _ => (),
-                }
-            } else {
-                match c {
-                    '\'' | '"' => {
-                        quote = Some(c)
-                    },
-                    '\\' => {
-                        backslash = true;
-                    }
-                    _ if separators.contains(&c) => {
-                        // word is done
-                        break;
-                    },
-                    _ => word.push(c),
-                }
-            }
-
-            self.bump();
-        }
-
-        // if backslash {
-        //     // do nothing -> eat up trailing backslash
-        //     // otherwise we'd have to push it onto `word`
-        // }
-
-        if word.is_empty() {
-            None
-        } else {
-            Some(word)
-        }
-    }
-
     fn parse_escape_sequence(&mut self) -> Result<char, String> {
         if let Some(c) = self.c {
             let r = match c {
@@ -281,7 +218,62 @@ impl<'a> Iterator for SplitWord<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.next()
+        let separators = &WHITESPACE;
+        let mut word = String::new();
+
+        // skip initial whitespace
+        self.parse_until_none_of(separators);
+
+        let mut quote: Option<char> = None;  // None or Some('\'') or Some('"')
+        let mut backslash = false;  // whether we've just seen a backslash
+        while let Some(c) = self.c {
+            if backslash {
+                match self.parse_escape_sequence() {
+                    Ok(r) => word.push(r),
+                    Err(_) => return None,
+                };
+
+                backslash = false;
+            } else if let Some(q) = quote {
+                // inside either single or double quotes
+                word.push_str(self.parse_until_any_of(&[q, '\\']).as_str());
+
+                match self.c {
+                    Some(c) if c == q => {
+                        quote = None;
+                    },
+                    Some('\\') => backslash = true,
+                    _ => (),
+                }
+            } else {
+                match c {
+                    '\'' | '"' => {
+                        quote = Some(c)
+                    },
+                    '\\' => {
+                        backslash = true;
+                    }
+                    _ if separators.contains(&c) => {
+                        // word is done
+                        break;
+                    },
+                    _ => word.push(c),
+                }
+            }
+
+            self.bump();
+        }
+
+        // if backslash {
+        //     // do nothing -> eat up trailing backslash
+        //     // otherwise we'd have to push it onto `word`
+        // }
+
+        if word.is_empty() {
+            None
+        } else {
+            Some(word)
+        }
     }
 }
 
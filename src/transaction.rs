@@ -0,0 +1,505 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use super::*;
+
+/// Why a job of a [`Transaction`] fails
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FailureReason {
+    /// There's no unit file for the unit
+    NotFound,
+    /// A unit required by `Requires=` or `BindsTo=` fails or isn't started
+    DependencyFailed(String),
+    /// A unit required by `Requisite=` isn't started before this unit
+    RequisiteNotActive(String),
+    /// The jobs are ordered in a cycle and none of them can be dropped, so the whole transaction fails
+    OrderingCycle(OrderingCycle),
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::NotFound => write!(f, "unit not found"),
+            FailureReason::DependencyFailed(unit) => write!(f, "dependency {unit} failed"),
+            FailureReason::RequisiteNotActive(unit) => write!(f, "requisite {unit} isn't active"),
+            FailureReason::OrderingCycle(cycle) => {
+                write!(f, "transaction order is cyclic: {}", cycle.units.join(" -> "))
+            }
+        }
+    }
+}
+
+/// A job which fails, so its unit isn't started
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedJob {
+    pub unit: String,
+    pub reason: FailureReason,
+}
+
+/// Why a job is dropped from a [`Transaction`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DropReason {
+    /// The unit conflicts with this unit, which is kept
+    Conflicts(String),
+    /// The job is part of this ordering cycle and isn't required by the target, so dropping it breaks the cycle
+    OrderingCycle(OrderingCycle),
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropReason::Conflicts(unit) => write!(f, "conflicts with {unit}"),
+            DropReason::OrderingCycle(cycle) => write!(f, "breaks ordering cycle {}", cycle.units.join(" -> ")),
+        }
+    }
+}
+
+/// A job dropped from a [`Transaction`], its unit isn't started
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DroppedJob {
+    pub unit: String,
+    pub reason: DropReason,
+}
+
+/// The start transaction systemd would build for a unit, see [`UnitGraph::transaction()`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transaction {
+    /// The unit the transaction starts
+    pub target: String,
+    /// The units which are started, in start order
+    pub start_order: Vec<String>,
+    pub dropped: Vec<DroppedJob>,
+    pub failed: Vec<FailedJob>,
+    /// The ordering cycles between jobs, each is either broken by dropping a job or fails the transaction
+    pub ordering_cycles: Vec<OrderingCycle>,
+}
+
+impl Transaction {
+    /// Whether the target itself is started
+    pub fn is_ok(&self) -> bool {
+        self.starts(&self.target)
+    }
+
+    /// Whether unit `name` is started by this transaction
+    pub fn starts(&self, name: &str) -> bool {
+        self.start_order.iter().any(|u| u == name)
+    }
+
+    /// Why unit `name` isn't started, if it's part of the transaction but fails
+    pub fn failure(&self, name: &str) -> Option<&FailureReason> {
+        self.failed.iter().find(|j| j.unit == name).map(|j| &j.reason)
+    }
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for unit in &self.start_order {
+            writeln!(f, "start {unit}")?;
+        }
+        for job in &self.dropped {
+            writeln!(f, "drop {} ({})", job.unit, job.reason)?;
+        }
+        for job in &self.failed {
+            writeln!(f, "fail {} ({})", job.unit, job.reason)?;
+        }
+        for cycle in &self.ordering_cycles {
+            writeln!(f, "{cycle}")?;
+        }
+        Ok(())
+    }
+}
+
+impl UnitGraph {
+    /// Simulate starting unit `target` (e.g. `multi-user.target` at boot) with no unit active yet
+    ///
+    /// This pulls in units through `Requires=`, `Wants=` and `BindsTo=`, drops jobs for conflicting units (preferring
+    /// units the target doesn't require), fails jobs of missing units and of units whose requirements fail, and
+    /// orders the remaining jobs by `After=`/`Before=`. Units of type `.device` are assumed to exist.
+    ///
+    /// Like systemd, an ordering cycle is broken by dropping a job of the cycle the target doesn't require. If
+    /// every job of the cycle is required, the whole transaction fails and nothing is started.
+    pub fn transaction(&self, target: &str) -> Transaction {
+        let mut dropped = Vec::new();
+        let mut dropped_units = BTreeSet::new();
+        let jobs = self.pull_in(target, &dropped_units);
+
+        let required = self.required_units(target, &jobs);
+        for &unit in &jobs {
+            if dropped_units.contains(unit) {
+                continue;
+            }
+            for d in self.dependencies(unit).filter(|d| d.kind == DependencyKind::Conflicts) {
+                let other = d.to;
+                if other == unit || !jobs.contains(other) || dropped_units.contains(other) {
+                    continue;
+                }
+                let (drop, keep) = if other == target || (required.contains(other) && !required.contains(unit)) {
+                    (unit, other)
+                } else {
+                    (other, unit)
+                };
+                dropped.push(DroppedJob {
+                    unit: drop.to_string(),
+                    reason: DropReason::Conflicts(keep.to_string()),
+                });
+                dropped_units.insert(drop);
+                if drop == unit {
+                    break;
+                }
+            }
+        }
+
+        let mut ordering_cycles = Vec::new();
+        'transaction: loop {
+            let jobs = self.pull_in(target, &dropped_units);
+            let required = self.required_units(target, &jobs);
+
+            let mut failed: BTreeMap<&str, FailureReason> = jobs
+                .iter()
+                .filter(|&&u| self.unit(u).is_none() && UnitType::from_name(u) != Some(UnitType::Device))
+                .map(|&u| (u, FailureReason::NotFound))
+                .collect();
+
+            loop {
+                // failed requirements fail the units requiring them
+                let mut changed = true;
+                while changed {
+                    changed = false;
+                    for &unit in &jobs {
+                        if failed.contains_key(unit) {
+                            continue;
+                        }
+                        let broken = self
+                            .dependencies(unit)
+                            .filter(|d| matches!(d.kind, DependencyKind::Requires | DependencyKind::BindsTo))
+                            .find(|d| failed.contains_key(d.to) || dropped_units.contains(d.to));
+                        if let Some(d) = broken {
+                            failed.insert(unit, FailureReason::DependencyFailed(d.to.to_string()));
+                            changed = true;
+                        }
+                    }
+                }
+
+                let started: BTreeSet<&str> = jobs.iter().copied().filter(|u| !failed.contains_key(u)).collect();
+                let start_order = match self.start_order(&started) {
+                    Ok(start_order) => start_order,
+                    Err(cycle) => {
+                        ordering_cycles.push(cycle.clone());
+                        let units = &cycle.units[..cycle.units.len() - 1];
+                        let droppable = units
+                            .iter()
+                            .filter_map(|u| jobs.get(u.as_str()).copied())
+                            .find(|u| !required.contains(u));
+                        match droppable {
+                            Some(unit) => {
+                                dropped.push(DroppedJob {
+                                    unit: unit.to_string(),
+                                    reason: DropReason::OrderingCycle(cycle),
+                                });
+                                dropped_units.insert(unit);
+                                continue 'transaction;
+                            }
+                            None => {
+                                return Transaction {
+                                    target: target.to_string(),
+                                    start_order: Vec::new(),
+                                    dropped,
+                                    failed: vec![FailedJob {
+                                        unit: target.to_string(),
+                                        reason: FailureReason::OrderingCycle(cycle),
+                                    }],
+                                    ordering_cycles,
+                                };
+                            }
+                        }
+                    }
+                };
+
+                // a requisite has to be started before the unit requiring it
+                let position: BTreeMap<&str, usize> = start_order.iter().enumerate().map(|(i, u)| (*u, i)).collect();
+                let mut requisite_failed = false;
+                for &unit in &started {
+                    let missing = self
+                        .dependencies(unit)
+                        .filter(|d| d.kind == DependencyKind::Requisite)
+                        .find(|d| position.get(d.to).is_none_or(|p| *p > position[unit]));
+                    if let Some(d) = missing {
+                        failed.insert(unit, FailureReason::RequisiteNotActive(d.to.to_string()));
+                        requisite_failed = true;
+                    }
+                }
+                if requisite_failed {
+                    continue;
+                }
+
+                return Transaction {
+                    target: target.to_string(),
+                    start_order: start_order.into_iter().map(str::to_string).collect(),
+                    dropped,
+                    failed: failed
+                        .into_iter()
+                        .map(|(unit, reason)| FailedJob {
+                            unit: unit.to_string(),
+                            reason,
+                        })
+                        .collect(),
+                    ordering_cycles,
+                };
+            }
+        }
+    }
+
+    // `target` and the units it pulls in, without going through `excluded` units
+    fn pull_in<'a>(&'a self, target: &'a str, excluded: &BTreeSet<&str>) -> BTreeSet<&'a str> {
+        let pulling = [DependencyKind::Requires, DependencyKind::Wants, DependencyKind::BindsTo];
+        let mut jobs = BTreeSet::from([target]);
+        let mut queue = VecDeque::from([target]);
+
+        while let Some(unit) = queue.pop_front() {
+            for d in self.dependencies(unit) {
+                if pulling.contains(&d.kind) && !excluded.contains(d.to) && jobs.insert(d.to) {
+                    queue.push_back(d.to);
+                }
+            }
+        }
+
+        jobs
+    }
+
+    // the jobs `target` can't do without
+    fn required_units<'a>(&'a self, target: &'a str, jobs: &BTreeSet<&str>) -> BTreeSet<&'a str> {
+        let mut required = BTreeSet::from([target]);
+        let mut queue = VecDeque::from([target]);
+
+        while let Some(unit) = queue.pop_front() {
+            for d in self.dependencies(unit) {
+                let requires = matches!(d.kind, DependencyKind::Requires | DependencyKind::BindsTo);
+                if requires && jobs.contains(d.to) && required.insert(d.to) {
+                    queue.push_back(d.to);
+                }
+            }
+        }
+
+        required
+    }
+
+    // sort `jobs` by their ordering dependencies, ties are broken by name, fails with the first ordering cycle
+    fn start_order<'a>(&'a self, jobs: &BTreeSet<&'a str>) -> Result<Vec<&'a str>, OrderingCycle> {
+        let successors: BTreeMap<&str, BTreeSet<&str>> = self
+            .ordering()
+            .into_iter()
+            .filter(|(unit, _)| jobs.contains(unit))
+            .map(|(unit, next)| (unit, next.into_iter().filter(|n| jobs.contains(n)).collect()))
+            .collect();
+        if let Some(cycle) = find_cycles(&successors).into_iter().next() {
+            return Err(cycle);
+        }
+
+        let mut waiting_for: BTreeMap<&str, usize> = jobs.iter().map(|u| (*u, 0)).collect();
+        for next in successors.values().flatten() {
+            *waiting_for.entry(next).or_default() += 1;
+        }
+
+        let mut order = Vec::with_capacity(jobs.len());
+        while let Some(unit) = waiting_for.iter().find(|(_, waiting)| **waiting == 0).map(|(unit, _)| *unit) {
+            waiting_for.remove(unit);
+            for next in successors.get(unit).into_iter().flatten() {
+                if let Some(waiting) = waiting_for.get_mut(next) {
+                    *waiting -= 1;
+                }
+            }
+            order.push(unit);
+        }
+        debug_assert_eq!(order.len(), jobs.len());
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(units: &[(&str, &str)]) -> UnitGraph {
+        let mut graph = UnitGraph::new();
+        for (name, data) in units {
+            graph.insert(*name, SystemdUnit::load_from_str(data).unwrap());
+        }
+        graph
+    }
+
+    #[test]
+    fn start_order() {
+        let graph = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service c.service\nAfter=c.service\n"),
+            ("a.service", "[Unit]\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nBefore=c.service\n"),
+            ("c.service", "[Unit]\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        assert!(transaction.is_ok());
+        assert_eq!(transaction.start_order, ["b.service", "a.service", "c.service", "t.target"]);
+    }
+
+    #[test]
+    fn two_cycles_in_one_component() {
+        // a and b as well as c and d are ordered against each other, and b before c, d before a
+        let graph = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service c.service d.service\n"),
+            ("a.service", "[Unit]\nBefore=b.service\n"),
+            ("b.service", "[Unit]\nBefore=a.service c.service\n"),
+            ("c.service", "[Unit]\nBefore=d.service\n"),
+            ("d.service", "[Unit]\nBefore=c.service a.service\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        assert!(transaction.failed.is_empty());
+        assert_eq!(transaction.ordering_cycles.len(), 2);
+        let dropped: Vec<&str> = transaction.dropped.iter().map(|j| j.unit.as_str()).collect();
+        assert_eq!(dropped, ["a.service", "c.service"]);
+        assert_eq!(transaction.start_order, ["b.service", "d.service", "t.target"]);
+    }
+
+    #[test]
+    fn cycle_drops_wanted_job() {
+        let graph = graph(&[
+            ("t.target", "[Unit]\nRequires=a.service\nWants=b.service c.service\n"),
+            ("a.service", "[Unit]\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nAfter=a.service\n"),
+            ("c.service", "[Unit]\nRequires=b.service\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        let cycle = OrderingCycle {
+            units: vec!["a.service".into(), "b.service".into(), "a.service".into()],
+        };
+        assert_eq!(
+            transaction.dropped,
+            [DroppedJob {
+                unit: "b.service".into(),
+                reason: DropReason::OrderingCycle(cycle.clone()),
+            }]
+        );
+        assert_eq!(transaction.ordering_cycles, [cycle]);
+        // units requiring the dropped job fail
+        assert_eq!(transaction.failure("c.service"), Some(&FailureReason::DependencyFailed("b.service".into())));
+        assert_eq!(transaction.start_order, ["a.service", "t.target"]);
+        assert!(transaction.to_string().contains("drop b.service (breaks ordering cycle a.service -> b.service"));
+    }
+
+    #[test]
+    fn required_cycle_fails() {
+        let graph = graph(&[
+            ("t.target", "[Unit]\nRequires=a.service\nWants=c.service\n"),
+            ("a.service", "[Unit]\nRequires=b.service\nAfter=b.service\n"),
+            ("b.service", "[Unit]\nAfter=a.service\n"),
+            ("c.service", "[Unit]\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        assert!(!transaction.is_ok());
+        assert!(transaction.start_order.is_empty());
+        assert!(transaction.dropped.is_empty());
+        assert!(matches!(transaction.failure("t.target"), Some(FailureReason::OrderingCycle(c)) if c.units.len() == 3));
+    }
+
+    #[test]
+    fn conflicts() {
+        // neither is required, the unit conflicted with is dropped
+        let graph1 = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Unit]\nConflicts=b.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        let transaction = graph1.transaction("t.target");
+        assert_eq!(
+            transaction.dropped,
+            [DroppedJob {
+                unit: "b.service".into(),
+                reason: DropReason::Conflicts("a.service".into()),
+            }]
+        );
+        assert_eq!(transaction.start_order, ["a.service", "t.target"]);
+
+        // a required unit is kept
+        let graph2 = graph(&[
+            ("t.target", "[Unit]\nWants=a.service\nRequires=b.service\n"),
+            ("a.service", "[Unit]\nConflicts=b.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        let transaction = graph2.transaction("t.target");
+        assert_eq!(transaction.dropped[0].unit, "a.service");
+        assert_eq!(transaction.start_order, ["b.service", "t.target"]);
+
+        // so is the target, and units pulled in only by the dropped unit aren't started
+        let graph3 = graph(&[
+            ("t.target", "[Unit]\nWants=a.service\n"),
+            ("a.service", "[Unit]\nConflicts=t.target\nWants=c.service\n"),
+            ("c.service", "[Unit]\n"),
+        ]);
+        let transaction = graph3.transaction("t.target");
+        assert_eq!(transaction.dropped[0].reason, DropReason::Conflicts("t.target".into()));
+        assert_eq!(transaction.start_order, ["t.target"]);
+        assert_eq!(transaction.to_string(), "start t.target\ndrop a.service (conflicts with t.target)\n");
+    }
+
+    #[test]
+    fn requisite() {
+        let ordered = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Unit]\nRequisite=b.service\nAfter=b.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        assert_eq!(ordered.transaction("t.target").start_order, ["b.service", "a.service", "t.target"]);
+
+        // without ordering the requisite isn't active yet
+        let unordered = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Unit]\nRequisite=b.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        let transaction = unordered.transaction("t.target");
+        assert_eq!(transaction.failure("a.service"), Some(&FailureReason::RequisiteNotActive("b.service".into())));
+        assert_eq!(transaction.start_order, ["b.service", "t.target"]);
+
+        // Requisite= doesn't pull in the unit
+        let not_pulled = graph(&[
+            ("t.target", "[Unit]\nWants=a.service\n"),
+            ("a.service", "[Unit]\nRequisite=b.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        let transaction = not_pulled.transaction("t.target");
+        assert!(!transaction.starts("b.service"));
+        assert!(transaction.failure("a.service").is_some());
+    }
+
+    #[test]
+    fn binds_to() {
+        let graph = graph(&[
+            ("t.target", "[Unit]\nWants=a.service b.service\n"),
+            ("a.service", "[Unit]\nBindsTo=c.service\nAfter=c.service\n"),
+            ("b.service", "[Unit]\nBindsTo=missing.service\n"),
+            ("c.service", "[Unit]\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        assert_eq!(transaction.start_order, ["c.service", "a.service", "t.target"]);
+        assert_eq!(
+            transaction.failure("b.service"),
+            Some(&FailureReason::DependencyFailed("missing.service".into()))
+        );
+        // the target only wants it, so it's still started
+        assert!(transaction.is_ok());
+    }
+
+    #[test]
+    fn missing_requirement() {
+        let graph = graph(&[
+            ("t.target", "[Unit]\nRequires=a.service\nWants=b.service\n"),
+            ("a.service", "[Unit]\nRequires=missing.service\n"),
+            ("b.service", "[Unit]\n"),
+        ]);
+        let transaction = graph.transaction("t.target");
+        assert_eq!(transaction.failure("missing.service"), Some(&FailureReason::NotFound));
+        assert_eq!(
+            transaction.failure("a.service"),
+            Some(&FailureReason::DependencyFailed("missing.service".into()))
+        );
+        assert_eq!(transaction.start_order, ["b.service"]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+use super::*;
+
+/// What's wrong with a section or key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationWarningKind {
+    /// The section isn't valid for this unit type
+    UnknownSection,
+    /// The key isn't valid in any section of this unit type
+    UnknownKey,
+    /// The key is valid, but belongs in another section
+    WrongSection { expected: &'static str },
+}
+
+/// A warning found by [`SystemdUnit::validate()`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationWarning {
+    pub kind: ValidationWarningKind,
+    pub section: String,
+    /// The offending key (`None` for [`ValidationWarningKind::UnknownSection`])
+    pub key: Option<String>,
+    /// A similarly spelled section or key, if any
+    pub suggestion: Option<&'static str>,
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.kind, &self.key) {
+            (ValidationWarningKind::WrongSection { expected }, Some(key)) => write!(
+                f,
+                "Unknown key name '{key}' in section '{}', ignoring. It belongs in section '{expected}'.",
+                self.section
+            )?,
+            (_, Some(key)) => write!(f, "Unknown key name '{key}' in section '{}', ignoring.", self.section)?,
+            (_, None) => write!(f, "Unknown section '{}'. Ignoring.", self.section)?,
+        }
+
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " Did you mean '{suggestion}'?")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&ValidationWarning> for Error {
+    fn from(w: &ValidationWarning) -> Self {
+        let kind = match w.kind {
+            ValidationWarningKind::UnknownSection => ErrorKind::Validation,
+            _ => ErrorKind::UnknownKey,
+        };
+        Error::new(kind, w.to_string())
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, ignoring (ASCII) case
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().map(|c| c.to_ascii_lowercase()).enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Find the candidate closest to `name`, if it's close enough to be a likely misspelling
+fn suggest<'c>(name: &str, candidates: impl Iterator<Item = &'c &'static str>) -> Option<&'static str> {
+    let max_distance = (name.len() / 4).max(2);
+    candidates
+        .map(|c| (edit_distance(name, c), *c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+impl SystemdUnit {
+    /// The unit type according to the unit's path or, if that's unknown, its type specific section
+    pub fn unit_type(&self) -> Option<UnitType> {
+        self.path
+            .as_ref()
+            .and_then(UnitType::from_name)
+            .or_else(|| {
+                UnitType::ALL
+                    .into_iter()
+                    .find(|t| t.section().is_some_and(|s| self.has_section(s)))
+            })
+    }
+
+    /// Check for unknown sections, unknown keys and keys in the wrong section like systemd does when loading
+    /// a unit, using the type from [`SystemdUnit::unit_type()`] (or [`UnitType::Target`] if it's unknown)
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        self.validate_as(self.unit_type().unwrap_or(UnitType::Target))
+    }
+
+    /// Same as [`SystemdUnit::validate()`], but validate as a unit of type `unit_type`
+    pub fn validate_as(&self, unit_type: UnitType) -> Vec<ValidationWarning> {
+        let sections = unit_type.sections();
+        let mut warnings = Vec::new();
+
+        for (section, entries) in self.sections.iter() {
+            if !unit_type.is_known_section(section) {
+                warnings.push(ValidationWarning {
+                    kind: ValidationWarningKind::UnknownSection,
+                    section: section.clone(),
+                    key: None,
+                    suggestion: suggest(section, sections.iter()),
+                });
+                continue;
+            }
+
+            for (key, _) in entries.data.iter() {
+                if unit_type.is_known_key(section, key) {
+                    continue;
+                }
+
+                let kind = match sections
+                    .iter()
+                    .find(|s| *s != section && unit_type.is_known_key(s, key))
+                {
+                    Some(expected) => ValidationWarningKind::WrongSection { expected },
+                    None => ValidationWarningKind::UnknownKey,
+                };
+                let suggestion = match kind {
+                    ValidationWarningKind::UnknownKey => unit_type
+                        .section_keys(section)
+                        .and_then(|groups| suggest(key, groups.iter().flat_map(|keys| keys.iter()))),
+                    _ => None,
+                };
+
+                warnings.push(ValidationWarning {
+                    kind,
+                    section: section.clone(),
+                    key: Some(key.clone()),
+                    suggestion,
+                });
+            }
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings(unit_type: UnitType, data: &str) -> Vec<ValidationWarning> {
+        SystemdUnit::load_from_str(data).unwrap().validate_as(unit_type)
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("ExecStart", "ExecStart"), 0);
+        assert_eq!(edit_distance("execstart", "ExecStart"), 0);
+        assert_eq!(edit_distance("ExecStrat", "ExecStart"), 2);
+        assert_eq!(edit_distance("Descripton", "Description"), 1);
+        assert_eq!(edit_distance("", "Type"), 4);
+    }
+
+    #[test]
+    fn suggestions() {
+        let keys = [SERVICE_KEYS];
+        let candidates = || keys.iter().flat_map(|keys| keys.iter());
+        assert_eq!(suggest("Typ", candidates()), Some("Type"));
+        assert_eq!(suggest("RemainAfterExti", candidates()), Some("RemainAfterExit"));
+        assert_eq!(suggest("Completely", candidates()), None);
+    }
+
+    #[test]
+    fn valid() {
+        let data = "[Unit]\nDescription=x\nBindTo=a.service\nConditionPathExists=/x\n[Service]\nExecStart=/bin/true\n\
+            X-Foo=1\n[X-Extension]\nAnything=1\n[Install]\nWantedBy=multi-user.target\n";
+        assert_eq!(warnings(UnitType::Service, data), []);
+    }
+
+    #[test]
+    fn unknown() {
+        let data = "[Unit]\nDescripton=x\nExecStart=/bin/true\n[Serivce]\nType=simple\n[Install]\nFoo=1\n";
+        let warnings = warnings(UnitType::Service, data);
+        assert_eq!(
+            warnings,
+            [
+                ValidationWarning {
+                    kind: ValidationWarningKind::UnknownKey,
+                    section: UNIT_SECTION.into(),
+                    key: Some("Descripton".into()),
+                    suggestion: Some("Description"),
+                },
+                ValidationWarning {
+                    kind: ValidationWarningKind::WrongSection { expected: SERVICE_SECTION },
+                    section: UNIT_SECTION.into(),
+                    key: Some("ExecStart".into()),
+                    suggestion: None,
+                },
+                ValidationWarning {
+                    kind: ValidationWarningKind::UnknownSection,
+                    section: "Serivce".into(),
+                    key: None,
+                    suggestion: Some(SERVICE_SECTION),
+                },
+                ValidationWarning {
+                    kind: ValidationWarningKind::UnknownKey,
+                    section: INSTALL_SECTION.into(),
+                    key: Some("Foo".into()),
+                    suggestion: None,
+                },
+            ]
+        );
+
+        assert_eq!(
+            warnings[0].to_string(),
+            "Unknown key name 'Descripton' in section 'Unit', ignoring. Did you mean 'Description'?"
+        );
+        assert_eq!(
+            warnings[1].to_string(),
+            "Unknown key name 'ExecStart' in section 'Unit', ignoring. It belongs in section 'Service'."
+        );
+        assert_eq!(warnings[2].to_string(), "Unknown section 'Serivce'. Ignoring. Did you mean 'Service'?");
+        assert_eq!(Error::from(&warnings[0]).kind(), ErrorKind::UnknownKey);
+        assert_eq!(Error::from(&warnings[2]).kind(), ErrorKind::Validation);
+    }
+
+    #[test]
+    fn unit_type() {
+        let mut unit = SystemdUnit::load_from_str("[Unit]\nDescription=x\n[Socket]\nListenStream=80\n").unwrap();
+        assert_eq!(unit.unit_type(), Some(UnitType::Socket));
+        assert_eq!(unit.validate(), []);
+
+        // the path takes precedence over the sections
+        unit.path = Some("foo.service".into());
+        assert_eq!(unit.unit_type(), Some(UnitType::Service));
+        assert_eq!(unit.validate()[0].kind, ValidationWarningKind::UnknownSection);
+
+        // units without a type specific section are validated as targets
+        let unit = SystemdUnit::load_from_str("[Unit]\nDescription=x\n[Install]\nWantedBy=a.target\n").unwrap();
+        assert_eq!(unit.unit_type(), None);
+        assert_eq!(unit.validate(), []);
+    }
+}
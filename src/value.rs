@@ -1,7 +1,7 @@
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use ordered_multimap::ListOrderedMultimap;
 use std::str::FromStr;
-use super::{parse_bool, quote_value, unquote_value};
+use super::{parse_bool, quote_value, unquote_value, unquote_value_relaxed};
 
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Entries {
@@ -10,7 +10,7 @@ pub struct Entries {
 
 impl Default for &Entries {
     fn default() -> Self {
-        static EMPTY: Lazy<Entries> = Lazy::new(|| Entries::default());
+        static EMPTY: Lazy<Entries> = Lazy::new(Entries::default);
         &EMPTY
     }
 }
@@ -19,18 +19,54 @@ pub type EntryKey = String;
 
 pub type EntryRawValue = String;
 
-#[derive(Clone, Default, Debug, PartialEq)]
+/// How to unquote a value
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnquoteMode {
+    /// Reject malformed values (see [`unquote_value()`])
+    #[default]
+    Strict,
+    /// Accept malformed values, keeping invalid escape sequences as they are (see [`unquote_value_relaxed()`])
+    Relaxed,
+}
+
+#[derive(Clone, Debug)]
+enum Unquoted {
+    // `raw` doesn't contain anything to unquote
+    Verbatim,
+    // strictly unquoted `raw`
+    Strict(String),
+    // `raw` can't be unquoted strictly, this is the relaxed result
+    Relaxed(String),
+}
+
+#[derive(Clone, Default, Debug)]
 pub struct EntryValue {
     raw: EntryRawValue,
-    unquoted: String,
+    // computed on first use
+    unquoted: OnceCell<Unquoted>,
 }
 
 impl EntryValue {
+    /// Create from an unquoted value, quoting it as necessary
     pub fn from_unquoted<S: Into<String>>(unquoted: S) -> Self {
         let unquoted = unquoted.into();
+        let raw = quote_value(unquoted.as_str());
+        let unquoted = if raw == unquoted {
+            Unquoted::Verbatim
+        } else {
+            Unquoted::Strict(unquoted)
+        };
         Self {
-            raw: quote_value(unquoted.as_str()),
-            unquoted,
+            raw,
+            unquoted: OnceCell::with_value(unquoted),
+        }
+    }
+
+    /// Create from a raw value without validating it (it is only unquoted on first use)
+    pub fn from_raw<S: Into<String>>(raw: S) -> Self {
+        Self {
+            raw: raw.into(),
+            unquoted: OnceCell::new(),
         }
     }
 
@@ -38,13 +74,44 @@ impl EntryValue {
         &self.raw
     }
 
+    fn cached(&self) -> &Unquoted {
+        self.unquoted.get_or_init(|| {
+            if !self.raw.contains(['\\', '"', '\'']) {
+                return Unquoted::Verbatim;
+            }
+            match unquote_value(self.raw.as_str()) {
+                Ok(s) => Unquoted::Strict(s),
+                Err(_) => Unquoted::Relaxed(unquote_value_relaxed(self.raw.as_str())),
+            }
+        })
+    }
+
+    /// Unquote the value, never fails (see [`UnquoteMode::Relaxed`])
     pub fn unquote(&self) -> String {
-        self.try_unquote().expect("parsing error")
+        self.unquote_relaxed().to_string()
     }
 
     #[deprecated = "use unquote() or try_unquote()"]
     pub fn unquoted(&self) -> &String {
-        &self.unquoted
+        match self.cached() {
+            Unquoted::Verbatim => &self.raw,
+            Unquoted::Strict(s) | Unquoted::Relaxed(s) => s,
+        }
+    }
+
+    /// Unquote the value, never fails (see [`UnquoteMode::Relaxed`])
+    pub fn unquote_relaxed(&self) -> &str {
+        match self.cached() {
+            Unquoted::Verbatim => self.raw.as_str(),
+            Unquoted::Strict(s) | Unquoted::Relaxed(s) => s.as_str(),
+        }
+    }
+
+    pub fn unquote_with(&self, mode: UnquoteMode) -> Result<&str, super::Error> {
+        match mode {
+            UnquoteMode::Strict => self.unquote_strict(),
+            UnquoteMode::Relaxed => Ok(self.unquote_relaxed()),
+        }
     }
 
     pub fn to_bool(&self) -> Result<bool, super::Error> {
@@ -56,16 +123,37 @@ impl EntryValue {
         parse_bool(trimmed)
     }
 
+    /// Create from a raw value, failing if it can't be unquoted strictly
+    ///
+    /// Only escape sequences can be invalid, so values without a backslash are still unquoted lazily.
     pub fn try_from_raw<S: Into<String>>(raw: S) -> Result<Self, super::Error> {
-        let raw = raw.into();
-        Ok(Self {
-            unquoted: unquote_value(raw.as_str())?,
-            raw,
-        })
+        let value = Self::from_raw(raw);
+        if value.raw.contains('\\') {
+            value.unquote_strict()?;
+        }
+        Ok(value)
     }
 
+    /// Unquote the value strictly (see [`UnquoteMode::Strict`]), use
+    /// [`unquote_with()`](EntryValue::unquote_with) to borrow the result
     pub fn try_unquote(&self) -> Result<String, super::Error> {
-        unquote_value(self.raw.as_str())
+        self.unquote_strict().map(str::to_string)
+    }
+
+    fn unquote_strict(&self) -> Result<&str, super::Error> {
+        match self.cached() {
+            Unquoted::Verbatim => Ok(self.raw.as_str()),
+            Unquoted::Strict(s) => Ok(s.as_str()),
+            // the error isn't cached, so just recreate it
+            Unquoted::Relaxed(s) => unquote_value(self.raw.as_str()).and(Ok(s.as_str())),
+        }
+    }
+}
+
+/// Values are equal if their raw values are equal
+impl PartialEq for EntryValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
     }
 }
 
@@ -93,3 +181,73 @@ impl FromStr for EntryValue {
 }
 
 pub type SectionKey = String;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn lazy() {
+        let value = EntryValue::from_raw("\"a  b\"");
+        assert!(value.unquoted.get().is_none());
+        assert_eq!(value.unquote_relaxed(), "a  b");
+        assert!(matches!(value.unquoted.get(), Some(Unquoted::Strict(s)) if s == "a  b"));
+
+        // nothing to unquote isn't copied
+        let value = EntryValue::from_raw("plain");
+        assert_eq!(value.unquote_relaxed(), "plain");
+        assert!(matches!(value.unquoted.get(), Some(Unquoted::Verbatim)));
+    }
+
+    #[test]
+    fn modes() {
+        let value = EntryValue::from_raw("'a b' \\x4g");
+        assert_eq!(value.unquote_with(UnquoteMode::Strict).unwrap_err().kind(), ErrorKind::Escape);
+        assert_eq!(value.try_unquote().unwrap_err().kind(), ErrorKind::Escape);
+        assert_eq!(value.unquote_with(UnquoteMode::Relaxed).unwrap(), "a b \\x4g");
+        assert_eq!(value.unquote(), "a b \\x4g");
+        // the relaxed result is cached, strict unquoting still fails afterwards
+        assert!(value.try_unquote().is_err());
+
+        let value = EntryValue::from_raw("'a b' \\x41");
+        assert_eq!(value.unquote_with(UnquoteMode::Strict).unwrap(), "a b A");
+        assert_eq!(value.try_unquote().unwrap(), "a b A");
+    }
+
+    #[test]
+    fn try_from_raw() {
+        assert_eq!(EntryValue::try_from_raw("\\x4g").unwrap_err().kind(), ErrorKind::Escape);
+        assert_eq!("a\\".parse::<EntryValue>().unwrap_err().kind(), ErrorKind::Escape);
+
+        let value = EntryValue::try_from_raw("\\x41").unwrap();
+        assert_eq!(value.raw(), "\\x41");
+        assert_eq!(value.unquote_relaxed(), "A");
+
+        // without a backslash there's nothing that can be invalid, so it's still unquoted lazily
+        let value = EntryValue::try_from_raw("\"a b\"").unwrap();
+        assert!(value.unquoted.get().is_none());
+    }
+
+    #[test]
+    fn from_unquoted() {
+        let value = EntryValue::from_unquoted("a \"b\"\n");
+        assert_eq!(value.raw(), "a \\\"b\\\"\\n");
+        assert_eq!(value.try_unquote().unwrap(), "a \"b\"\n");
+        assert_eq!(value, EntryValue::from_raw(value.raw().as_str()));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn unquoted() {
+        assert_eq!(EntryValue::from_raw("'a b'").unquoted(), "a b");
+        assert_eq!(EntryValue::from_raw("a\\").unquoted(), "a");
+    }
+
+    #[test]
+    fn to_bool() {
+        assert!(EntryValue::from_raw(" on ").to_bool().unwrap());
+        assert!(!EntryValue::from_raw("").to_bool().unwrap());
+        assert_eq!(EntryValue::from_raw("maybe").to_bool().unwrap_err().kind(), ErrorKind::Bool);
+    }
+}
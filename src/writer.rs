@@ -0,0 +1,491 @@
+use super::*;
+
+const DEFAULT_HEADER: &str = "Automatically generated by systemd-unit-rs";
+
+/// The comment written before the first section
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Header {
+    None,
+    /// `# Automatically generated by systemd-unit-rs`
+    #[default]
+    Default,
+    /// Each line is written as a comment
+    Custom(String),
+}
+
+/// Where blank lines go between sections
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SectionSpacing {
+    /// After every section, including the last one
+    #[default]
+    AfterEvery,
+    /// Only between sections
+    Between,
+    None,
+}
+
+/// How [`SystemdUnit::write_with()`] formats a unit, the default is the format of [`SystemdUnit::write_to()`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub header: Header,
+    /// Add `# Source: <path>` to the header if the unit has a path
+    pub source_path: bool,
+    pub section_spacing: SectionSpacing,
+    /// Pad the keys of a section so their `=` line up (written as ` = `)
+    pub align_keys: bool,
+    /// Wrap values longer than this (in characters, including the key) with `\` line continuations
+    ///
+    /// Values are only wrapped at spaces, so the parser reads them back identically.
+    pub max_line_width: Option<usize>,
+}
+
+// the byte offsets of the spaces in `raw` at which the line of `prefix` + `raw` is continued
+fn wrap_points(prefix: &str, raw: &str, width: usize) -> Vec<usize> {
+    // (byte offset, char offset) of the spaces a line can be continued at
+    let mut candidates = Vec::new();
+    let mut escaped = false;
+    let mut chars = 0;
+    for (i, c) in raw.char_indices() {
+        if c == ' ' && i > 0 && !escaped {
+            // the parser skips comment lines, even in continuations
+            let rest = raw[i + 1..].trim_start_matches(parser::WHITESPACE);
+            if !rest.is_empty() && !rest.starts_with(['#', ';']) {
+                candidates.push((i, chars));
+            }
+        }
+        escaped = !escaped && c == '\\';
+        chars += 1;
+    }
+
+    let mut points = Vec::new();
+    let mut start = 0;
+    let mut indent = prefix.chars().count();
+    let mut candidates = candidates.into_iter().peekable();
+    while indent + chars - start > width {
+        // the last point the line (with its trailing `\`) fits before, or the first one if there's none
+        let mut point = None;
+        while let Some(&(i, c)) = candidates.peek() {
+            if point.is_some() && indent + c - start + 1 > width {
+                break;
+            }
+            point = Some((i, c));
+            candidates.next();
+        }
+
+        let Some((i, c)) = point else {
+            break;
+        };
+        points.push(i);
+        start = c + 1;
+        indent = 0;
+    }
+
+    points
+}
+
+impl SystemdUnit {
+    /// Write to a writer, formatted according to `options`
+    pub fn write_with<W: io::Write>(&self, writer: &mut W, options: &WriteOptions) -> io::Result<()> {
+        let header = match &options.header {
+            Header::None => None,
+            Header::Default => Some(DEFAULT_HEADER),
+            Header::Custom(header) => Some(header.as_str()),
+        };
+        for line in header.into_iter().flat_map(str::lines) {
+            if line.is_empty() {
+                writeln!(writer, "#")?;
+            } else {
+                writeln!(writer, "# {line}")?;
+            }
+        }
+        if options.source_path
+            && let Some(path) = &self.path
+        {
+            writeln!(writer, "# Source: {}", path.display())?;
+        }
+
+        for (i, (section, entries)) in self.sections.iter().enumerate() {
+            if i > 0 && options.section_spacing == SectionSpacing::Between {
+                writeln!(writer)?;
+            }
+
+            writeln!(writer, "[{section}]")?;
+            let key_width = entries.data.keys().map(|k| k.chars().count()).max().unwrap_or(0);
+            for (k, v) in &entries.data {
+                let prefix = if options.align_keys {
+                    format!("{k:key_width$} = ")
+                } else {
+                    format!("{k}=")
+                };
+                let raw = v.raw();
+
+                let points = match options.max_line_width {
+                    Some(width) => wrap_points(&prefix, raw, width),
+                    None => Vec::new(),
+                };
+                let mut start = 0;
+                write!(writer, "{prefix}")?;
+                for point in points {
+                    writeln!(writer, "{}\\", &raw[start..point])?;
+                    start = point + 1;
+                }
+                writeln!(writer, "{}", &raw[start..])?;
+            }
+
+            if options.section_spacing == SectionSpacing::AfterEvery {
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How [`SystemdUnit::write_file()`] writes a unit file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileOptions {
+    pub format: WriteOptions,
+    /// The permissions of the file, regardless of the umask
+    pub mode: u32,
+    /// The owner and group of the file, `None` to keep those of the process
+    pub owner: Option<(u32, u32)>,
+    /// Leave the file alone if it already has the same contents
+    pub skip_unchanged: bool,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        Self {
+            format: WriteOptions::default(),
+            mode: 0o644,
+            owner: None,
+            skip_unchanged: false,
+        }
+    }
+}
+
+/// The result of [`SystemdUnit::write_file()`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrittenFile {
+    pub path: PathBuf,
+    /// `false` if the file was skipped because it was unchanged
+    pub changed: bool,
+}
+
+#[cfg(unix)]
+fn set_mode_and_owner(file: &File, options: &FileOptions) -> io::Result<()> {
+    use std::os::unix::fs::{fchown, PermissionsExt};
+
+    file.set_permissions(fs::Permissions::from_mode(options.mode))?;
+    if let Some((uid, gid)) = options.owner {
+        fchown(file, Some(uid), Some(gid))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode_and_owner(_file: &File, _options: &FileOptions) -> io::Result<()> {
+    Ok(())
+}
+
+// a hidden file next to `path`, like systemd's `tempfn_random()`
+fn temp_path(path: &Path) -> PathBuf {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    let name = path.file_name().map_or_else(Default::default, |n| n.to_string_lossy());
+    path.with_file_name(format!(".#{name}{:08x}{:08x}", std::process::id(), nanos))
+}
+
+impl SystemdUnit {
+    /// Write the unit file `path` atomically: to a temporary file in the same directory, which is synced and then
+    /// renamed, so readers never see a partially written file
+    pub fn write_file<P: AsRef<Path>>(&self, path: P, options: &FileOptions) -> Result<WrittenFile, Error> {
+        let path = path.as_ref();
+        let mut contents = Vec::new();
+        self.write_with(&mut contents, &options.format)?;
+
+        if options.skip_unchanged && fs::read(path).is_ok_and(|existing| existing == contents) {
+            let file = File::open(path).map_err(|e| Error::from(e).in_file(path))?;
+            set_mode_and_owner(&file, options).map_err(|e| Error::from(e).in_file(path))?;
+            return Ok(WrittenFile {
+                path: path.to_path_buf(),
+                changed: false,
+            });
+        }
+
+        let temp = temp_path(path);
+        let write_temp = || -> io::Result<()> {
+            let mut file = File::options().write(true).create_new(true).open(&temp)?;
+            io::Write::write_all(&mut file, &contents)?;
+            set_mode_and_owner(&file, options)?;
+            file.sync_all()?;
+            fs::rename(&temp, path)
+        };
+        if let Err(e) = write_temp() {
+            let _ = fs::remove_file(&temp);
+            return Err(Error::from(e).in_file(path));
+        }
+
+        // make the rename itself durable
+        #[cfg(unix)]
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            File::open(dir)
+                .and_then(|d| d.sync_all())
+                .map_err(|e| Error::from(e).in_file(dir))?;
+        }
+
+        Ok(WrittenFile {
+            path: path.to_path_buf(),
+            changed: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(data: &str) -> SystemdUnit {
+        SystemdUnit::load_from_str(data).unwrap()
+    }
+
+    fn write(unit: &SystemdUnit, options: &WriteOptions) -> String {
+        let mut out = Vec::new();
+        unit.write_with(&mut out, options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn default_format() {
+        let unit = unit("[Unit]\nDescription=Foo\n[Service]\nExecStart=/bin/foo \"a b\"\n");
+        assert_eq!(
+            write(&unit, &WriteOptions::default()),
+            "# Automatically generated by systemd-unit-rs\n\
+             [Unit]\nDescription=Foo\n\n\
+             [Service]\nExecStart=/bin/foo \"a b\"\n\n"
+        );
+        let mut out = Vec::new();
+        unit.write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), write(&unit, &WriteOptions::default()));
+    }
+
+    #[test]
+    fn header() {
+        let mut unit = unit("[Unit]\nDescription=Foo\n");
+        let options = WriteOptions {
+            header: Header::Custom("Generated by foo\n\nDo not edit".into()),
+            source_path: true,
+            ..WriteOptions::default()
+        };
+        // without a path there's no source
+        assert_eq!(
+            write(&unit, &options),
+            "# Generated by foo\n#\n# Do not edit\n[Unit]\nDescription=Foo\n\n"
+        );
+        unit.path = Some("/etc/foo.container".into());
+        assert_eq!(
+            write(&unit, &options),
+            "# Generated by foo\n#\n# Do not edit\n# Source: /etc/foo.container\n[Unit]\nDescription=Foo\n\n"
+        );
+
+        let options = WriteOptions {
+            header: Header::None,
+            ..WriteOptions::default()
+        };
+        assert_eq!(write(&unit, &options), "[Unit]\nDescription=Foo\n\n");
+    }
+
+    #[test]
+    fn section_spacing() {
+        let unit = unit("[A]\nX=1\n[B]\nY=2\n[C]\nZ=3\n");
+        let format = |section_spacing| {
+            let options = WriteOptions {
+                header: Header::None,
+                section_spacing,
+                ..WriteOptions::default()
+            };
+            write(&unit, &options)
+        };
+        assert_eq!(format(SectionSpacing::AfterEvery), "[A]\nX=1\n\n[B]\nY=2\n\n[C]\nZ=3\n\n");
+        assert_eq!(format(SectionSpacing::Between), "[A]\nX=1\n\n[B]\nY=2\n\n[C]\nZ=3\n");
+        assert_eq!(format(SectionSpacing::None), "[A]\nX=1\n[B]\nY=2\n[C]\nZ=3\n");
+    }
+
+    #[test]
+    fn align_keys() {
+        let unit = unit("[Unit]\nDescription=Foo\nAfter=a.service\n[Service]\nType=simple\nExecStart=/bin/foo\n");
+        let options = WriteOptions {
+            header: Header::None,
+            align_keys: true,
+            ..WriteOptions::default()
+        };
+        let written = write(&unit, &options);
+        // keys are aligned per section
+        assert_eq!(
+            written,
+            "[Unit]\nDescription = Foo\nAfter       = a.service\n\n\
+             [Service]\nType      = simple\nExecStart = /bin/foo\n\n"
+        );
+        assert_eq!(SystemdUnit::load_from_str(&written).unwrap(), unit);
+    }
+
+    #[test]
+    fn wrap() {
+        let unit = unit("[Service]\nExecStart=/bin/foo --first --second --third\nUser=foo\n");
+        let options = WriteOptions {
+            header: Header::None,
+            max_line_width: Some(20),
+            ..WriteOptions::default()
+        };
+        let written = write(&unit, &options);
+        assert_eq!(written, "[Service]\nExecStart=/bin/foo\\\n--first --second\\\n--third\nUser=foo\n\n");
+        assert!(written.lines().all(|l| l.chars().count() <= 20));
+        assert_eq!(SystemdUnit::load_from_str(&written).unwrap(), unit);
+
+        // a word longer than the line isn't split
+        let options = WriteOptions {
+            max_line_width: Some(5),
+            ..options
+        };
+        assert_eq!(
+            write(&unit, &options),
+            "[Service]\nExecStart=/bin/foo\\\n--first\\\n--second\\\n--third\nUser=foo\n\n"
+        );
+    }
+
+    #[test]
+    fn wrap_reads_back() {
+        // escaped and repeated spaces, quotes and words which would be comment lines on their own
+        let unit = unit(
+            "[Service]\nExecStart=/bin/echo \"a  b\" c  d\\x20e 'p  q' #not ;comment end\nEnvironment=A=\"x y\"\n",
+        );
+        for width in 1..80 {
+            for align_keys in [false, true] {
+                let options = WriteOptions {
+                    max_line_width: Some(width),
+                    align_keys,
+                    ..WriteOptions::default()
+                };
+                let written = write(&unit, &options);
+                assert_eq!(SystemdUnit::load_from_str(&written).unwrap(), unit, "width {width}:\n{written}");
+            }
+        }
+    }
+
+    // a fresh directory below the system's temporary directory
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("systemd-unit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_replaces_atomically() {
+        let dir = dir("write-file-replace");
+        let path = dir.join("foo.service");
+        fs::write(&path, "old").unwrap();
+        // a hard link keeps the old file, which is replaced and not overwritten
+        fs::hard_link(&path, dir.join("old")).unwrap();
+
+        let unit = unit("[Service]\nExecStart=/bin/foo\n");
+        let written = unit.write_file(&path, &FileOptions::default()).unwrap();
+        assert_eq!(
+            written,
+            WrittenFile {
+                path: path.clone(),
+                changed: true,
+            }
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), write(&unit, &WriteOptions::default()));
+        assert_eq!(fs::read_to_string(dir.join("old")).unwrap(), "old");
+        // no temporary file is left behind
+        assert_eq!(file_names(&dir), ["foo.service", "old"]);
+
+        // nor when writing fails
+        fs::create_dir(dir.join("dir.service")).unwrap();
+        fs::write(dir.join("dir.service/file"), "").unwrap();
+        let e = unit.write_file(dir.join("dir.service"), &FileOptions::default()).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Io);
+        assert_eq!(e.path(), Some(dir.join("dir.service").as_path()));
+        assert_eq!(file_names(&dir), ["dir.service", "foo.service", "old"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_skip_unchanged() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = dir("write-file-skip");
+        let path = dir.join("foo.service");
+        let unit = unit("[Service]\nExecStart=/bin/foo\n");
+        let options = FileOptions {
+            skip_unchanged: true,
+            ..FileOptions::default()
+        };
+
+        assert!(unit.write_file(&path, &options).unwrap().changed);
+        let inode = fs::metadata(&path).unwrap().ino();
+        // the same contents leave the file alone
+        assert!(!unit.write_file(&path, &options).unwrap().changed);
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+        // but not without skip_unchanged
+        assert!(unit.write_file(&path, &FileOptions::default()).unwrap().changed);
+        assert_ne!(fs::metadata(&path).unwrap().ino(), inode);
+
+        let other = SystemdUnit::load_from_str("[Service]\nExecStart=/bin/bar\n").unwrap();
+        assert!(other.write_file(&path, &options).unwrap().changed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_mode_and_owner() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = dir("write-file-mode");
+        let path = dir.join("foo.service");
+        let unit = unit("[Service]\nExecStart=/bin/foo\n");
+
+        unit.write_file(&path, &FileOptions::default()).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o644);
+        // only root may give files away, so use the owner the process already has
+        let owner = (metadata.uid(), metadata.gid());
+
+        let options = FileOptions {
+            mode: 0o600,
+            owner: Some(owner),
+            ..FileOptions::default()
+        };
+        unit.write_file(&path, &options).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o600);
+        assert_eq!((metadata.uid(), metadata.gid()), owner);
+
+        // the mode is applied to unchanged files too
+        let options = FileOptions {
+            mode: 0o640,
+            skip_unchanged: true,
+            ..FileOptions::default()
+        };
+        assert!(!unit.write_file(&path, &options).unwrap().changed);
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o640);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Conformance corpus for the parser, derived from the `config_file` cases of systemd's
+//! `src/test/test-conf-parser.c` (`test_config_parse()`) plus the BOM handling of `config_parse()`
+//!
+//! Every case is parsed by both [`SystemdUnit`] and [`SystemdUnitRef`], which must agree.
+
+use systemd_unit::{Error, ErrorKind, SystemdUnit, SystemdUnitRef};
+
+fn x1000(s: &str) -> String {
+    s.repeat(1000)
+}
+
+fn long_line() -> String {
+    x1000(&format!(
+        "{}{}{}",
+        "x".repeat(1000),
+        "abcdefghijklmnopqrstuvwxyz".repeat(10),
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZ".repeat(10)
+    ))
+}
+
+/// Parse `data` as a [`SystemdUnit`] and check that parsing it as a [`SystemdUnitRef`] gives the same result
+fn load(data: &str) -> Result<SystemdUnit, Error> {
+    let unit = SystemdUnit::load_from_str(data);
+    let borrowed = SystemdUnitRef::load_from_str(data).and_then(|u| u.to_unit());
+    match (&unit, &borrowed) {
+        (Ok(unit), Ok(borrowed)) => assert_eq!(unit, borrowed),
+        (Err(e), Err(borrowed_e)) => assert_eq!(e, borrowed_e),
+        _ => panic!("parse results differ: {unit:?} vs. {borrowed:?}"),
+    }
+    unit
+}
+
+/// The raw value of the last `setting1=` in `[Section]`
+fn setting1(data: &str) -> String {
+    let unit = load(data).unwrap();
+    unit.lookup_last_value("Section", "setting1").unwrap().raw().clone()
+}
+
+#[test]
+fn single_setting() {
+    assert_eq!(setting1("[Section]\nsetting1=1\n"), "1");
+}
+
+#[test]
+fn quoted_setting() {
+    let data = "[Section]\nsetting1=\"a  b\" 'c'\\x41\n";
+    assert_eq!(setting1(data), "\"a  b\" 'c'\\x41");
+    let unit = load(data).unwrap();
+    assert_eq!(unit.lookup_last("Section", "setting1").as_deref(), Some("a  b cA"));
+}
+
+#[test]
+fn no_terminating_newline() {
+    assert_eq!(setting1("[Section]\nsetting1=1"), "1");
+}
+
+#[test]
+fn whitespace_and_no_terminating_newline() {
+    assert_eq!(setting1("\n\n\n\n[Section]\n\n\nsetting1=1"), "1");
+}
+
+#[test]
+fn repeated_settings() {
+    let data = "[Section]\n[Section]\nsetting1=1\nsetting1=    2 \t\nsetting1=    1\n";
+    assert_eq!(setting1(data), "1");
+
+    let unit = load(data).unwrap();
+    let values: Vec<String> = unit.lookup_all("Section", "setting1").collect();
+    assert_eq!(values, ["1", "2", "1"]);
+}
+
+#[test]
+fn empty_line_breaks_continuation() {
+    let data = "[Section]\n[Section]\nsetting1=1\nsetting1=2\\\n   \nsetting1=1\n";
+    let unit = load(data).unwrap();
+    let values: Vec<String> = unit.lookup_all("Section", "setting1").collect();
+    assert_eq!(values, ["1", "2", "1"]);
+}
+
+#[test]
+fn continuation() {
+    assert_eq!(setting1("[Section]\nsetting1=1\\\n2\\\n3\n"), "1 2 3");
+}
+
+#[test]
+fn continuation_in_comment_is_ignored() {
+    assert_eq!(setting1("[Section]\n#hogehoge\\\nsetting1=1\\\n2\\\n3\n"), "1 2 3");
+}
+
+#[test]
+fn comment_inside_continuation_is_ignored() {
+    assert_eq!(setting1("[Section]\nsetting1=1\\\n#hogehoge\\\n2\\\n3\n"), "1 2 3");
+}
+
+#[test]
+fn whitespace_before_comment_and_key() {
+    assert_eq!(setting1("[Section]\n   #hogehoge\\\n   setting1=1\\\n2\\\n3\n"), "1 2 3");
+    assert_eq!(setting1("[Section]\n   setting1=1\\\n   #hogehoge\\\n2\\\n3\n"), "1 2 3");
+}
+
+#[test]
+fn escaped_backslash_at_end_of_line() {
+    assert_eq!(setting1("[Section]\nsetting1=1\\\n2\\\n3\\\\\n"), "1 2 3\\\\");
+}
+
+#[test]
+fn continuation_with_trailing_escapes() {
+    assert_eq!(setting1("[Section]\nsetting1=1\\\\\\\n\\\\2\n"), "1\\\\ \\\\2");
+}
+
+#[test]
+fn long_line_below_limit() {
+    assert_eq!(setting1(&format!("\n[Section]\n\nsetting1={}\n", x1000("ABCD"))), x1000("ABCD"));
+}
+
+#[test]
+fn long_line_with_continuation() {
+    let expected = format!("{} foobar", x1000("ABCD"));
+    assert_eq!(setting1(&format!("[Section]\nsetting1={}\\\nfoobar", x1000("ABCD"))), expected);
+    assert_eq!(setting1(&format!("[Section]\nsetting1={}\\\nfoobar\\\n", x1000("ABCD"))), expected);
+}
+
+#[test]
+fn line_above_limit() {
+    let err = load(&format!("[Section]\nsetting1={}\n", long_line())).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Syntax);
+}
+
+#[test]
+fn continuation_above_limit() {
+    let lines = x1000(&format!("{}\\\n", &long_line()[..1520]));
+    let err = load(&format!("[Section]\nsetting1={lines}\n")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Syntax);
+}
+
+#[test]
+fn multiple_sections() {
+    let data = "[Section]\nsetting1=2\n[NoWarnSection]\nsetting1=3\n[WarnSection]\nsetting1=3\n[X-Section]\nsetting1=3\n";
+    assert_eq!(setting1(data), "2");
+}
+
+#[test]
+fn byte_order_mark() {
+    assert_eq!(setting1("\u{feff}[Section]\nsetting1=1\n"), "1");
+}
+
+#[test]
+fn byte_order_mark_before_comment() {
+    // the comment check sees the BOM, so the line isn't a comment and its trailing backslash continues it into the
+    // section header, which makes everything an assignment outside of a section
+    let unit = load("\u{feff}# comment\\\n[Section]\nsetting1=1\n").unwrap();
+    assert_eq!(unit.len(), 0);
+
+    // without the continuation the line is only an assignment without '='
+    assert_eq!(setting1("\u{feff}# comment\n[Section]\nsetting1=1\n"), "1");
+}
+
+#[test]
+fn byte_order_mark_after_first_line() {
+    // the BOM is stripped from the first line which has one
+    assert_eq!(setting1("\n\u{feff}[Section]\nsetting1=1\n"), "1");
+
+    // but only once, a later one is part of the line, so this isn't a section header
+    let unit = load("\u{feff}[Section]\n\u{feff}[Other]\nsetting1=1\n").unwrap();
+    assert_eq!(unit.len(), 1);
+    assert_eq!(unit.lookup_last("Section", "setting1").as_deref(), Some("1"));
+}
+
+#[test]
+fn line_endings() {
+    assert_eq!(setting1("[Section]\r\nsetting1=1\r\n"), "1");
+    assert_eq!(setting1("[Section]\rsetting1=1\r"), "1");
+    assert_eq!(setting1("[Section]\n\rsetting1=1\n\r"), "1");
+    assert_eq!(setting1("[Section]\r\nsetting1=1\\\r\n2\r\n"), "1 2");
+}
+
+#[test]
+fn repeated_line_endings_are_empty_lines() {
+    // only a combination of *different* markers ends a single line, so the empty line ends the continuation
+    let data = "[Section]\r\rsetting1=1\\\r\r2\n";
+    let unit = load(data).unwrap();
+    let values: Vec<String> = unit.lookup_all("Section", "setting1").collect();
+    assert_eq!(values, ["1"]);
+}
+
+#[test]
+fn nul_line_ending() {
+    assert_eq!(setting1("[Section]\0setting1=1\0"), "1");
+}
+
+#[test]
+fn invalid_section_header() {
+    let err = load("[Section\nsetting1=1\n").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Syntax);
+    assert_eq!(err.line(), Some(1));
+}
+
+#[test]
+fn invalid_section_names() {
+    for header in ["[Sec\"tion]", "[Sec'tion]", "[Sec\\tion]", "[Sec\ttion]", "[Sec\x7ftion]"] {
+        let err = load(&format!("{header}\nsetting1=1\n")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Syntax, "{header:?}");
+    }
+}
+
+#[test]
+fn empty_section_name_is_ignored() {
+    let unit = load("[]\nsetting1=1\n[Section]\nsetting1=2\n").unwrap();
+    assert_eq!(unit.len(), 1);
+    assert_eq!(setting1("[]\nsetting1=1\n[Section]\nsetting1=2\n"), "2");
+}